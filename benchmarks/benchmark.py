@@ -1,70 +1,92 @@
-import subprocess
-import time
-from pathlib import Path
+"""Run the full benchmark suite and write one combined JSON report.
 
-import httpx
+Starts the bundled test server from benchmarks/rust-client once, then runs
+against it, each in its own process so RSS is measured per client:
 
-SERVER_CMD = ["go", "run", "benchmarks/server.go"]
-RUST_CLIENT_MANIFEST = Path("benchmarks/rust-client/Cargo.toml")
-RUST_BINARY = Path("benchmarks/rust-client/target/release/rust-client")
-URL = "http://localhost:8000/"
+- rust-client (reqwest directly, the transport's floor)
+- python_client.py --transport rust (httpx with rust_httpx.AsyncTransport)
+- python_client.py --transport httpx (httpx's default transport)
 
+Options are forwarded to every run, e.g.:
 
-def start_server():
-    return subprocess.Popen(
-        SERVER_CMD,
-        stdout=subprocess.DEVNULL,
-        stderr=subprocess.DEVNULL,
-    )
+    python benchmarks/benchmark.py --scenario concurrent,download --concurrency 64
+"""
 
+import argparse
+import datetime
+import json
+import platform
+import subprocess
+import sys
+from pathlib import Path
 
-def wait_for_server(timeout: float = 5.0) -> None:
-    start = time.time()
-    while time.time() - start < timeout:
-        try:
-            httpx.get(URL)
-            return
-        except httpx.HTTPError:
-            time.sleep(0.1)
-    raise RuntimeError("Server did not start in time")
+from python_client import SCENARIOS, build_rust_client, start_server
 
+PYTHON_CLIENT = Path(__file__).parent / "python_client.py"
+DEFAULT_OUTPUT = Path(__file__).parent / "results.json"
 
-def benchmark_python(requests: int) -> float:
-    with httpx.Client() as client:
-        start = time.perf_counter()
-        for _ in range(requests):
-            r = client.get(URL)
-            r.raise_for_status()
-        end = time.perf_counter()
-    return end - start
 
+def run_json(command):
+    return json.loads(subprocess.check_output(command))
 
-def benchmark_rust(requests: int) -> float:
-    if not RUST_BINARY.exists():
-        subprocess.check_call(
-            ["cargo", "build", "--release", "--manifest-path", str(RUST_CLIENT_MANIFEST)],
-            stdout=subprocess.DEVNULL,
-            stderr=subprocess.DEVNULL,
-        )
-    result = subprocess.check_output([str(RUST_BINARY), str(requests)])
-    return float(result.strip())
+
+def summarize(report):
+    print(f"{'client':<18} {'scenario':<11} {'proto':<5} {'p50 ms':>9} {'p99 ms':>9} {'req/s':>10} {'MB/s':>9}")
+    for run in report["runs"]:
+        for result in run["results"]:
+            prefix = f"{run['client']:<18} {result['scenario']:<11} {result['protocol']:<5}"
+            if "skipped" in result:
+                print(f"{prefix} skipped: {result['skipped']}")
+                continue
+            latency = result["latency_ms"]
+            throughput = result["throughput"]
+            print(
+                f"{prefix} {latency['p50']:>9.3f} {latency['p99']:>9.3f} "
+                f"{throughput['requests_per_s']:>10.0f} {throughput['bytes_per_s'] / 1e6:>9.1f}"
+            )
 
 
 def main():
-    requests = 1000
-    server = start_server()
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--scenario", default=",".join(SCENARIOS), help="comma-separated scenarios")
+    parser.add_argument("--protocol", choices=("h1", "h2", "both"), default="both")
+    parser.add_argument("--requests", type=int, default=1000)
+    parser.add_argument("--concurrency", type=int, default=32)
+    parser.add_argument("--body-size", type=int, default=1024 * 1024)
+    parser.add_argument("--warmup", type=int, default=50)
+    parser.add_argument("--output", type=Path, default=DEFAULT_OUTPUT)
+    options = parser.parse_args()
+
+    rust_client = build_rust_client()
+    server, url = start_server()
+    shared = [
+        "--scenario", options.scenario,
+        "--protocol", options.protocol,
+        "--requests", str(options.requests),
+        "--concurrency", str(options.concurrency),
+        "--body-size", str(options.body_size),
+        "--warmup", str(options.warmup),
+        "--url", url,
+    ]  # fmt: skip
     try:
-        wait_for_server()
-        py_time = benchmark_python(requests)
-        rust_time = benchmark_rust(requests)
-        speedup = py_time / rust_time
-        print(f"Python transport: {py_time:.4f}s")
-        print(f"Rust transport: {rust_time:.4f}s")
-        print(f"Speedup: {speedup:.2f}x")
+        runs = [
+            run_json([str(rust_client), *shared]),
+            run_json([sys.executable, str(PYTHON_CLIENT), "--transport", "rust", *shared]),
+            run_json([sys.executable, str(PYTHON_CLIENT), "--transport", "httpx", *shared]),
+        ]
     finally:
-        server.terminate()
+        server.kill()
         server.wait()
 
+    report = {
+        "generated_at": datetime.datetime.now(datetime.timezone.utc).isoformat(),
+        "machine": {"platform": platform.platform(), "python": platform.python_version()},
+        "runs": runs,
+    }
+    options.output.write_text(json.dumps(report, indent=2, sort_keys=True) + "\n")
+    summarize(report)
+    print(f"\nWrote {options.output}")
+
 
 if __name__ == "__main__":
     main()
@@ -0,0 +1,94 @@
+"""JSON serialization benchmark for the json_body extension.
+
+Times turning a representative nested payload into request body bytes with
+the stdlib json module, orjson (if installed) and rust_httpx's json_dumps,
+the serializer behind json_body, and prints a JSON report:
+
+    python benchmarks/json_body.py
+    python benchmarks/json_body.py --size-mb 20 --rounds 10
+
+No server is involved; the transport serializes before sending, so this is
+the part of a `json=` request that json_body replaces.
+"""
+
+import argparse
+import json
+import math
+import time
+
+import rust_httpx
+
+try:
+    import orjson
+except ImportError:
+    orjson = None
+
+
+def make_payload(size_mb):
+    """Records with strings, unicode, numbers, booleans, nulls and nesting."""
+    records = []
+    size = 0
+    i = 0
+    while size < size_mb * 1024 * 1024:
+        record = {
+            "id": i,
+            "name": f"customer {i}",
+            "city": "Zürich" if i % 3 else "東京",
+            "balance": i * 12.345,
+            "active": i % 2 == 0,
+            "referrer": None,
+            "tags": ["alpha", "beta", f"tag-{i % 17}"],
+            "address": {"street": f"{i} Main St", "zip": f"{i % 99999:05d}", "geo": [47.37 + i / 1e6, 8.54]},
+        }
+        records.append(record)
+        size += len(json.dumps(record))
+        i += 1
+    return {"records": records, "count": len(records)}
+
+
+def serializers():
+    found = {
+        "json": lambda obj: json.dumps(obj, ensure_ascii=False, separators=(",", ":")).encode(),
+        "rust_httpx": rust_httpx.AsyncTransport.json_dumps,
+    }
+    if orjson is not None:
+        found["orjson"] = orjson.dumps
+    return found
+
+
+def bench(run, rounds, body_bytes):
+    """Time `rounds` calls of `run`, handling a body of `body_bytes`."""
+    timings = []
+    for _ in range(rounds):
+        start = time.perf_counter()
+        run()
+        timings.append(time.perf_counter() - start)
+    timings.sort()
+    return {
+        "body_bytes": body_bytes,
+        "median_ms": timings[math.ceil(len(timings) / 2) - 1] * 1000,
+        "min_ms": timings[0] * 1000,
+        "mb_per_s": body_bytes / timings[0] / 1e6,
+    }
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--size-mb", type=float, default=5.0, help="approximate payload size")
+    parser.add_argument("--rounds", type=int, default=20, help="measured runs per serializer")
+    args = parser.parse_args()
+
+    payload = make_payload(args.size_mb)
+    results = {}
+    for name, dumps in serializers().items():
+        # All three must produce the same document
+        body = dumps(payload)
+        assert json.loads(body) == payload, name
+        results[name] = bench(lambda: dumps(payload), args.rounds, len(body))
+    if orjson is None:
+        results["orjson"] = {"skipped": "orjson is not installed"}
+    print(json.dumps({"payload_mb": args.size_mb, "rounds": args.rounds, "results": results}, indent=2))
+
+
+if __name__ == "__main__":
+    main()
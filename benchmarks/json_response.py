@@ -0,0 +1,57 @@
+"""JSON parsing benchmark for the parse_json extension.
+
+Times turning a representative JSON response body into Python objects with
+the stdlib json module, orjson (if installed) and rust_httpx's json_loads,
+the parser behind parse_json, and prints a JSON report:
+
+    python benchmarks/json_response.py
+    python benchmarks/json_response.py --size-mb 20 --rounds 10
+
+No server is involved; this is the `response.json()` step that parse_json
+replaces. The payload is the one json_body.py serializes.
+"""
+
+import argparse
+import json
+
+import rust_httpx
+
+from json_body import bench, make_payload
+
+try:
+    import orjson
+except ImportError:
+    orjson = None
+
+
+def parsers():
+    found = {
+        # What response.json() does with a UTF-8 body
+        "json": lambda body: json.loads(body.decode()),
+        "rust_httpx": rust_httpx.AsyncTransport.json_loads,
+    }
+    if orjson is not None:
+        found["orjson"] = orjson.loads
+    return found
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--size-mb", type=float, default=5.0, help="approximate body size")
+    parser.add_argument("--rounds", type=int, default=20, help="measured runs per parser")
+    args = parser.parse_args()
+
+    body = json.dumps(make_payload(args.size_mb), ensure_ascii=False).encode()
+    expected = json.loads(body)
+    results = {}
+    for name, loads in parsers().items():
+        # All three must produce the same objects
+        assert loads(body) == expected, name
+        results[name] = bench(lambda: loads(body), args.rounds, len(body))
+    if orjson is None:
+        results["orjson"] = {"skipped": "orjson is not installed"}
+    print(json.dumps({"payload_mb": args.size_mb, "rounds": args.rounds, "results": results}, indent=2))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,223 @@
+"""httpx benchmarks mirroring benchmarks/rust-client.
+
+Runs the same scenarios with the same options and prints a JSON report of
+the same shape, for one transport per process so that RSS figures aren't
+mixed:
+
+    python benchmarks/python_client.py --transport rust
+    python benchmarks/python_client.py --transport httpx --protocol h1
+
+Without --url the bundled test server is started from the rust-client
+binary (built on first use).
+"""
+
+import argparse
+import asyncio
+import json
+import math
+import resource
+import subprocess
+import sys
+import time
+from pathlib import Path
+
+import httpx
+
+RUST_CLIENT_MANIFEST = Path(__file__).parent / "rust-client" / "Cargo.toml"
+RUST_BINARY = Path(__file__).parent / "rust-client" / "target" / "release" / "rust-client"
+SCENARIOS = ("serial", "concurrent", "download", "stream", "upload")
+HTTP_VERSIONS = {"h1": "HTTP/1.1", "h2": "HTTP/2"}
+
+
+def build_rust_client():
+    if not RUST_BINARY.exists():
+        subprocess.check_call(
+            ["cargo", "build", "--release", "--manifest-path", str(RUST_CLIENT_MANIFEST)],
+            stdout=subprocess.DEVNULL,
+        )
+    return RUST_BINARY
+
+
+def start_server():
+    """Start the bundled server and return (process, base URL) once it listens."""
+    server = subprocess.Popen([str(build_rust_client()), "serve"], stdout=subprocess.PIPE, text=True)
+    line = server.stdout.readline().strip()
+    prefix = "listening on "
+    if not line.startswith(prefix):
+        server.kill()
+        raise RuntimeError(f"unexpected server output {line!r}")
+    return server, line[len(prefix) :]
+
+
+def peak_rss_bytes():
+    maxrss = resource.getrusage(resource.RUSAGE_SELF).ru_maxrss
+    # Linux reports kilobytes, macOS bytes
+    return maxrss if sys.platform == "darwin" else maxrss * 1024
+
+
+def latency_summary(latencies):
+    """Nearest-rank percentiles in milliseconds, as rust-client reports them."""
+    latencies = sorted(latencies)
+
+    def percentile(pct):
+        rank = math.ceil(pct / 100 * len(latencies))
+        return latencies[min(max(rank, 1), len(latencies)) - 1] * 1000
+
+    return {
+        "p50": percentile(50),
+        "p95": percentile(95),
+        "p99": percentile(99),
+        "mean": sum(latencies) / len(latencies) * 1000,
+        "max": latencies[-1] * 1000,
+    }
+
+
+def make_client(transport, protocol):
+    """Return an AsyncClient for the transport/protocol pair, or a reason it can't run."""
+    if transport == "rust":
+        import rust_httpx
+
+        if protocol == "h1":
+            return None, "AsyncTransport always uses HTTP/2 prior knowledge for cleartext URLs"
+        return httpx.AsyncClient(transport=rust_httpx.AsyncTransport(), timeout=None), None
+
+    if protocol == "h2":
+        try:
+            import h2  # noqa: F401
+        except ImportError:
+            return None, "httpx needs the h2 package for HTTP/2"
+    limits = httpx.Limits(max_connections=None, max_keepalive_connections=None)
+    pool = httpx.AsyncHTTPTransport(http1=protocol == "h1", http2=protocol == "h2", limits=limits)
+    return httpx.AsyncClient(transport=pool, timeout=None), None
+
+
+async def request(client, scenario, url, payload, http_version):
+    """Issue one request and consume its response, returning the body bytes moved."""
+    if scenario == "upload":
+        response = await client.post(url, content=payload)
+        moved = len(payload)
+    elif scenario == "stream":
+        async with client.stream("GET", url) as response:
+            moved = 0
+            async for chunk in response.aiter_raw():
+                moved += len(chunk)
+    else:
+        response = await client.get(url)
+        moved = len(response.content)
+
+    response.raise_for_status()
+    if response.http_version != http_version:
+        raise RuntimeError(f"expected {http_version}, server answered with {response.http_version}")
+    return moved
+
+
+async def drive(client, scenario, url, payload, http_version, total, concurrency):
+    """Run `total` requests with up to `concurrency` in flight; return latencies and bytes moved."""
+    issued = iter(range(total))
+    latencies = []
+    moved = 0
+
+    async def worker():
+        nonlocal moved
+        for _ in issued:
+            start = time.perf_counter()
+            moved += await request(client, scenario, url, payload, http_version)
+            latencies.append(time.perf_counter() - start)
+
+    await asyncio.gather(*(worker() for _ in range(min(concurrency, total))))
+    return latencies, moved
+
+
+async def run(base_url, scenario, protocol, options):
+    client, skipped = make_client(options.transport, protocol)
+    if client is None:
+        return {"scenario": scenario, "protocol": protocol, "skipped": skipped}
+
+    body_size = options.body_size if scenario in ("download", "stream", "upload") else 0
+    path = {"download": f"/bytes?size={body_size}", "stream": f"/bytes?size={body_size}", "upload": "/upload"}
+    url = base_url + path.get(scenario, "/")
+    payload = b"x" * body_size if scenario == "upload" else b""
+    concurrency = 1 if scenario == "serial" else options.concurrency
+    args = (client, scenario, url, payload, HTTP_VERSIONS[protocol])
+
+    async with client:
+        if options.warmup:
+            await drive(*args, options.warmup, concurrency)
+        start = time.perf_counter()
+        latencies, moved = await drive(*args, options.requests, concurrency)
+        elapsed = time.perf_counter() - start
+
+    return {
+        "scenario": scenario,
+        "protocol": protocol,
+        "requests": options.requests,
+        "concurrency": concurrency,
+        "body_size": body_size,
+        "elapsed_s": elapsed,
+        "latency_ms": latency_summary(latencies),
+        "throughput": {
+            "requests_per_s": options.requests / elapsed,
+            "bytes_per_s": moved / elapsed,
+        },
+        "peak_rss_bytes": peak_rss_bytes(),
+    }
+
+
+async def benchmark(options):
+    server = None
+    base_url = options.url
+    if base_url is None:
+        server, base_url = start_server()
+    try:
+        results = []
+        for protocol in options.protocols:
+            for scenario in options.scenarios:
+                print(f"running {scenario} over {protocol}", file=sys.stderr)
+                results.append(await run(base_url.rstrip("/"), scenario, protocol, options))
+    finally:
+        if server is not None:
+            server.kill()
+            server.wait()
+
+    return {
+        "client": "httpx+rust_httpx" if options.transport == "rust" else "httpx",
+        "server": base_url,
+        "warmup": options.warmup,
+        "results": results,
+        "peak_rss_bytes": peak_rss_bytes(),
+    }
+
+
+def parse_args(argv=None):
+    parser = argparse.ArgumentParser(description=__doc__, formatter_class=argparse.RawDescriptionHelpFormatter)
+    parser.add_argument("--transport", choices=("rust", "httpx"), default="rust")
+    parser.add_argument("--scenario", default=",".join(SCENARIOS), help="comma-separated scenarios")
+    parser.add_argument("--protocol", choices=("h1", "h2", "both"), default="both")
+    parser.add_argument("--requests", type=int, default=1000)
+    parser.add_argument("--concurrency", type=int, default=32)
+    parser.add_argument("--body-size", type=int, default=1024 * 1024)
+    parser.add_argument("--warmup", type=int, default=50)
+    parser.add_argument("--url", help="benchmark an already running server instead of the bundled one")
+    parser.add_argument("--output", type=Path, help="also write the JSON report here")
+    options = parser.parse_args(argv)
+
+    options.scenarios = options.scenario.split(",")
+    unknown = set(options.scenarios) - set(SCENARIOS)
+    if unknown:
+        parser.error(f"unknown scenario {sorted(unknown)[0]!r}")
+    options.protocols = ["h1", "h2"] if options.protocol == "both" else [options.protocol]
+    options.requests = max(options.requests, 1)
+    options.concurrency = max(options.concurrency, 1)
+    return options
+
+
+def main(argv=None):
+    options = parse_args(argv)
+    report = json.dumps(asyncio.run(benchmark(options)), indent=2, sort_keys=True)
+    print(report)
+    if options.output:
+        options.output.write_text(report + "\n")
+
+
+if __name__ == "__main__":
+    main()
@@ -1,16 +1,382 @@
-use std::time::Instant;
+//! Benchmark harness for the reqwest client that backs the transport
+//!
+//! Runs each scenario over HTTP/1.1 and HTTP/2 against a bundled test
+//! server (started as a child process unless `--url` points elsewhere) and
+//! prints one JSON report to stdout. Run `rust-client --help` for options.
+
+mod server;
+mod stats;
+
+use std::io::{BufRead, BufReader};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures_util::StreamExt;
+use serde_json::{json, Value};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+const USAGE: &str = "\
+usage: rust-client [options]
+       rust-client serve [--addr ADDR]
+
+options:
+  --scenario LIST     comma-separated: serial,concurrent,download,stream,upload (default: all)
+  --protocol P        h1, h2 or both (default: both)
+  --requests N        measured requests per scenario (default: 1000)
+  --concurrency N     in-flight requests, except for serial (default: 32)
+  --body-size BYTES   body size for download, stream and upload (default: 1048576)
+  --warmup N          unmeasured requests before each scenario (default: 50)
+  --url URL           benchmark an already running server instead of the bundled one
+  --output PATH       also write the JSON report to PATH";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scenario {
+    /// Tiny GETs, one at a time
+    Serial,
+    /// Tiny GETs, `concurrency` at a time
+    Concurrent,
+    /// Large buffered downloads
+    Download,
+    /// Large downloads consumed chunk by chunk
+    Stream,
+    /// Large uploads
+    Upload,
+}
+
+impl Scenario {
+    const ALL: [Scenario; 5] = [
+        Scenario::Serial,
+        Scenario::Concurrent,
+        Scenario::Download,
+        Scenario::Stream,
+        Scenario::Upload,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Scenario::Serial => "serial",
+            Scenario::Concurrent => "concurrent",
+            Scenario::Download => "download",
+            Scenario::Stream => "stream",
+            Scenario::Upload => "upload",
+        }
+    }
+
+    fn moves_body(self) -> bool {
+        matches!(self, Scenario::Download | Scenario::Stream | Scenario::Upload)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Http1,
+    Http2,
+}
+
+impl Protocol {
+    fn name(self) -> &'static str {
+        match self {
+            Protocol::Http1 => "h1",
+            Protocol::Http2 => "h2",
+        }
+    }
+
+    fn version(self) -> reqwest::Version {
+        match self {
+            Protocol::Http1 => reqwest::Version::HTTP_11,
+            Protocol::Http2 => reqwest::Version::HTTP_2,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Options {
+    scenarios: Vec<Scenario>,
+    protocols: Vec<Protocol>,
+    requests: usize,
+    concurrency: usize,
+    body_size: usize,
+    warmup: usize,
+    url: Option<String>,
+    output: Option<PathBuf>,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options {
+            scenarios: Scenario::ALL.to_vec(),
+            protocols: vec![Protocol::Http1, Protocol::Http2],
+            requests: 1000,
+            concurrency: 32,
+            body_size: 1024 * 1024,
+            warmup: 50,
+            url: None,
+            output: None,
+        };
+
+        while let Some(flag) = args.next() {
+            if flag == "-h" || flag == "--help" {
+                return Err(String::new());
+            }
+            let value = args.next().ok_or_else(|| format!("{flag} needs a value"))?;
+            let number = || value.parse::<usize>().map_err(|_| format!("{flag}: invalid number {value:?}"));
+            match flag.as_str() {
+                "--scenario" => {
+                    options.scenarios = value
+                        .split(',')
+                        .map(|name| {
+                            Scenario::ALL
+                                .into_iter()
+                                .find(|scenario| scenario.name() == name)
+                                .ok_or_else(|| format!("unknown scenario {name:?}"))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                "--protocol" => {
+                    options.protocols = match value.as_str() {
+                        "h1" => vec![Protocol::Http1],
+                        "h2" => vec![Protocol::Http2],
+                        "both" => vec![Protocol::Http1, Protocol::Http2],
+                        _ => return Err(format!("unknown protocol {value:?}")),
+                    };
+                }
+                "--requests" => options.requests = number()?.max(1),
+                "--concurrency" => options.concurrency = number()?.max(1),
+                "--body-size" => options.body_size = number()?,
+                "--warmup" => options.warmup = number()?,
+                "--url" => options.url = Some(value.trim_end_matches('/').to_string()),
+                "--output" => options.output = Some(PathBuf::from(value)),
+                _ => return Err(format!("unknown option {flag:?}")),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Everything a worker needs to issue one scenario's requests
+#[derive(Clone)]
+struct Job {
+    client: reqwest::Client,
+    scenario: Scenario,
+    version: reqwest::Version,
+    url: String,
+    payload: Bytes,
+}
+
+impl Job {
+    /// Issue one request and consume its response, returning the body bytes moved
+    async fn request(&self) -> Result<u64, Error> {
+        let request = match self.scenario {
+            Scenario::Upload => self.client.post(&self.url).body(self.payload.clone()),
+            _ => self.client.get(&self.url),
+        };
+        let response = request.send().await?.error_for_status()?;
+        if response.version() != self.version {
+            return Err(format!("expected {:?}, server answered with {:?}", self.version, response.version()).into());
+        }
+
+        match self.scenario {
+            Scenario::Upload => {
+                response.bytes().await?;
+                Ok(self.payload.len() as u64)
+            }
+            Scenario::Stream => {
+                let mut received = 0;
+                let mut chunks = response.bytes_stream();
+                while let Some(chunk) = chunks.next().await {
+                    received += chunk?.len() as u64;
+                }
+                Ok(received)
+            }
+            _ => Ok(response.bytes().await?.len() as u64),
+        }
+    }
+}
+
+/// Run `total` requests of `job` with up to `concurrency` in flight
+///
+/// Returns each request's latency (send to last body byte) and the total
+/// body bytes moved.
+async fn drive(job: &Job, total: usize, concurrency: usize) -> Result<(Vec<Duration>, u64), Error> {
+    let issued = Arc::new(AtomicUsize::new(0));
+    let workers: Vec<_> = (0..concurrency.min(total))
+        .map(|_| {
+            let job = job.clone();
+            let issued = issued.clone();
+            tokio::spawn(async move {
+                let mut latencies = Vec::new();
+                let mut moved = 0;
+                while issued.fetch_add(1, Ordering::Relaxed) < total {
+                    let start = Instant::now();
+                    moved += job.request().await?;
+                    latencies.push(start.elapsed());
+                }
+                Ok::<_, Error>((latencies, moved))
+            })
+        })
+        .collect();
+
+    let mut latencies = Vec::with_capacity(total);
+    let mut moved = 0;
+    for worker in workers {
+        let (worker_latencies, worker_moved) = worker.await??;
+        latencies.extend(worker_latencies);
+        moved += worker_moved;
+    }
+    Ok((latencies, moved))
+}
+
+async fn run(base_url: &str, scenario: Scenario, protocol: Protocol, options: &Options) -> Result<Value, Error> {
+    let builder = reqwest::Client::builder().tcp_nodelay(true);
+    let client = match protocol {
+        Protocol::Http1 => builder.http1_only(),
+        Protocol::Http2 => builder.http2_prior_knowledge().http2_adaptive_window(true),
+    }
+    .build()?;
+
+    let body_size = if scenario.moves_body() { options.body_size } else { 0 };
+    let path = match scenario {
+        Scenario::Serial | Scenario::Concurrent => "/".to_string(),
+        Scenario::Download | Scenario::Stream => format!("/bytes?size={body_size}"),
+        Scenario::Upload => "/upload".to_string(),
+    };
+    let job = Job {
+        client,
+        scenario,
+        version: protocol.version(),
+        url: format!("{base_url}{path}"),
+        payload: Bytes::from(vec![b'x'; if scenario == Scenario::Upload { body_size } else { 0 }]),
+    };
+    let concurrency = if scenario == Scenario::Serial { 1 } else { options.concurrency };
+
+    if options.warmup > 0 {
+        drive(&job, options.warmup, concurrency).await?;
+    }
+    let start = Instant::now();
+    let (mut latencies, moved) = drive(&job, options.requests, concurrency).await?;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    Ok(json!({
+        "scenario": scenario.name(),
+        "protocol": protocol.name(),
+        "requests": options.requests,
+        "concurrency": concurrency,
+        "body_size": body_size,
+        "elapsed_s": elapsed,
+        "latency_ms": stats::latency_summary(&mut latencies),
+        "throughput": {
+            "requests_per_s": options.requests as f64 / elapsed,
+            "bytes_per_s": moved as f64 / elapsed,
+        },
+        "peak_rss_bytes": stats::peak_rss_bytes(),
+    }))
+}
+
+/// The bundled server running as a child process, killed on drop
+struct Server(Child);
+
+impl Server {
+    /// Start the server and wait until it listens, returning it and its URL
+    fn spawn() -> Result<(Self, String), Error> {
+        let mut child = Command::new(std::env::current_exe()?)
+            .arg("serve")
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or("server stdout not captured")?;
+        let server = Server(child);
+
+        let mut line = String::new();
+        BufReader::new(stdout).read_line(&mut line)?;
+        let url = line
+            .trim_end()
+            .strip_prefix("listening on ")
+            .ok_or_else(|| format!("unexpected server output {line:?}"))?
+            .to_string();
+        Ok((server, url))
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+async fn benchmark(options: Options) -> Result<Value, Error> {
+    // Keep the bundled server alive for the whole run
+    let (_server, base_url) = match &options.url {
+        Some(url) => (None, url.clone()),
+        None => {
+            let (server, url) = Server::spawn()?;
+            (Some(server), url)
+        }
+    };
+
+    let mut results = Vec::new();
+    for &protocol in &options.protocols {
+        for &scenario in &options.scenarios {
+            eprintln!("running {} over {}", scenario.name(), protocol.name());
+            results.push(run(&base_url, scenario, protocol, &options).await?);
+        }
+    }
+
+    Ok(json!({
+        "client": "reqwest",
+        "server": base_url,
+        "warmup": options.warmup,
+        "results": results,
+        "peak_rss_bytes": stats::peak_rss_bytes(),
+    }))
+}
 
 #[tokio::main]
 async fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    let iterations: usize = args.get(1).and_then(|s| s.parse().ok()).unwrap_or(1000);
-    let client = reqwest::Client::new();
-    let url = "http://localhost:8000/";
-    let start = Instant::now();
-    for _ in 0..iterations {
-        let resp = client.get(url).send().await.unwrap();
-        resp.bytes().await.unwrap();
+    let mut args = std::env::args().skip(1).peekable();
+    if args.peek().map(String::as_str) == Some("serve") {
+        args.next();
+        let addr: SocketAddr = match (args.next().as_deref(), args.next()) {
+            (Some("--addr"), Some(addr)) => addr.parse().expect("invalid --addr"),
+            _ => "127.0.0.1:0".parse().unwrap(),
+        };
+        if let Err(e) = server::serve(addr).await {
+            eprintln!("server failed: {e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let options = match Options::parse(args) {
+        Ok(options) => options,
+        Err(message) if message.is_empty() => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(message) => {
+            eprintln!("{message}\n\n{USAGE}");
+            std::process::exit(2);
+        }
+    };
+
+    let output = options.output.clone();
+    let report = match benchmark(options).await {
+        Ok(report) => serde_json::to_string_pretty(&report).expect("report serializes"),
+        Err(e) => {
+            eprintln!("benchmark failed: {e}");
+            std::process::exit(1);
+        }
+    };
+    println!("{report}");
+    if let Some(path) = output {
+        if let Err(e) = std::fs::write(&path, format!("{report}\n")) {
+            eprintln!("failed to write {}: {e}", path.display());
+            std::process::exit(1);
+        }
     }
-    let elapsed = start.elapsed().as_secs_f64();
-    println!("{elapsed}");
 }
@@ -0,0 +1,115 @@
+//! Test server bundled with the harness, so results don't depend on
+//! whatever else is listening on the machine.
+//!
+//! Serves HTTP/1.1 and cleartext HTTP/2 (prior knowledge) on one port:
+//!
+//! - `GET /` returns `Hello, World!`
+//! - `GET /bytes?size=N` returns `N` bytes, written in 64 KiB frames
+//! - `POST /upload` drains the request body and returns its size
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http_body_util::combinators::BoxBody;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Body, Frame, Incoming, SizeHint};
+use hyper::service::service_fn;
+use hyper::{Method, Request, Response, StatusCode};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use tokio::net::TcpListener;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+static CHUNK: [u8; CHUNK_SIZE] = [b'x'; CHUNK_SIZE];
+
+/// Bind to `addr` and serve until the process exits
+///
+/// The bound address is printed as `listening on http://ADDR` once the
+/// socket accepts connections, so a parent process can wait for that line.
+pub async fn serve(addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("listening on http://{}", listener.local_addr()?);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        stream.set_nodelay(true)?;
+        tokio::spawn(async move {
+            let mut builder = auto::Builder::new(TokioExecutor::new());
+            builder.http2().adaptive_window(true).max_concurrent_streams(1024);
+            // Connection errors (e.g. the client hanging up) end only that connection
+            let _ = builder.serve_connection(TokioIo::new(stream), service_fn(handle)).await;
+        });
+    }
+}
+
+async fn handle(request: Request<Incoming>) -> Result<Response<BoxBody<Bytes, Infallible>>, hyper::Error> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/") => Response::new(full("Hello, World!")),
+        (&Method::GET, "/bytes") => match size_param(request.uri().query()) {
+            Some(size) => Response::new(Repeat { remaining: size }.boxed()),
+            None => status(StatusCode::BAD_REQUEST),
+        },
+        (&Method::POST, "/upload") => {
+            let mut body = request.into_body();
+            let mut received = 0u64;
+            while let Some(frame) = body.frame().await {
+                if let Some(data) = frame?.data_ref() {
+                    received += data.len() as u64;
+                }
+            }
+            Response::new(full(received.to_string()))
+        }
+        _ => status(StatusCode::NOT_FOUND),
+    };
+    Ok(response)
+}
+
+fn size_param(query: Option<&str>) -> Option<u64> {
+    query?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("size="))
+        .and_then(|size| size.parse().ok())
+}
+
+fn full(body: impl Into<Bytes>) -> BoxBody<Bytes, Infallible> {
+    Full::new(body.into()).boxed()
+}
+
+fn status(status: StatusCode) -> Response<BoxBody<Bytes, Infallible>> {
+    let mut response = Response::new(full(Bytes::new()));
+    *response.status_mut() = status;
+    response
+}
+
+/// A body of `remaining` bytes, produced without allocating
+struct Repeat {
+    remaining: u64,
+}
+
+impl Body for Repeat {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, Infallible>>> {
+        if self.remaining == 0 {
+            return Poll::Ready(None);
+        }
+        let len = self.remaining.min(CHUNK_SIZE as u64) as usize;
+        self.remaining -= len as u64;
+        Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(&CHUNK[..len])))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
@@ -0,0 +1,42 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+
+/// Latency percentiles (milliseconds) over one scenario's requests
+pub fn latency_summary(latencies: &mut [Duration]) -> Value {
+    latencies.sort_unstable();
+    let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+    let total: Duration = latencies.iter().sum();
+    json!({
+        "p50": ms(percentile(latencies, 50.0)),
+        "p95": ms(percentile(latencies, 95.0)),
+        "p99": ms(percentile(latencies, 99.0)),
+        "mean": ms(total / latencies.len().max(1) as u32),
+        "max": ms(latencies.last().copied().unwrap_or_default()),
+    })
+}
+
+/// Nearest-rank percentile of sorted `values`
+fn percentile(values: &[Duration], pct: f64) -> Duration {
+    if values.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (pct / 100.0 * values.len() as f64).ceil() as usize;
+    values[rank.clamp(1, values.len()) - 1]
+}
+
+/// Peak resident set size of this process so far, in bytes
+pub fn peak_rss_bytes() -> u64 {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // SAFETY: `usage` is a valid, writable rusage
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) } != 0 {
+        return 0;
+    }
+    let maxrss = usage.ru_maxrss as u64;
+    // Linux reports kilobytes, macOS bytes
+    if cfg!(target_os = "macos") {
+        maxrss
+    } else {
+        maxrss * 1024
+    }
+}
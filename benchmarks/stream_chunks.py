@@ -0,0 +1,63 @@
+"""Per-chunk overhead of reading a streamed response body.
+
+Streams a body of many small chunks from MockTransport, so no network is
+involved, and times reading it through the response stream's async
+iterator, the path httpx takes for `aiter_bytes()`. Prints a JSON report:
+
+    python benchmarks/stream_chunks.py
+    python benchmarks/stream_chunks.py --chunks 10000 --rounds 50
+"""
+
+import argparse
+import asyncio
+import json
+import math
+import time
+
+import httpcore
+import rust_httpx
+
+URL = "https://bench.example/stream"
+
+
+async def read_stream(transport):
+    response = await transport.handle_async_request(httpcore.Request("GET", URL))
+    count = 0
+    async for _ in response.stream:
+        count += 1
+    return count
+
+
+async def run(chunks, chunk_size, rounds):
+    transport = rust_httpx.MockTransport()
+    transport.add_route("GET", URL, chunks=[b"x" * chunk_size] * chunks)
+    # Unmeasured, so the runtime's threads are up before timing starts
+    assert await read_stream(transport) == chunks
+
+    timings = []
+    for _ in range(rounds):
+        start = time.perf_counter()
+        await read_stream(transport)
+        timings.append(time.perf_counter() - start)
+    timings.sort()
+    median = timings[math.ceil(len(timings) / 2) - 1]
+    return {
+        "chunks": chunks,
+        "chunk_size": chunk_size,
+        "median_ms": median * 1000,
+        "min_ms": timings[0] * 1000,
+        "us_per_chunk": median / chunks * 1e6,
+    }
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--chunks", type=int, default=1000, help="chunks per body")
+    parser.add_argument("--chunk-size", type=int, default=1024, help="bytes per chunk")
+    parser.add_argument("--rounds", type=int, default=20, help="measured bodies")
+    args = parser.parse_args()
+    print(json.dumps(asyncio.run(run(args.chunks, args.chunk_size, args.rounds)), indent=2))
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,307 @@
+//! `httpx-transport-diag`: request a URL through the transport's client stack
+//!
+//! Answers "is it the network or the app" with the code path the Python
+//! transport uses, not curl: the configuration is parsed by the same
+//! functions, connections go through the same resolver, and the request is
+//! sent by a client built by the same `client.rs` code (retries included).
+//! The report covers a direct DNS/TCP/TLS probe of the origin (phase
+//! timings, negotiated TLS and ALPN, certificate chain) and the request
+//! itself (status, HTTP version, timings), or the classified error.
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{HeaderName, HeaderValue};
+use reqwest::{Method, Url};
+use rust_httpx::client::{check_http_version, create_sync_client, init_tracing, ClientConfig};
+use rust_httpx::config::{
+    parse_client_timeout, parse_dns_override, parse_nameserver, parse_proxy, parse_required_http_version, read_ca_file,
+    ConfigError,
+};
+use rust_httpx::errors::TransportError;
+use rust_httpx::pool::ConnectionPool;
+use rust_httpx::probe::{check_origin, OriginCheck};
+
+const USAGE: &str = "\
+usage: httpx-transport-diag [options] URL
+
+Options mirror the transport's keyword arguments:
+  -X, --method METHOD          request method (default: GET)
+  -H, --header 'NAME: VALUE'   default_headers entry; repeatable
+      --proxy URL              proxy
+      --insecure               verify=False
+      --ca-file PATH           ca_file
+      --http2                  require_http_version=\"2\"
+      --http-version VERSION   require_http_version
+      --resolve HOST=IP[,IP]   dns_overrides entry; repeatable
+      --nameserver ADDR        nameservers entry; repeatable
+      --timeout SECONDS        request timeout (default: 30)
+      --max-redirects N        max_redirects
+      --no-follow-redirects    follow_redirects=False
+      --retries N              total attempts per request (default: 3)
+      --disable-keepalive      disable_keepalive=True
+      --sync                   use the SyncTransport client instead of the AsyncTransport one
+  -v, --verbose                log the client's internals to stdout";
+
+struct Args {
+    url: Url,
+    method: Method,
+    config: ClientConfig,
+    sync: bool,
+    verbose: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let invalid = |e: ConfigError| e.to_string();
+    let mut url = None;
+    let mut method = Method::GET;
+    let mut config = ClientConfig::default();
+    let mut sync = false;
+    let mut verbose = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => return Err(String::new()),
+            "-X" | "--method" => {
+                let name = value()?;
+                method = Method::from_bytes(name.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| format!("Invalid method {:?}", name))?;
+            }
+            "-H" | "--header" => {
+                let header = value()?;
+                let (name, value) = header
+                    .split_once(':')
+                    .ok_or_else(|| format!("Invalid header {:?} (expected 'NAME: VALUE')", header))?;
+                let name = HeaderName::from_bytes(name.trim().as_bytes())
+                    .map_err(|e| format!("Invalid header name {:?}: {}", name, e))?;
+                let value = HeaderValue::from_str(value.trim())
+                    .map_err(|e| format!("Invalid header value for {}: {}", name, e))?;
+                config.default_headers.append(name, value);
+            }
+            "--proxy" => config.proxy = Some(parse_proxy(&value()?).map_err(invalid)?),
+            "--insecure" => config.verify = false,
+            "--disable-keepalive" => config.disable_keepalive = true,
+            "--ca-file" => config.ca_bundle = Some(read_ca_file(Path::new(&value()?)).map_err(invalid)?),
+            "--http2" => config.require_http_version = Some(reqwest::Version::HTTP_2),
+            "--http-version" => {
+                config.require_http_version = Some(parse_required_http_version(&value()?).map_err(invalid)?)
+            }
+            "--resolve" => {
+                let entry = value()?;
+                let (host, addresses) = entry
+                    .split_once('=')
+                    .ok_or_else(|| format!("Invalid --resolve {:?} (expected HOST=IP[,IP])", entry))?;
+                let addresses: Vec<&str> = addresses.split(',').collect();
+                let (host, ips) = parse_dns_override(host, &addresses).map_err(invalid)?;
+                config.dns.overrides.insert(host, ips);
+            }
+            "--nameserver" => config.dns.nameservers.push(parse_nameserver(&value()?).map_err(invalid)?),
+            "--timeout" => {
+                let seconds = value()?;
+                let parsed = seconds.parse().map_err(|_| format!("Invalid timeout {:?}", seconds))?;
+                config.timeout = parse_client_timeout("timeout", parsed).map_err(invalid)?;
+            }
+            "--max-redirects" => {
+                let count = value()?;
+                config.max_redirects = count.parse().map_err(|_| format!("Invalid max_redirects {:?}", count))?;
+            }
+            "--no-follow-redirects" => config.follow_redirects = false,
+            "--retries" => {
+                let count = value()?;
+                config.retries_max_attempts = count.parse().map_err(|_| format!("Invalid retries {:?}", count))?;
+            }
+            "--sync" => sync = true,
+            "-v" | "--verbose" => verbose = true,
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+            _ if url.is_none() => url = Some(Url::parse(&arg).map_err(|e| format!("Invalid URL {:?}: {}", arg, e))?),
+            _ => return Err(format!("Unexpected argument {:?}", arg)),
+        }
+    }
+
+    let url = url.ok_or_else(|| "Missing URL".to_string())?;
+    Ok(Args { url, method, config, sync, verbose })
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
+
+fn print_config(args: &Args) {
+    let config = &args.config;
+    println!("Configuration");
+    println!("  client:          {}", if args.sync { "SyncTransport" } else { "AsyncTransport" });
+    println!(
+        "  proxy:           {}",
+        config.proxy.as_ref().map_or("from environment, if any".to_string(), Url::to_string)
+    );
+    println!("  verify:          {}", config.verify);
+    println!("  ca_file:         {}", if config.ca_bundle.is_some() { "custom bundle" } else { "system roots" });
+    println!(
+        "  require version: {}",
+        config.require_http_version.map_or("none".to_string(), |version| format!("{:?}", version))
+    );
+    for (host, ips) in &config.dns.overrides {
+        let ips: Vec<String> = ips.iter().map(ToString::to_string).collect();
+        println!("  resolve:         {} -> {}", host, ips.join(", "));
+    }
+    if !config.dns.nameservers.is_empty() {
+        let nameservers: Vec<String> = config.dns.nameservers.iter().map(ToString::to_string).collect();
+        println!("  nameservers:     {}", nameservers.join(", "));
+    }
+    println!("  timeout:         {:?}", config.timeout);
+    if config.disable_keepalive {
+        println!("  keep-alive:      disabled");
+    }
+}
+
+fn print_probe(check: &OriginCheck) {
+    let resolved: Vec<String> = check.resolved.iter().map(ToString::to_string).collect();
+    println!("  resolved:        {}", resolved.join(", "));
+    println!("  connected to:    {}", check.address);
+    println!("  dns:             {}", ms(check.timings.dns));
+    println!("  connect:         {}", ms(check.timings.connect));
+    if let Some(tls) = check.timings.tls {
+        println!("  tls handshake:   {}", ms(tls));
+        println!(
+            "  tls:             {} {}",
+            check.tls_version.unwrap_or("unknown"),
+            check.cipher_suite.as_deref().unwrap_or("")
+        );
+        println!("  alpn:            {}", check.alpn.as_deref().unwrap_or("none"));
+        for (depth, certificate) in check.certificates.iter().enumerate() {
+            println!("  certificate {}:   {}", depth, certificate.subject);
+            println!("                   issued by {}", certificate.issuer);
+            println!("                   valid {} to {}", certificate.not_before, certificate.not_after);
+        }
+    }
+}
+
+/// A failed phase: the error as the transport classifies it, plus the
+/// underlying causes the classification leaves out
+struct Failure {
+    error: TransportError,
+    causes: Vec<String>,
+}
+
+impl Failure {
+    fn new<E: std::error::Error + Into<TransportError>>(err: E) -> Self {
+        let mut causes = Vec::new();
+        let mut source = err.source();
+        while let Some(cause) = source {
+            causes.push(cause.to_string());
+            source = cause.source();
+        }
+        Self { error: err.into(), causes }
+    }
+
+    fn print(&self) {
+        println!("  error:           [{}] {}", self.error.kind(), self.error);
+        for cause in &self.causes {
+            println!("  caused by:       {}", cause);
+        }
+    }
+}
+
+/// What the request phase observed
+struct Exchange {
+    status: reqwest::StatusCode,
+    version: reqwest::Version,
+    url: Url,
+    remote_addr: Option<std::net::SocketAddr>,
+    headers: Duration,
+    total: Duration,
+    body_len: usize,
+}
+
+fn print_exchange(exchange: &Exchange) {
+    println!("  status:          {}", exchange.status);
+    println!("  http version:    {:?}", exchange.version);
+    println!("  final url:       {}", exchange.url);
+    if let Some(remote_addr) = exchange.remote_addr {
+        println!("  remote address:  {}", remote_addr);
+    }
+    println!("  headers after:   {}", ms(exchange.headers));
+    println!("  body:            {} bytes after {}", exchange.body_len, ms(exchange.total));
+}
+
+async fn request(pool: &ConnectionPool, method: Method, url: Url) -> Result<Exchange, Failure> {
+    let started = Instant::now();
+    let response = pool.client_for(&url).request(method, url).send().await.map_err(Failure::new)?;
+    let headers = started.elapsed();
+    check_http_version(pool.config().require_http_version, response.version()).map_err(Failure::new)?;
+
+    let (status, version, url, remote_addr) =
+        (response.status(), response.version(), response.url().clone(), response.remote_addr());
+    let body_len = response.bytes().await.map_err(Failure::new)?.len();
+    Ok(Exchange { status, version, url, remote_addr, headers, total: started.elapsed(), body_len })
+}
+
+async fn request_sync(pool: &ConnectionPool, method: Method, url: Url) -> Result<Exchange, Failure> {
+    let client =
+        create_sync_client(pool.config(), pool.resolver().clone(), pool.handshakes().clone()).map_err(Failure::new)?;
+    let started = Instant::now();
+    let response = client.request(method, url).send().await.map_err(Failure::new)?;
+    let headers = started.elapsed();
+    check_http_version(pool.config().require_http_version, response.version()).map_err(Failure::new)?;
+
+    let (status, version, url, remote_addr) =
+        (response.status(), response.version(), response.url().clone(), response.remote_addr());
+    let body_len = response.bytes().await.map_err(Failure::new)?.len();
+    Ok(Exchange { status, version, url, remote_addr, headers, total: started.elapsed(), body_len })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) if message.is_empty() => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.verbose {
+        init_tracing();
+    }
+
+    print_config(&args);
+    let pool = ConnectionPool::new(args.config.clone());
+    let mut failed = false;
+
+    println!("\nProbe {} (direct, without proxy)", args.url.origin().ascii_serialization());
+    match check_origin(pool.config(), pool.resolver(), pool.probe_sessions(), &args.url).await {
+        Ok(check) => {
+            print_probe(&check);
+            println!("  total:           {}", ms(check.elapsed));
+        }
+        Err(error) => {
+            Failure::new(error).print();
+            failed = true;
+        }
+    }
+
+    println!("\nRequest {} {}", args.method, args.url);
+    let result = if args.sync {
+        request_sync(&pool, args.method.clone(), args.url.clone()).await
+    } else {
+        request(&pool, args.method.clone(), args.url.clone()).await
+    };
+    match result {
+        Ok(exchange) => print_exchange(&exchange),
+        Err(failure) => {
+            failure.print();
+            failed = true;
+        }
+    }
+
+    if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
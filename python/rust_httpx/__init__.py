@@ -6,14 +6,29 @@ reqwest library with tokio and hyper for improved performance.
 """
 
 import sys
-from typing import Any, Optional, TYPE_CHECKING
+from typing import Any, Callable, Optional, Union, TYPE_CHECKING
 
 if TYPE_CHECKING:
+    import os
     import httpx
     import httpcore
+    from httpx import AsyncBaseTransport as _AsyncBaseTransport, BaseTransport as _BaseTransport
+else:
+    try:
+        from httpx import AsyncBaseTransport as _AsyncBaseTransport, BaseTransport as _BaseTransport
+    except ImportError:
+        class _AsyncBaseTransport:
+            pass
+
+        class _BaseTransport:
+            pass
 
 try:
     from ._rust_httpx import AsyncTransport as _AsyncTransport, SyncTransport as _SyncTransport
+    from ._rust_httpx import MultipartBody, MockTransport as _MockTransport
+    from ._rust_httpx import AsyncBodyWriter, ResponseBodyAccumulator
+    from ._rust_httpx import DecodingError, UnexpectedStatusError
+    from ._rust_httpx import encode_query_params
     from ._rust_httpx import __version__
 
     _RUST_AVAILABLE = True
@@ -22,11 +37,28 @@ except ImportError as e:
     _RUST_AVAILABLE = False
     _IMPORT_ERROR = e
     
+    class UnexpectedStatusError(RuntimeError):  # type: ignore[no-redef]
+        """A response status outside the request's ``expected_status`` list."""
+
+        status: int
+        body: str
+    
+    class DecodingError(ValueError):  # type: ignore[no-redef]
+        """A response body that isn't valid JSON."""
+
+        lineno: int
+        colno: int
+        pos: int
+    
+    def encode_query_params(params: Any) -> str:  # type: ignore[misc]
+        """Encode ``params`` as a URL query string."""
+        raise ImportError(f"Rust transport not available: {_IMPORT_ERROR}")
+    
     # Fallback version
     __version__ = "0.1.0"
 
 
-class AsyncTransport:
+class AsyncTransport(_AsyncBaseTransport):
     """
     High-performance async transport for httpx using Rust.
     
@@ -48,25 +80,185 @@ class AsyncTransport:
                 f"package is properly installed. Original error: {_IMPORT_ERROR}"
             )
         
-        self._transport = _AsyncTransport()
+        self._transport = _AsyncTransport(**kwargs)
     
-    async def handle_async_request(self, request: "httpcore.Request") -> "httpcore.Response":
-        """Handle an async HTTP request."""
-        if not isinstance(request.url, (str, bytes)) or not isinstance(request.method, str):
-            import httpcore
+    async def handle_async_request(  # type: ignore[override]
+        self, request: Union["httpx.Request", "httpcore.Request"]
+    ) -> Union["httpx.Response", "httpcore.Response"]:
+        """Handle an async HTTP request.
 
-            method = (
-                request.method.decode() if isinstance(request.method, (bytes, bytearray)) else request.method
-            )
+        httpx requests get httpx responses, so the transport can be passed to
+        ``httpx.AsyncClient``; httpcore requests get httpcore responses.
+        """
+        import httpcore
 
-            request = httpcore.Request(
-                method=method,
-                url=str(request.url),
-                headers=[],
-                content=request.stream,
-                extensions=request.extensions,
-            )
-        return await self._transport.handle_async_request(request)
+        if isinstance(request, httpcore.Request):
+            return await self._transport.handle_async_request(request)
+
+        from ._adapters import to_httpcore_request, to_httpx_async_response
+
+        response = await self._transport.handle_async_request(to_httpcore_request(request))
+        return to_httpx_async_response(response)
+    
+    async def request(
+        self,
+        method: Union[str, bytes],
+        url: Union[str, bytes],
+        content: Any = None,
+        headers: Any = None,
+        extensions: Optional[dict[str, Any]] = None,
+    ) -> "httpcore.Response":
+        """Send a request built from its parts, without httpx or ``httpcore.Request``.
+
+        ``content`` is bytes, a string, an iterable of either or a
+        ``MultipartBody``; ``headers`` a dict or a list of pairs. Extensions
+        work as they do for ``handle_async_request``.
+        """
+        return await self._transport.request(method, url, content, headers, extensions)
+    
+    async def fetch_json(
+        self,
+        method: Union[str, bytes],
+        url: Union[str, bytes],
+        content: Any = None,
+        headers: Any = None,
+        extensions: Optional[dict[str, Any]] = None,
+    ) -> Any:
+        """Send a request built from its parts and return its JSON body, parsed.
+
+        Raises ``ValueError`` unless the response's ``Content-Type`` is
+        ``application/json`` (or a ``+json`` type), and ``DecodingError`` if
+        the body isn't valid JSON.
+        """
+        return await self._transport.fetch_json(method, url, content, headers, extensions)
+    
+    async def stream_to_file(
+        self,
+        request: Union["httpx.Request", "httpcore.Request"],
+        path: Union[str, "os.PathLike[str]"],
+        progress: Optional[Callable[[int, Optional[int]], None]] = None,
+    ) -> int:
+        """Send a request and write its response body to ``path``.
+
+        The body goes straight from the socket to the file in Rust. If given,
+        ``progress(bytes_written, total)`` is called after each chunk, with
+        ``total`` from ``Content-Length`` or ``None``. Returns the number of
+        bytes written.
+        """
+        import httpcore
+
+        if not isinstance(request, httpcore.Request):
+            from ._adapters import to_httpcore_request
+
+            request = to_httpcore_request(request)
+        return await self._transport.stream_to_file(request, path, progress)
+    
+    async def accumulate(
+        self,
+        stream: Any,
+        max_bytes: int,
+        hash: Optional[str] = None,
+        decompress: bool = False,
+    ) -> tuple[bytes, Optional[str]]:
+        """Read a streamed response body whole in Rust, returning ``(body, checksum)``.
+
+        ``stream`` is the stream of a response sent with the ``stream``
+        extension. Raises ``ValueError`` once the body grows past
+        ``max_bytes``. ``hash`` (``"sha256"``, ``"sha384"``, ``"sha512"`` or
+        ``"crc32"``) gives a hex checksum of the body, ``None`` without one;
+        ``decompress`` gunzips a body served as a gzip file.
+        """
+        return await self._transport.accumulate(stream, max_bytes, hash, decompress)
+    
+    @staticmethod
+    def json_dumps(
+        obj: Any,
+        big_ints: str = "error",
+        nan: str = "error",
+        default: Optional[Callable[[Any], Any]] = None,
+    ) -> bytes:
+        """Serialize ``obj`` to JSON in Rust, as the ``json_body`` extension does.
+
+        ``big_ints="string"`` sends integers beyond 64 bits as strings and
+        ``nan="null"`` sends non-finite floats as null; both raise
+        ``ValueError`` by default. ``default`` converts other objects.
+        """
+        return _AsyncTransport.json_dumps(obj, big_ints, nan, default)
+    
+    @staticmethod
+    def json_loads(data: bytes) -> Any:
+        """Parse JSON ``data`` in Rust, as the ``parse_json`` extension does.
+
+        Raises ``DecodingError``, with the ``lineno``, ``colno`` and byte
+        offset ``pos`` of the problem, if ``data`` isn't valid JSON.
+        """
+        return _AsyncTransport.json_loads(data)
+    
+    async def send_batch(self, requests: list["httpcore.Request"]) -> list["httpcore.Response"]:
+        """Send requests concurrently, multiplexing same-origin requests over HTTP/2.
+
+        Responses are buffered and returned in request order; each has
+        ``extensions["shared_connection"]``.
+        """
+        return await self._transport.send_batch(requests)
+    
+    def pool(self) -> list[dict[str, Any]]:
+        """Snapshot of the pooled connections, one dict per connection."""
+        return self._transport.pool()
+    
+    def evict(self, origin: str) -> int:
+        """Drop all pooled connections to an origin such as "https://api.example.com"."""
+        return self._transport.evict(origin)
+    
+    async def check_origin(self, url: str, keep: bool = False) -> dict[str, Any]:
+        """Verify DNS, TCP, TLS and ALPN for an origin without sending a request."""
+        return await self._transport.check_origin(url, keep=keep)
+    
+    async def get_ssl_info(self, url: str) -> dict[str, Any]:
+        """Inspect the TLS certificate chain of an https origin without sending a request."""
+        return await self._transport.get_ssl_info(url)
+    
+    async def resolve(self, host: str) -> dict[str, Any]:
+        """Resolve a hostname through the transport's resolver (overrides, cache, nameservers)."""
+        return await self._transport.resolve(host)
+    
+    async def warm_dns(self, hosts: list[str]) -> dict[str, Any]:
+        """Resolve hosts ahead of first use, returning each host's resolution."""
+        return await self._transport.warm_dns(hosts)
+    
+    def resolve_stats(self) -> dict[str, Any]:
+        """Resolver cache hits, misses and hit rate."""
+        return self._transport.resolve_stats()
+    
+    def tls_stats(self) -> dict[str, Any]:
+        """TLS handshakes made, full and resumed, and the resumption rate."""
+        return self._transport.tls_stats()
+
+    def clear_dns_cache(self) -> int:
+        """Forget cached DNS answers, including NXDOMAINs; returns how many hosts were cached."""
+        return self._transport.clear_dns_cache()
+    
+    def hsts_hosts(self) -> dict[str, dict[str, Any]]:
+        """Hosts known to require HTTPS, with ``include_subdomains`` and ``expires_in`` seconds."""
+        return self._transport.hsts_hosts()
+    
+    def config(self) -> dict[str, Any]:
+        """The settings in effect, defaults included, keyed by option name; credentials are redacted."""
+        return self._transport.config()
+    
+    async def start_health_server(self, port: int) -> int:
+        """Serve GET /health on 127.0.0.1:port for liveness probes; returns the bound port.
+
+        Requires the extension to be built with the ``health-server`` feature.
+        """
+        if not hasattr(self._transport, "start_health_server"):
+            raise RuntimeError("rust_httpx was built without the health-server feature")
+        return await self._transport.start_health_server(port)
+    
+    async def stop_health_server(self) -> None:
+        """Stop the health server started by start_health_server."""
+        if hasattr(self._transport, "stop_health_server"):
+            await self._transport.stop_health_server()
     
     async def aclose(self) -> None:
         """Close the transport and clean up resources."""
@@ -82,7 +274,7 @@ class AsyncTransport:
         await self.aclose()
 
 
-class SyncTransport:
+class SyncTransport(_BaseTransport):
     """
     High-performance sync transport for httpx using Rust.
     
@@ -104,28 +296,102 @@ class SyncTransport:
                 f"package is properly installed. Original error: {_IMPORT_ERROR}"
             )
         
-        self._transport = _SyncTransport()
+        self._transport = _SyncTransport(**kwargs)
     
-    def handle_request(self, request: "httpcore.Request") -> "httpcore.Response":
-        """Handle a sync HTTP request."""
-        if not isinstance(request.url, (str, bytes)) or not isinstance(request.method, str):
-            import httpcore
+    def handle_request(  # type: ignore[override]
+        self, request: Union["httpx.Request", "httpcore.Request"]
+    ) -> Union["httpx.Response", "httpcore.Response"]:
+        """Handle a sync HTTP request.
 
-            method = (
-                request.method.decode() if isinstance(request.method, (bytes, bytearray)) else request.method
-            )
+        httpx requests get httpx responses, so the transport can be passed to
+        ``httpx.Client``; httpcore requests get httpcore responses.
+        """
+        import httpcore
 
-            request = httpcore.Request(
-                method=method,
-                url=str(request.url),
-                headers=[],
-                content=request.stream,
-                extensions=request.extensions,
-            )
-        return self._transport.handle_request(request)
+        if isinstance(request, httpcore.Request):
+            return self._transport.handle_request(request)
+
+        from ._adapters import to_httpcore_request, to_httpx_response
+
+        return to_httpx_response(self._transport.handle_request(to_httpcore_request(request)))
+    
+    def stream_to_file(
+        self, request: Union["httpx.Request", "httpcore.Request"], path: Union[str, "os.PathLike[str]"]
+    ) -> int:
+        """Send a request and write its response body to ``path``.
+
+        The body goes straight from the socket to the file without the GIL
+        held. Returns the number of bytes written.
+        """
+        import httpcore
+
+        if not isinstance(request, httpcore.Request):
+            from ._adapters import to_httpcore_request
+
+            request = to_httpcore_request(request)
+        return self._transport.stream_to_file(request, path)
+    
+    @staticmethod
+    def json_dumps(
+        obj: Any,
+        big_ints: str = "error",
+        nan: str = "error",
+        default: Optional[Callable[[Any], Any]] = None,
+    ) -> bytes:
+        """Serialize ``obj`` to JSON in Rust; see ``AsyncTransport.json_dumps``."""
+        return _SyncTransport.json_dumps(obj, big_ints, nan, default)
+    
+    @staticmethod
+    def json_loads(data: bytes) -> Any:
+        """Parse JSON ``data`` in Rust; see ``AsyncTransport.json_loads``."""
+        return _SyncTransport.json_loads(data)
+    
+    def check_origin(self, url: str, keep: bool = False) -> dict[str, Any]:
+        """Verify DNS, TCP, TLS and ALPN for an origin without sending a request."""
+        return self._transport.check_origin(url, keep=keep)
+    
+    def get_ssl_info(self, url: str) -> dict[str, Any]:
+        """Inspect the TLS certificate chain of an https origin without sending a request."""
+        return self._transport.get_ssl_info(url)
+    
+    def resolve(self, host: str) -> dict[str, Any]:
+        """Resolve a hostname through the transport's resolver (overrides, cache, nameservers)."""
+        return self._transport.resolve(host)
+    
+    def warm_dns(self, hosts: list[str]) -> dict[str, Any]:
+        """Resolve hosts ahead of first use, returning each host's resolution."""
+        return self._transport.warm_dns(hosts)
+    
+    def prefetch_connections(
+        self, urls: list[str], concurrency: Optional[int] = None
+    ) -> list[tuple[str, bool, float]]:
+        """Warm the pool with a HEAD request to each URL, at most ``concurrency`` at a time,
+        returning ``(url, success, latency_ms)`` for each.
+        """
+        return self._transport.prefetch_connections(urls, concurrency)
+    
+    def resolve_stats(self) -> dict[str, Any]:
+        """Resolver cache hits, misses and hit rate."""
+        return self._transport.resolve_stats()
+    
+    def tls_stats(self) -> dict[str, Any]:
+        """TLS handshakes made, full and resumed, and the resumption rate."""
+        return self._transport.tls_stats()
+
+    def clear_dns_cache(self) -> int:
+        """Forget cached DNS answers, including NXDOMAINs; returns how many hosts were cached."""
+        return self._transport.clear_dns_cache()
+    
+    def hsts_hosts(self) -> dict[str, dict[str, Any]]:
+        """Hosts known to require HTTPS, with ``include_subdomains`` and ``expires_in`` seconds."""
+        return self._transport.hsts_hosts()
+    
+    def config(self) -> dict[str, Any]:
+        """The settings in effect, defaults included, keyed by option name; credentials are redacted."""
+        return self._transport.config()
     
     def close(self) -> None:
-        """Close the transport and clean up resources."""
+        """Close the transport, dropping its idle connections."""
         self._transport.close()
     
     def __repr__(self) -> str:
@@ -138,6 +404,102 @@ class SyncTransport:
         self.close()
 
 
+class MockTransport(_BaseTransport, _AsyncBaseTransport):
+    """
+    Transport answering from registered routes, for tests.
+    
+    Works as both a sync and an async transport. Routes are tried in
+    registration order; unmatched requests go to ``passthrough`` (another
+    transport) if given, and raise ``LookupError`` otherwise. Every request
+    is recorded in ``requests``.
+    
+    Example:
+        mock = rust_httpx.MockTransport()
+        mock.add_route("GET", "https://api.example.com/items/*", body=b'{"id": 1}')
+        
+        with httpx.Client(transport=mock) as client:
+            assert client.get("https://api.example.com/items/1").status_code == 200
+        assert mock.requests[0]["url"] == "https://api.example.com/items/1"
+    """
+    
+    def __init__(self, passthrough: Optional[Union["AsyncTransport", "SyncTransport"]] = None) -> None:
+        if not _RUST_AVAILABLE:
+            raise ImportError(
+                f"Rust extension not available. Please ensure the rust-httpx-transport "
+                f"package is properly installed. Original error: {_IMPORT_ERROR}"
+            )
+        
+        self._transport = _MockTransport(passthrough=getattr(passthrough, "_transport", passthrough))
+    
+    def add_route(
+        self,
+        method: str,
+        url: str,
+        status: int = 200,
+        headers: Optional[dict[str, str]] = None,
+        body: Union[bytes, str, Callable[[Any], Union[bytes, str]], None] = None,
+        chunks: Optional[list[bytes]] = None,
+        delay: Optional[float] = None,
+        error: Optional[str] = None,
+    ) -> None:
+        """Register a canned response for a method (or "*") and a URL glob.
+
+        ``body`` may be a callable taking the httpcore request. ``chunks``
+        streams the response instead. ``delay`` waits (in seconds) before
+        responding. ``error`` injects a failure: "connect_timeout",
+        "connect_error" or "read_timeout" raise instead of responding, and
+        "reset" fails the body after it was sent.
+        """
+        self._transport.add_route(
+            method, url, status=status, headers=headers, body=body, chunks=chunks, delay=delay, error=error
+        )
+    
+    @property
+    def requests(self) -> list[dict[str, Any]]:
+        """Requests received so far, with method, url, headers, content and extensions."""
+        return self._transport.requests
+    
+    def reset(self) -> None:
+        """Forget recorded requests; routes are kept."""
+        self._transport.reset()
+    
+    def handle_request(  # type: ignore[override]
+        self, request: Union["httpx.Request", "httpcore.Request"]
+    ) -> Union["httpx.Response", "httpcore.Response"]:
+        """Handle a sync HTTP request; see ``SyncTransport.handle_request``."""
+        import httpcore
+
+        if isinstance(request, httpcore.Request):
+            return self._transport.handle_request(request)
+
+        from ._adapters import to_httpcore_request, to_httpx_response
+
+        return to_httpx_response(self._transport.handle_request(to_httpcore_request(request)))
+    
+    async def handle_async_request(  # type: ignore[override]
+        self, request: Union["httpx.Request", "httpcore.Request"]
+    ) -> Union["httpx.Response", "httpcore.Response"]:
+        """Handle an async HTTP request; see ``AsyncTransport.handle_async_request``."""
+        import httpcore
+
+        if isinstance(request, httpcore.Request):
+            return await self._transport.handle_async_request(request)
+
+        from ._adapters import to_httpcore_request, to_httpx_async_response
+
+        response = await self._transport.handle_async_request(to_httpcore_request(request))
+        return to_httpx_async_response(response)
+    
+    def close(self) -> None:
+        pass
+    
+    async def aclose(self) -> None:
+        pass
+    
+    def __repr__(self) -> str:
+        return f"{self.__class__.__name__}()"
+
+
 def is_available() -> bool:
     """Check if the Rust transport is available."""
     return _RUST_AVAILABLE
@@ -157,6 +519,13 @@ def get_version_info() -> dict[str, Any]:
 __all__ = [
     "AsyncTransport",
     "SyncTransport", 
+    "MultipartBody",
+    "AsyncBodyWriter",
+    "ResponseBodyAccumulator",
+    "MockTransport",
+    "UnexpectedStatusError",
+    "DecodingError",
+    "encode_query_params",
     "is_available",
     "get_version_info",
     "__version__",
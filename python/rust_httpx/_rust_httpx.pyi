@@ -0,0 +1,139 @@
+import os
+from typing import Any, Awaitable, Callable, Optional, Union
+
+import httpcore
+
+__version__: str
+
+class UnexpectedStatusError(RuntimeError):
+    status: int
+    body: str
+
+class DecodingError(ValueError):
+    lineno: int
+    colno: int
+    pos: int
+
+def encode_query_params(params: dict[str, str] | list[tuple[str, str]]) -> str: ...
+
+class AsyncTransport:
+    def __init__(self, *, shared: bool = False, **kwargs: Any) -> None: ...
+    def handle_async_request(self, request: httpcore.Request) -> Awaitable[httpcore.Response]: ...
+    def stream_to_file(
+        self,
+        request: httpcore.Request,
+        path: str | os.PathLike[str],
+        progress: Optional[Callable[[int, Optional[int]], None]] = None,
+    ) -> Awaitable[int]: ...
+    def accumulate(
+        self, stream: Any, max_bytes: int, hash: Optional[str] = None, decompress: bool = False
+    ) -> Awaitable[tuple[bytes, Optional[str]]]: ...
+    @staticmethod
+    def json_dumps(
+        obj: Any, big_ints: str = "error", nan: str = "error", default: Optional[Callable[[Any], Any]] = None
+    ) -> bytes: ...
+    @staticmethod
+    def json_loads(data: bytes) -> Any: ...
+    def request(
+        self,
+        method: str | bytes,
+        url: str | bytes,
+        content: Any = None,
+        headers: Any = None,
+        extensions: Optional[dict[str, Any]] = None,
+    ) -> Awaitable[httpcore.Response]: ...
+    def fetch_json(
+        self,
+        method: str | bytes,
+        url: str | bytes,
+        content: Any = None,
+        headers: Any = None,
+        extensions: Optional[dict[str, Any]] = None,
+    ) -> Awaitable[Any]: ...
+    def send_batch(self, requests: list[httpcore.Request]) -> Awaitable[list[httpcore.Response]]: ...
+    def pool(self) -> list[dict[str, Any]]: ...
+    def evict(self, origin: str) -> int: ...
+    def check_origin(self, url: str, keep: bool = False) -> Awaitable[dict[str, Any]]: ...
+    def get_ssl_info(self, url: str) -> Awaitable[dict[str, Any]]: ...
+    def resolve(self, host: str) -> Awaitable[dict[str, Any]]: ...
+    def warm_dns(self, hosts: list[str]) -> Awaitable[dict[str, Any]]: ...
+    def resolve_stats(self) -> dict[str, Any]: ...
+    def tls_stats(self) -> dict[str, Any]: ...
+    def clear_dns_cache(self) -> int: ...
+    def hsts_hosts(self) -> dict[str, dict[str, Any]]: ...
+    def config(self) -> dict[str, Any]: ...
+    def start_health_server(self, port: int) -> Awaitable[int]: ...
+    def stop_health_server(self) -> Awaitable[None]: ...
+    def aclose(self) -> Awaitable[None]: ...
+    def __aenter__(self) -> Awaitable["AsyncTransport"]: ...
+    def __aexit__(self, *args: Any) -> Awaitable[None]: ...
+
+class SyncTransport:
+    def __init__(self, **kwargs: Any) -> None: ...
+    def handle_request(self, request: httpcore.Request) -> httpcore.Response: ...
+    def stream_to_file(self, request: httpcore.Request, path: str | os.PathLike[str]) -> int: ...
+    @staticmethod
+    def json_dumps(
+        obj: Any, big_ints: str = "error", nan: str = "error", default: Optional[Callable[[Any], Any]] = None
+    ) -> bytes: ...
+    @staticmethod
+    def json_loads(data: bytes) -> Any: ...
+    def check_origin(self, url: str, keep: bool = False) -> dict[str, Any]: ...
+    def get_ssl_info(self, url: str) -> dict[str, Any]: ...
+    def resolve(self, host: str) -> dict[str, Any]: ...
+    def warm_dns(self, hosts: list[str]) -> dict[str, Any]: ...
+    def prefetch_connections(
+        self, urls: list[str], concurrency: Optional[int] = None
+    ) -> list[tuple[str, bool, float]]: ...
+    def resolve_stats(self) -> dict[str, Any]: ...
+    def tls_stats(self) -> dict[str, Any]: ...
+    def clear_dns_cache(self) -> int: ...
+    def hsts_hosts(self) -> dict[str, dict[str, Any]]: ...
+    def config(self) -> dict[str, Any]: ...
+    def close(self) -> None: ...
+    def __enter__(self) -> "SyncTransport": ...
+    def __exit__(self, *args: Any) -> None: ...
+
+class MultipartBody:
+    def __init__(self) -> None: ...
+    def add_field(self, name: str, value: str) -> None: ...
+    def add_file(
+        self,
+        name: str,
+        path: str,
+        filename: Optional[str] = None,
+        content_type: Optional[str] = None,
+    ) -> None: ...
+    def __len__(self) -> int: ...
+
+class AsyncBodyWriter:
+    def __init__(self) -> None: ...
+    def write(self, chunk: bytes) -> Awaitable[None]: ...
+    def aclose(self) -> Awaitable[None]: ...
+
+class ResponseBodyAccumulator:
+    max_bytes: int
+    hash_algorithm: Optional[str]
+    decompress: bool
+    def __init__(self, max_bytes: int, hash_algorithm: Optional[str] = None, decompress: bool = False) -> None: ...
+    def accumulate(self, stream: Any) -> Awaitable[tuple[bytes, Optional[str]]]: ...
+
+class MockTransport:
+    requests: list[dict[str, Any]]
+    def __init__(self, passthrough: Optional[Union[AsyncTransport, SyncTransport]] = None) -> None: ...
+    def add_route(
+        self,
+        method: str,
+        url: str,
+        status: int = 200,
+        headers: Optional[dict[str, str]] = None,
+        body: Union[bytes, str, Callable[[httpcore.Request], Union[bytes, str]], None] = None,
+        chunks: Optional[list[bytes]] = None,
+        delay: Optional[float] = None,
+        error: Optional[str] = None,
+    ) -> None: ...
+    def reset(self) -> None: ...
+    def handle_request(self, request: httpcore.Request) -> httpcore.Response: ...
+    def handle_async_request(self, request: httpcore.Request) -> Awaitable[httpcore.Response]: ...
+    def close(self) -> None: ...
+    def aclose(self) -> Awaitable[None]: ...
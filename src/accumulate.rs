@@ -0,0 +1,238 @@
+//! `ResponseBodyAccumulator`, which reads a streamed response body whole in
+//! one pass: gunzipping it if asked, failing once it grows past a size
+//! limit, and hashing it as it goes
+//!
+//! The limit and the hash apply to the bytes returned, so to the decoded
+//! body when the accumulator decompresses. Decoding writes into the same
+//! sink that counts, so a gzip bomb fails at the limit rather than after
+//! inflating whole.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::errors::TransportError;
+use crate::streaming::{lock, ByteStream, Receiver};
+
+/// Names of the supported hash algorithms, for error messages
+#[cfg(any(feature = "rustls", feature = "sigv4"))]
+const ALGORITHMS: &str = "crc32, sha256, sha384, sha512";
+#[cfg(not(any(feature = "rustls", feature = "sigv4")))]
+const ALGORITHMS: &str = "crc32";
+
+/// A checksum an accumulator can compute
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+    Crc32,
+    #[cfg(any(feature = "rustls", feature = "sigv4"))]
+    Sha(&'static ring::digest::Algorithm),
+}
+
+impl HashAlgorithm {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "crc32" => Ok(HashAlgorithm::Crc32),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            "sha256" => Ok(HashAlgorithm::Sha(&ring::digest::SHA256)),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            "sha384" => Ok(HashAlgorithm::Sha(&ring::digest::SHA384)),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            "sha512" => Ok(HashAlgorithm::Sha(&ring::digest::SHA512)),
+            _ => Err(PyValueError::new_err(format!(
+                "unsupported hash algorithm {:?}: expected one of {}",
+                name, ALGORITHMS
+            ))),
+        }
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            HashAlgorithm::Crc32 => Hasher::Crc32(flate2::Crc::new()),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            HashAlgorithm::Sha(algorithm) => Hasher::Sha(ring::digest::Context::new(algorithm)),
+        }
+    }
+}
+
+enum Hasher {
+    Crc32(flate2::Crc),
+    #[cfg(any(feature = "rustls", feature = "sigv4"))]
+    Sha(ring::digest::Context),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Crc32(crc) => crc.update(data),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            Hasher::Sha(context) => context.update(data),
+        }
+    }
+
+    /// The checksum as lowercase hex, as `hashlib`'s `hexdigest()` and
+    /// `sha256sum` print it (a CRC-32 as its 8 big-endian digits)
+    fn finish(self) -> String {
+        match self {
+            Hasher::Crc32(crc) => format!("{:08x}", crc.sum()),
+            #[cfg(any(feature = "rustls", feature = "sigv4"))]
+            Hasher::Sha(context) => context.finish().as_ref().iter().map(|byte| format!("{:02x}", byte)).collect(),
+        }
+    }
+}
+
+/// Where the body's bytes end up: counted against the limit, hashed and kept
+struct Sink {
+    data: BytesMut,
+    max_bytes: usize,
+    hasher: Option<Hasher>,
+    exceeded: bool,
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.data.len() + buf.len() > self.max_bytes {
+            self.exceeded = true;
+            return Err(std::io::Error::other("body exceeds max_bytes"));
+        }
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(buf);
+        }
+        self.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The sink's bytes, or the decoder writing into it
+enum Output {
+    Plain(Sink),
+    Gzip(flate2::write::MultiGzDecoder<Sink>),
+}
+
+impl Output {
+    fn sink(&self) -> &Sink {
+        match self {
+            Output::Plain(sink) => sink,
+            Output::Gzip(decoder) => decoder.get_ref(),
+        }
+    }
+}
+
+/// Reads a `ByteStream` whole, with a size limit, an optional checksum and
+/// optional gzip decoding, for `AsyncTransport.accumulate`
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct ResponseBodyAccumulator {
+    max_bytes: usize,
+    hash_algorithm: Option<String>,
+    hash: Option<HashAlgorithm>,
+    decompress: bool,
+}
+
+impl ResponseBodyAccumulator {
+    /// An accumulator keeping at most `max_bytes`, hashing with
+    /// `hash_algorithm` if given and gunzipping the body if `decompress`
+    pub fn new(max_bytes: usize, hash_algorithm: Option<String>, decompress: bool) -> PyResult<Self> {
+        let hash = hash_algorithm.as_deref().map(HashAlgorithm::parse).transpose()?;
+        Ok(Self { max_bytes, hash_algorithm, hash, decompress })
+    }
+
+    /// The error for a write into `sink` that failed with `e`
+    fn write_error(&self, sink: &Sink, e: std::io::Error) -> TransportError {
+        if sink.exceeded {
+            TransportError::RemoteProtocolError(format!("response body exceeds max_bytes ({} bytes)", self.max_bytes))
+        } else {
+            TransportError::RemoteProtocolError(format!("invalid gzip body: {}", e))
+        }
+    }
+
+    /// Read the chunks of a stream, see [`ByteStream::chunks`], to its end,
+    /// returning the body and its checksum
+    pub async fn read(
+        self,
+        receiver: Arc<tokio::sync::Mutex<Receiver>>,
+    ) -> Result<(Bytes, Option<String>), TransportError> {
+        let sink = Sink {
+            data: BytesMut::new(),
+            max_bytes: self.max_bytes,
+            hasher: self.hash.map(HashAlgorithm::hasher),
+            exceeded: false,
+        };
+        let mut output =
+            if self.decompress { Output::Gzip(flate2::write::MultiGzDecoder::new(sink)) } else { Output::Plain(sink) };
+
+        let mut rx = lock(&receiver).await;
+        while let Some(chunk) = rx.recv().await {
+            let chunk = chunk?;
+            let written = match &mut output {
+                Output::Plain(sink) => sink.write_all(&chunk),
+                Output::Gzip(decoder) => decoder.write_all(&chunk),
+            };
+            if let Err(e) = written {
+                // Stop the producer rather than read the rest of the body
+                rx.close();
+                return Err(self.write_error(output.sink(), e));
+            }
+        }
+
+        let sink = match output {
+            Output::Plain(sink) => sink,
+            Output::Gzip(mut decoder) => {
+                // A body cut short mid-member fails here
+                if let Err(e) = decoder.try_finish() {
+                    return Err(self.write_error(decoder.get_ref(), e));
+                }
+                decoder
+                    .finish()
+                    .map_err(|e| TransportError::RemoteProtocolError(format!("invalid gzip body: {}", e)))?
+            }
+        };
+        Ok((sink.data.freeze(), sink.hasher.map(Hasher::finish)))
+    }
+}
+
+#[pymethods]
+impl ResponseBodyAccumulator {
+    #[new]
+    #[pyo3(signature = (max_bytes, hash_algorithm=None, decompress=false))]
+    fn py_new(max_bytes: usize, hash_algorithm: Option<String>, decompress: bool) -> PyResult<Self> {
+        Self::new(max_bytes, hash_algorithm, decompress)
+    }
+
+    /// Read `stream` to its end (async), returning `(body, checksum)`;
+    /// the checksum is `None` without a `hash_algorithm`
+    ///
+    /// Fails with `RemoteProtocolError` once the body grows past
+    /// `max_bytes`, or if it should be gunzipped but isn't valid gzip.
+    pub fn accumulate<'py>(&self, py: Python<'py>, stream: Py<ByteStream>) -> PyResult<&'py PyAny> {
+        let accumulator = self.clone();
+        let receiver = stream.borrow(py).chunks();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let (body, checksum) = accumulator.read(receiver).await?;
+            Python::with_gil(|py| Ok((PyBytes::new(py, &body).to_object(py), checksum).to_object(py)))
+        })
+    }
+
+    #[getter]
+    fn max_bytes(&self) -> usize {
+        self.max_bytes
+    }
+
+    #[getter]
+    fn hash_algorithm(&self) -> Option<String> {
+        self.hash_algorithm.clone()
+    }
+
+    #[getter]
+    fn decompress(&self) -> bool {
+        self.decompress
+    }
+}
@@ -0,0 +1,231 @@
+//! Bearer tokens from a Python callable, refreshed near expiry and on 401
+
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use http::{Extensions, HeaderValue};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+
+/// Refresh a token once less than this much of its lifetime remains
+const MAX_REFRESH_MARGIN: Duration = Duration::from_secs(30);
+
+/// A token returned by `get_token`
+#[derive(Debug, Clone)]
+struct Token {
+    header: HeaderValue,
+    /// When the token should be replaced, if it expires at all
+    refresh_at: Option<Instant>,
+    /// Counts fetches, so a request rejected with an old token can tell
+    /// whether someone else has already replaced it
+    generation: u64,
+}
+
+/// Where bearer tokens come from
+///
+/// The configuration only holds the source behind this trait, so code that
+/// builds clients without Python (the diag CLI) doesn't pull in the
+/// interpreter calls of `PyTokenSource`.
+#[async_trait::async_trait]
+pub trait TokenSource: Send + Sync + std::fmt::Debug {
+    /// Fetch a new token and its lifetime, if it expires
+    async fn fetch(&self) -> std::result::Result<(String, Option<Duration>), String>;
+
+    /// Identifies the source, for comparing configurations
+    fn id(&self) -> usize;
+}
+
+/// The `auth` option: a token source and the statuses that force a refresh
+///
+/// The cached token is shared by every client built from the config.
+/// Configurations compare equal only when they share the same source.
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    source: Arc<dyn TokenSource>,
+    refresh_on: Vec<u16>,
+    cached: Arc<tokio::sync::Mutex<Option<Token>>>,
+}
+
+impl PartialEq for AuthConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.source.id() == other.source.id() && self.refresh_on == other.refresh_on
+    }
+}
+
+impl Eq for AuthConfig {}
+
+impl Hash for AuthConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.source.id().hash(state);
+        self.refresh_on.hash(state);
+    }
+}
+
+impl AuthConfig {
+    /// Parse the `auth` option: `{"get_token": callable, "refresh_on": [401]}`
+    pub fn from_py(value: &PyAny) -> PyResult<Option<Self>> {
+        if value.is_none() {
+            return Ok(None);
+        }
+        let options: &PyDict = value.downcast()?;
+        let (mut get_token, mut refresh_on) = (None, vec![401]);
+        for (key, value) in options {
+            match key.extract::<&str>()? {
+                "get_token" if value.is_callable() => get_token = Some(value.into_py(value.py())),
+                "get_token" => {
+                    return Err(pyo3::exceptions::PyValueError::new_err("Invalid auth get_token: expected a callable"));
+                }
+                "refresh_on" => {
+                    refresh_on = value.extract()?;
+                    if let Some(status) = refresh_on.iter().find(|status| !(100..=599).contains(*status)) {
+                        return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                            "Invalid auth refresh_on status: {}",
+                            status
+                        )));
+                    }
+                }
+                key => {
+                    return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                        "Unsupported auth option: {} (supported: get_token, refresh_on)",
+                        key
+                    )));
+                }
+            }
+        }
+
+        let get_token = get_token
+            .ok_or_else(|| pyo3::exceptions::PyValueError::new_err("Invalid auth options: get_token is required"))?;
+        Ok(Some(Self::new(Arc::new(PyTokenSource { get_token }), refresh_on)))
+    }
+
+    pub fn new(source: Arc<dyn TokenSource>, refresh_on: Vec<u16>) -> Self {
+        Self { source, refresh_on, cached: Arc::new(tokio::sync::Mutex::new(None)) }
+    }
+
+    /// The statuses that make the token refresh and the request go again
+    pub fn refresh_on(&self) -> &[u16] {
+        &self.refresh_on
+    }
+
+    /// The current token, fetching a new one if there is none, it is close
+    /// to expiry, or it is the `rejected` generation
+    ///
+    /// The cache stays locked while fetching, so concurrent callers wait for
+    /// that one fetch instead of starting their own.
+    async fn token(&self, rejected: Option<u64>) -> std::result::Result<Token, String> {
+        let mut cached = self.cached.lock().await;
+        if let Some(token) = &*cached {
+            let expiring = token.refresh_at.is_some_and(|refresh_at| Instant::now() >= refresh_at);
+            if !expiring && rejected != Some(token.generation) {
+                return Ok(token.clone());
+            }
+        }
+
+        let (token, lifetime) = self.source.fetch().await?;
+        let mut header = HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| "invalid token: must be printable ASCII".to_string())?;
+        header.set_sensitive(true);
+        let token = Token {
+            header,
+            refresh_at: lifetime.map(|lifetime| Instant::now() + lifetime - (lifetime / 10).min(MAX_REFRESH_MARGIN)),
+            generation: cached.as_ref().map_or(0, |token| token.generation + 1),
+        };
+        *cached = Some(token.clone());
+        Ok(token)
+    }
+}
+
+/// Tokens from a Python callable, which may be a coroutine function
+///
+/// It returns the token, or `(token, expires_in)` with the lifetime in
+/// seconds (`None` for a token that doesn't expire). A coroutine runs on
+/// the event loop of the request that needed the token.
+#[derive(Debug)]
+struct PyTokenSource {
+    get_token: PyObject,
+}
+
+#[async_trait::async_trait]
+impl TokenSource for PyTokenSource {
+    async fn fetch(&self) -> std::result::Result<(String, Option<Duration>), String> {
+        let fetch = async {
+            let (value, pending) = Python::with_gil(|py| -> PyResult<_> {
+                let value = self.get_token.call0(py)?;
+                let pending = if value.as_ref(py).hasattr("__await__")? {
+                    Some(pyo3_asyncio::tokio::into_future(value.as_ref(py))?)
+                } else {
+                    None
+                };
+                Ok((value, pending))
+            })?;
+            let value = match pending {
+                Some(pending) => pending.await?,
+                None => value,
+            };
+
+            Python::with_gil(|py| {
+                let value = value.as_ref(py);
+                match value.extract::<String>() {
+                    Ok(token) => Ok((token, None)),
+                    Err(_) => value.extract::<(String, Option<f64>)>(),
+                }
+            })
+        };
+        // A negative lifetime counts as already expired
+        let (token, expires_in) = fetch.await.map_err(|e: PyErr| e.to_string())?;
+        Ok((token, expires_in.map(|seconds| Duration::try_from_secs_f64(seconds).unwrap_or_default())))
+    }
+
+    fn id(&self) -> usize {
+        self.get_token.as_ptr() as usize
+    }
+}
+
+/// Adds the bearer token to requests and refreshes it when rejected
+///
+/// Sits outside `RetryMiddleware`. A response with a `refresh_on` status
+/// triggers one refresh and one more attempt, unless the body can't be
+/// replayed. Requests that already carry an `Authorization` header are
+/// sent as they are.
+#[derive(Debug, Clone)]
+pub struct AuthMiddleware {
+    config: AuthConfig,
+}
+
+impl AuthMiddleware {
+    pub fn new(config: AuthConfig) -> Self {
+        Self { config }
+    }
+
+    async fn token(&self, rejected: Option<u64>) -> Result<Token> {
+        self.config
+            .token(rejected)
+            .await
+            .map_err(|e| reqwest_middleware::Error::Middleware(anyhow::anyhow!("auth get_token failed: {}", e)))
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for AuthMiddleware {
+    async fn handle(&self, mut req: Request, extensions: &mut Extensions, next: Next<'_>) -> Result<Response> {
+        if req.headers().contains_key(reqwest::header::AUTHORIZATION) {
+            return next.run(req, extensions).await;
+        }
+
+        let token = self.token(None).await?;
+        let replay = req.try_clone();
+        req.headers_mut().insert(reqwest::header::AUTHORIZATION, token.header);
+        let response = next.clone().run(req, extensions).await?;
+        let Some(mut replay) = replay.filter(|_| self.config.refresh_on.contains(&response.status().as_u16())) else {
+            return Ok(response);
+        };
+
+        tracing::debug!(status = response.status().as_u16(), "refreshing rejected auth token");
+        let token = self.token(Some(token.generation)).await?;
+        replay.headers_mut().insert(reqwest::header::AUTHORIZATION, token.header);
+        next.run(replay, extensions).await
+    }
+}
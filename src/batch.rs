@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use futures::future::{try_join, try_join_all};
+use hyper_util::client::legacy::connect::HttpInfo;
+use reqwest_middleware::RequestBuilder;
+
+use crate::client::count_redirects;
+use crate::errors::TransportError;
+use crate::pool::{ConnectionPool, PartitionKey};
+use crate::streaming::read_body_with_trailers;
+
+/// A buffered response from a batch
+#[derive(Debug)]
+pub struct BatchResponse {
+    pub status: u16,
+    pub version: http::Version,
+    pub headers: reqwest::header::HeaderMap,
+    pub body: Bytes,
+    /// Whether the body was cut at `max_response_body_size`
+    pub truncated: bool,
+    /// Local/remote address pair of the connection that carried the response
+    pub connection: Option<(SocketAddr, SocketAddr)>,
+    /// Whether another response in the batch arrived on the same connection
+    pub shared_connection: bool,
+    /// Redirects followed to get the response
+    pub redirects: usize,
+}
+
+/// Send `requests` (each paired with its partition) concurrently
+///
+/// Requests are grouped by partition, and so by origin. The first request of each group is sent
+/// on its own until its response headers arrive, so its connection is
+/// established (and, over TLS, its protocol negotiated) before the rest of
+/// the group is sent. Over HTTP/2 the rest are then multiplexed onto that
+/// connection instead of racing to open connections of their own; over
+/// HTTP/1.1 they still run concurrently, on as many connections as needed.
+///
+/// Responses are returned in request order. The first failure fails the
+/// whole batch, dropping the requests still in flight.
+pub async fn send_batch(
+    pool: &ConnectionPool,
+    requests: Vec<(PartitionKey, RequestBuilder)>,
+) -> Result<Vec<BatchResponse>, TransportError> {
+    let mut groups: Vec<(PartitionKey, Vec<(usize, RequestBuilder)>)> = Vec::new();
+    for (index, (partition, request)) in requests.into_iter().enumerate() {
+        match groups.iter_mut().find(|(group_partition, _)| *group_partition == partition) {
+            Some((_, group)) => group.push((index, request)),
+            None => groups.push((partition, vec![(index, request)])),
+        }
+    }
+
+    let groups =
+        try_join_all(groups.into_iter().map(|(partition, mut group)| async move {
+            let rest = group.split_off(1);
+            let (first_index, first) = group.remove(0);
+            let first = send(pool, &partition, first).await?;
+
+            let (first, mut responses) =
+                try_join(
+                    read(pool, first),
+                    try_join_all(rest.into_iter().map(|(index, request)| {
+                        let partition = &partition;
+                        async move {
+                            Ok::<_, TransportError>((index, read(pool, send(pool, partition, request).await?).await?))
+                        }
+                    })),
+                )
+                .await?;
+            responses.push((first_index, first));
+            Ok::<_, TransportError>(responses)
+        }))
+        .await?;
+
+    let mut responses: Vec<(usize, BatchResponse)> = groups.into_iter().flatten().collect();
+    let mut per_connection: HashMap<(SocketAddr, SocketAddr), usize> = HashMap::new();
+    for connection in responses.iter().filter_map(|(_, response)| response.connection) {
+        *per_connection.entry(connection).or_default() += 1;
+    }
+    for (_, response) in &mut responses {
+        response.shared_connection = response.connection.is_some_and(|connection| per_connection[&connection] > 1);
+    }
+
+    responses.sort_by_key(|(index, _)| *index);
+    Ok(responses.into_iter().map(|(_, response)| response).collect())
+}
+
+/// Send `request`, returning its response and the redirects it followed
+async fn send(
+    pool: &ConnectionPool,
+    partition: &PartitionKey,
+    request: RequestBuilder,
+) -> Result<(reqwest::Response, usize), TransportError> {
+    let (response, redirects) = count_redirects(request.send()).await;
+    let response = response.map_err(TransportError::from)?;
+    pool.tracker().record(partition, &response);
+    Ok((response, redirects))
+}
+
+async fn read(
+    pool: &ConnectionPool,
+    (response, redirects): (reqwest::Response, usize),
+) -> Result<BatchResponse, TransportError> {
+    let connection = response.extensions().get::<HttpInfo>().map(|info| (info.local_addr(), info.remote_addr()));
+    let status = response.status().as_u16();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let (body, _, truncated) = read_body_with_trailers(response, pool.config().body_limit()).await?;
+
+    Ok(BatchResponse { status, version, headers, body, truncated, connection, shared_connection: false, redirects })
+}
@@ -0,0 +1,237 @@
+//! Minimal X.509 reading for reporting certificate chains
+//!
+//! Only the fields needed for a human-readable summary are decoded; the
+//! TLS stack has already verified the chain (or been told not to).
+
+/// Subject, issuer and validity of one certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateSummary {
+    /// Distinguished name, e.g. `CN=example.com, O=Example`
+    pub subject: String,
+    pub issuer: String,
+    /// RFC 3339 timestamps
+    pub not_before: String,
+    pub not_after: String,
+}
+
+impl CertificateSummary {
+    /// Summarize a DER-encoded certificate; `None` if it can't be decoded
+    pub fn from_der(der: &[u8]) -> Option<Self> {
+        let (_, certificate, _) = read_tlv(der, SEQUENCE)?;
+        let (_, tbs, _) = read_tlv(certificate, SEQUENCE)?;
+
+        // Skip the optional explicit version, the serial number and the
+        // signature algorithm
+        let mut rest = tbs;
+        if rest.first() == Some(&EXPLICIT_VERSION) {
+            rest = read_tlv(rest, EXPLICIT_VERSION)?.2;
+        }
+        rest = read_tlv(rest, INTEGER)?.2;
+        rest = read_tlv(rest, SEQUENCE)?.2;
+
+        let (_, issuer, rest) = read_tlv(rest, SEQUENCE)?;
+        let (_, validity, rest) = read_tlv(rest, SEQUENCE)?;
+        let (_, subject, _) = read_tlv(rest, SEQUENCE)?;
+        let (before_tag, not_before, validity) = read_any(validity)?;
+        let (after_tag, not_after, _) = read_any(validity)?;
+
+        Some(Self {
+            subject: distinguished_name(subject)?,
+            issuer: distinguished_name(issuer)?,
+            not_before: time(before_tag, not_before)?,
+            not_after: time(after_tag, not_after)?,
+        })
+    }
+}
+
+/// The DER-encoded SubjectPublicKeyInfo of a DER-encoded certificate, the
+/// part certificate pins hash; `None` if it can't be decoded
+pub fn subject_public_key_info(der: &[u8]) -> Option<&[u8]> {
+    let rest = after_subject(der)?;
+    let (_, _, after) = read_tlv(rest, SEQUENCE)?;
+    Some(&rest[..rest.len() - after.len()])
+}
+
+/// The SHA-256 hash of a DER-encoded certificate as colon-separated
+/// uppercase hex, the form `openssl x509 -fingerprint -sha256` prints
+#[cfg(feature = "rustls")]
+pub fn sha256_fingerprint(der: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, der);
+    digest.as_ref().iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<_>>().join(":")
+}
+
+/// Whether a DER-encoded OCSPResponse reports success and carries a
+/// response; its signature and the certificate status in it aren't checked
+pub fn ocsp_response_successful(der: &[u8]) -> bool {
+    let Some((_, response, _)) = read_tlv(der, SEQUENCE) else {
+        return false;
+    };
+    match read_tlv(response, ENUMERATED) {
+        Some((_, [SUCCESSFUL], rest)) => read_tlv(rest, EXPLICIT_RESPONSE_BYTES).is_some(),
+        _ => false,
+    }
+}
+
+/// The DNS names, IP addresses, URIs and email addresses in a DER-encoded
+/// certificate's subjectAltName extension, in encoded order; `None` if it
+/// can't be decoded
+pub fn subject_alt_names(der: &[u8]) -> Option<Vec<String>> {
+    // Skip the public key and the unique IDs to the optional extensions
+    let mut rest = read_tlv(after_subject(der)?, SEQUENCE)?.2;
+    let mut extensions: &[u8] = &[];
+    while !rest.is_empty() {
+        let (tag, contents, tail) = read_any(rest)?;
+        rest = tail;
+        if tag == EXPLICIT_EXTENSIONS {
+            extensions = read_tlv(contents, SEQUENCE)?.1;
+        }
+    }
+
+    while !extensions.is_empty() {
+        let (_, extension, tail) = read_tlv(extensions, SEQUENCE)?;
+        extensions = tail;
+        let (_, oid, mut value) = read_tlv(extension, OID)?;
+        if oid != SUBJECT_ALT_NAME {
+            continue;
+        }
+        // Skip the critical flag, if present
+        if value.first() == Some(&BOOLEAN) {
+            value = read_tlv(value, BOOLEAN)?.2;
+        }
+        let (_, value, _) = read_tlv(value, OCTET_STRING)?;
+        let (_, mut names, _) = read_tlv(value, SEQUENCE)?;
+        let mut result = Vec::new();
+        while !names.is_empty() {
+            let (tag, name, tail) = read_any(names)?;
+            names = tail;
+            match (tag, name.len()) {
+                (RFC822_NAME | DNS_NAME | URI, _) => result.push(String::from_utf8_lossy(name).into_owned()),
+                (IP_ADDRESS, 4) => result.push(std::net::Ipv4Addr::from(<[u8; 4]>::try_from(name).ok()?).to_string()),
+                (IP_ADDRESS, 16) => result.push(std::net::Ipv6Addr::from(<[u8; 16]>::try_from(name).ok()?).to_string()),
+                _ => {}
+            }
+        }
+        return Some(result);
+    }
+    Some(Vec::new())
+}
+
+/// The part of a DER-encoded certificate's TBSCertificate after the subject,
+/// starting with the SubjectPublicKeyInfo
+fn after_subject(der: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = read_tlv(der, SEQUENCE)?;
+    let (_, tbs, _) = read_tlv(certificate, SEQUENCE)?;
+
+    // Skip the optional explicit version, the serial number, the signature
+    // algorithm, the issuer, the validity and the subject
+    let mut rest = tbs;
+    if rest.first() == Some(&EXPLICIT_VERSION) {
+        rest = read_tlv(rest, EXPLICIT_VERSION)?.2;
+    }
+    for tag in [INTEGER, SEQUENCE, SEQUENCE, SEQUENCE, SEQUENCE] {
+        rest = read_tlv(rest, tag)?.2;
+    }
+    Some(rest)
+}
+
+const INTEGER: u8 = 0x02;
+const OID: u8 = 0x06;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const EXPLICIT_VERSION: u8 = 0xa0;
+const EXPLICIT_EXTENSIONS: u8 = 0xa3;
+const BOOLEAN: u8 = 0x01;
+const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+const EXPLICIT_RESPONSE_BYTES: u8 = 0xa0;
+/// The `successful` OCSPResponseStatus
+const SUCCESSFUL: u8 = 0;
+/// GeneralName tags (context-specific, implicit)
+const RFC822_NAME: u8 = 0x81;
+const DNS_NAME: u8 = 0x82;
+const URI: u8 = 0x86;
+const IP_ADDRESS: u8 = 0x87;
+/// id-ce-subjectAltName (2.5.29.17), as an OID body
+const SUBJECT_ALT_NAME: &[u8] = &[0x55, 0x1d, 0x11];
+
+/// Short names of the attribute types worth showing, by OID body
+const ATTRIBUTES: &[(&[u8], &str)] = &[
+    (&[0x55, 0x04, 0x03], "CN"),
+    (&[0x55, 0x04, 0x0a], "O"),
+    (&[0x55, 0x04, 0x0b], "OU"),
+    (&[0x55, 0x04, 0x07], "L"),
+    (&[0x55, 0x04, 0x08], "ST"),
+    (&[0x55, 0x04, 0x06], "C"),
+];
+
+/// Read one TLV of any tag, returning (tag, contents, remaining input)
+fn read_any(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let len = if first & 0x80 == 0 {
+        first as usize
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 || rest.len() < count {
+            return None;
+        }
+        let (bytes, tail) = rest.split_at(count);
+        rest = tail;
+        bytes.iter().fold(0, |len, byte| (len << 8) | *byte as usize)
+    };
+    if rest.len() < len {
+        return None;
+    }
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, contents, rest))
+}
+
+/// Read one TLV that must have `tag`
+fn read_tlv(input: &[u8], tag: u8) -> Option<(u8, &[u8], &[u8])> {
+    read_any(input).filter(|(found, _, _)| *found == tag)
+}
+
+/// Render the known attributes of a Name, in encoded order
+fn distinguished_name(mut name: &[u8]) -> Option<String> {
+    let mut parts = Vec::new();
+    while !name.is_empty() {
+        let (_, set, rest) = read_tlv(name, SET)?;
+        name = rest;
+        let (_, attribute, _) = read_tlv(set, SEQUENCE)?;
+        let (_, oid, value) = read_tlv(attribute, OID)?;
+        let (_, value, _) = read_any(value)?;
+        if let Some((_, short)) = ATTRIBUTES.iter().find(|(known, _)| *known == oid) {
+            parts.push(format!("{}={}", short, String::from_utf8_lossy(value)));
+        }
+    }
+    Some(parts.join(", "))
+}
+
+/// Render a UTCTime or GeneralizedTime as RFC 3339
+fn time(tag: u8, value: &[u8]) -> Option<String> {
+    let value = std::str::from_utf8(value).ok()?;
+    let digits = value.strip_suffix('Z')?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let full = match tag {
+        // Two-digit years 50-99 are 19xx (RFC 5280)
+        UTC_TIME if digits.len() == 12 => {
+            let century = if digits[..2] < *"50" { "20" } else { "19" };
+            format!("{}{}", century, digits)
+        }
+        GENERALIZED_TIME if digits.len() == 14 => digits.to_string(),
+        _ => return None,
+    };
+    Some(format!(
+        "{}-{}-{}T{}:{}:{}Z",
+        &full[..4],
+        &full[4..6],
+        &full[6..8],
+        &full[8..10],
+        &full[10..12],
+        &full[12..14]
+    ))
+}
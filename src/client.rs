@@ -1,65 +1,971 @@
-use std::sync::Arc;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
 
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 
-static CLIENT: OnceCell<Arc<ClientWithMiddleware>> = OnceCell::new();
+use crate::auth::{AuthConfig, AuthMiddleware};
+use crate::compress::Compression;
+use crate::connect::ConnectTimeoutLayer;
+use crate::decompress::DecompressMiddleware;
+use crate::dns::{DnsConfig, DnsResolver};
+use crate::errors::TransportError;
+use crate::handshake::{HandshakeLayer, Handshakes};
+use crate::hsts::HstsConfig;
+use crate::limit::Fairness;
+use crate::pin::PinConfig;
+use crate::pool::ConnectionPool;
+use crate::retry::RetryMiddleware;
+use crate::revocation::RevocationConfig;
+#[cfg(feature = "sigv4")]
+use crate::sigv4::{SigV4Config, SigV4Middleware};
+use crate::socket::{apply_socket_options, SocketOptions};
+use crate::streaming::BodyLimit;
+use crate::upload::EarlyResponseMiddleware;
+use crate::utils::{is_redirect_status, requires_body_after_redirect, DEFAULT_REDACTED_EXTENSIONS};
+use crate::wire::{WireCaptureMiddleware, SECRET_HEADERS};
+
+/// Live pools by configuration, shared by `shared=True` transports
+/// configured alike
+static POOLS: Lazy<Mutex<HashMap<ClientConfig, Weak<ConnectionPool>>>> = Lazy::new(Default::default);
+
+tokio::task_local! {
+    /// Redirects followed by the request being sent, see [`count_redirects`]
+    static REDIRECTS: Cell<usize>;
+}
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!("enable at least one TLS backend feature: `rustls` or `native-tls`");
+
+/// TLS implementation used by the HTTP client
+///
+/// Variants are only available when the matching Cargo feature is enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TlsBackend {
+    /// Pure-Rust TLS via rustls (portable, the default)
+    #[cfg(feature = "rustls")]
+    #[default]
+    Rustls,
+    /// Platform TLS stack (OpenSSL, Secure Transport, SChannel) for
+    /// integration with the OS certificate store
+    #[cfg(feature = "native-tls")]
+    #[cfg_attr(not(feature = "rustls"), default)]
+    #[cfg_attr(feature = "rustls", allow(dead_code))]
+    NativeTls,
+}
 
 /// Configuration for the HTTP client
 #[derive(Debug, Clone)]
 pub struct ClientConfig {
+    /// Keeps transports apart that would otherwise share a pool: only
+    /// transports with the same name (or none) share one
+    pub pool_name: Option<String>,
     pub timeout: Duration,
     pub pool_max_idle_per_host: usize,
     pub pool_idle_timeout: Duration,
+    /// Total attempts per request, including the first
     pub retries_max_attempts: u32,
+    /// Multiply each retry's connect timeout by this over the attempt before
+    pub connect_timeout_escalation: Option<f64>,
+    /// Follow redirects unless a request's `follow_redirects` extension says otherwise
+    pub follow_redirects: bool,
+    /// Redirects followed before failing with `TooManyRedirects`
+    pub max_redirects: usize,
     pub user_agent: String,
+    pub tls_backend: TlsBackend,
+    /// Minimum negotiated HTTP version; lower versions fail the request
+    pub require_http_version: Option<http::Version>,
+    /// Speak HTTP/2 on every connection without negotiating it, including
+    /// cleartext ones (h2c)
+    pub http2_prior_knowledge: bool,
+    /// PEM bundle of additional trusted root certificates
+    pub ca_bundle: Option<Vec<u8>>,
+    /// Send every request through this proxy instead of the one configured
+    /// by the `HTTP(S)_PROXY` environment variables
+    pub proxy: Option<reqwest::Url>,
+    /// Verify server certificates; disabling this is for debugging only
+    pub verify: bool,
+    /// Stream response bodies over this many bytes (or of unknown length)
+    /// unless the request sets the `stream` extension
+    pub auto_stream_threshold: Option<u64>,
+    /// Chunks a streamed body's producer task forwards before yielding to
+    /// the scheduler
+    pub stream_yield_interval: usize,
+    /// Bytes a streamed body may buffer ahead of its consumer, on top of
+    /// the channel's chunk limit
+    pub stream_buffer_bytes: Option<usize>,
+    /// Headers sent with every request unless the request sets them
+    pub default_headers: reqwest::header::HeaderMap,
+    pub socket_options: SocketOptions,
+    pub dns: DnsConfig,
+    /// Requests an async pool sends at once; the rest wait for a slot
+    pub max_concurrent_requests: Option<usize>,
+    /// Requests an async pool sends at once to any one host
+    pub max_concurrent_per_host: Option<usize>,
+    /// Order in which waiting requests get a slot
+    pub pool_fairness: Fairness,
+    /// Open a fresh connection for every request and close it afterwards
+    pub disable_keepalive: bool,
+    /// Upgrade requests to hosts that sent `Strict-Transport-Security`
+    pub hsts: Option<HstsConfig>,
+    /// Public keys each pinned host must present one of
+    pub pins: Option<PinConfig>,
+    /// CRLs to check and hosts that must staple an OCSP response
+    pub revocation: Option<RevocationConfig>,
+    /// Attach the server's certificate to every response
+    pub capture_certificate: bool,
+    /// Flag responses that arrive before the request body was sent in full
+    pub detect_early_responses: bool,
+    /// Send GET and HEAD requests that ask for it in TLS 1.3 early data
+    pub early_data: bool,
+    /// Attach the status and rate limit headers of non-2xx responses as
+    /// `extensions["error_detail"]`
+    pub parse_error_headers: bool,
+    /// Extension keys whose values request logs leave out
+    pub redact_extensions: Vec<String>,
+    /// Header carrying a generated idempotency key on non-idempotent
+    /// requests, which makes them safe to retry
+    pub idempotency_key: Option<http::HeaderName>,
+    /// Compress request bodies unless a request's `compress_request`
+    /// extension says otherwise
+    pub compress_request: Option<Compression>,
+    /// Smallest buffered body that gets compressed
+    pub compress_request_min_size: u64,
+    /// Largest (decoded) response body read; longer ones fail
+    pub max_response_body_size: Option<u64>,
+    /// Cut bodies over `max_response_body_size` short instead of failing
+    pub truncate_oversized: bool,
+    /// Largest decoded size of a compressed response body; larger ones fail
+    pub max_decompressed_size: Option<u64>,
+    /// Bearer tokens for async requests, refreshed near expiry and on rejection
+    pub auth: Option<AuthConfig>,
+    /// Sign every request (and every retry) with AWS SigV4
+    #[cfg(feature = "sigv4")]
+    pub sigv4: Option<SigV4Config>,
 }
 
 impl Default for ClientConfig {
     fn default() -> Self {
         Self {
+            pool_name: None,
             timeout: Duration::from_secs(30),
             pool_max_idle_per_host: 64,
             pool_idle_timeout: Duration::from_secs(90),
             retries_max_attempts: 3,
+            connect_timeout_escalation: None,
+            follow_redirects: true,
+            max_redirects: 10,
             user_agent: format!("rust-httpx-transport/{}", env!("CARGO_PKG_VERSION")),
+            tls_backend: TlsBackend::default(),
+            require_http_version: None,
+            http2_prior_knowledge: false,
+            ca_bundle: None,
+            proxy: None,
+            verify: true,
+            auto_stream_threshold: None,
+            stream_yield_interval: crate::streaming::DEFAULT_YIELD_INTERVAL,
+            stream_buffer_bytes: None,
+            default_headers: reqwest::header::HeaderMap::new(),
+            socket_options: SocketOptions::default(),
+            dns: DnsConfig::default(),
+            max_concurrent_requests: None,
+            max_concurrent_per_host: None,
+            pool_fairness: Fairness::default(),
+            disable_keepalive: false,
+            hsts: None,
+            pins: None,
+            revocation: None,
+            capture_certificate: false,
+            detect_early_responses: false,
+            early_data: false,
+            parse_error_headers: false,
+            redact_extensions: DEFAULT_REDACTED_EXTENSIONS.iter().map(|key| key.to_string()).collect(),
+            idempotency_key: None,
+            compress_request: None,
+            compress_request_min_size: crate::compress::DEFAULT_MIN_SIZE,
+            max_response_body_size: None,
+            truncate_oversized: false,
+            max_decompressed_size: Some(crate::decompress::DEFAULT_MAX_DECOMPRESSED_SIZE),
+            auth: None,
+            #[cfg(feature = "sigv4")]
+            sigv4: None,
+        }
+    }
+}
+
+/// Configurations compare field by field; `default_headers` ignores the
+/// order in which different header names were inserted.
+impl PartialEq for ClientConfig {
+    fn eq(&self, other: &Self) -> bool {
+        let ClientConfig {
+            pool_name,
+            timeout,
+            pool_max_idle_per_host,
+            pool_idle_timeout,
+            retries_max_attempts,
+            connect_timeout_escalation,
+            follow_redirects,
+            max_redirects,
+            user_agent,
+            tls_backend,
+            require_http_version,
+            http2_prior_knowledge,
+            ca_bundle,
+            proxy,
+            verify,
+            auto_stream_threshold,
+            stream_yield_interval,
+            stream_buffer_bytes,
+            default_headers,
+            socket_options,
+            dns,
+            max_concurrent_requests,
+            max_concurrent_per_host,
+            pool_fairness,
+            disable_keepalive,
+            hsts,
+            pins,
+            revocation,
+            capture_certificate,
+            detect_early_responses,
+            early_data,
+            parse_error_headers,
+            redact_extensions,
+            idempotency_key,
+            compress_request,
+            compress_request_min_size,
+            max_response_body_size,
+            truncate_oversized,
+            max_decompressed_size,
+            auth,
+            #[cfg(feature = "sigv4")]
+            sigv4,
+        } = self;
+        #[cfg(feature = "sigv4")]
+        if *sigv4 != other.sigv4 {
+            return false;
+        }
+        *pool_name == other.pool_name
+            && *timeout == other.timeout
+            && *pool_max_idle_per_host == other.pool_max_idle_per_host
+            && *pool_idle_timeout == other.pool_idle_timeout
+            && *retries_max_attempts == other.retries_max_attempts
+            && *connect_timeout_escalation == other.connect_timeout_escalation
+            && *follow_redirects == other.follow_redirects
+            && *max_redirects == other.max_redirects
+            && *user_agent == other.user_agent
+            && *tls_backend == other.tls_backend
+            && *require_http_version == other.require_http_version
+            && *http2_prior_knowledge == other.http2_prior_knowledge
+            && *ca_bundle == other.ca_bundle
+            && *proxy == other.proxy
+            && *verify == other.verify
+            && *auto_stream_threshold == other.auto_stream_threshold
+            && *stream_yield_interval == other.stream_yield_interval
+            && *stream_buffer_bytes == other.stream_buffer_bytes
+            && *default_headers == other.default_headers
+            && *socket_options == other.socket_options
+            && *dns == other.dns
+            && *max_concurrent_requests == other.max_concurrent_requests
+            && *max_concurrent_per_host == other.max_concurrent_per_host
+            && *pool_fairness == other.pool_fairness
+            && *disable_keepalive == other.disable_keepalive
+            && *hsts == other.hsts
+            && *pins == other.pins
+            && *revocation == other.revocation
+            && *capture_certificate == other.capture_certificate
+            && *detect_early_responses == other.detect_early_responses
+            && *early_data == other.early_data
+            && *parse_error_headers == other.parse_error_headers
+            && *redact_extensions == other.redact_extensions
+            && *idempotency_key == other.idempotency_key
+            && *compress_request == other.compress_request
+            && *compress_request_min_size == other.compress_request_min_size
+            && *max_response_body_size == other.max_response_body_size
+            && *truncate_oversized == other.truncate_oversized
+            && *max_decompressed_size == other.max_decompressed_size
+            && *auth == other.auth
+    }
+}
+
+impl Eq for ClientConfig {}
+
+impl ClientConfig {
+    /// The limit on response bodies, if `max_response_body_size` is set
+    pub fn body_limit(&self) -> Option<BodyLimit> {
+        self.max_response_body_size.map(|max_bytes| BodyLimit { max_bytes, truncate: self.truncate_oversized })
+    }
+
+    /// The effective settings, for `config()`, keyed by the options that
+    /// set them where there is one
+    ///
+    /// Durations are in seconds. Credentials are left out: the proxy's are
+    /// redacted, as are credential headers in `default_headers`, and the
+    /// `auth` token source and `sigv4` credentials aren't shown; CA bundles
+    /// and CRLs are summarized rather than included.
+    pub fn to_json(&self) -> serde_json::Value {
+        use base64::Engine;
+        use serde_json::json;
+
+        let ClientConfig {
+            pool_name,
+            timeout,
+            pool_max_idle_per_host,
+            pool_idle_timeout,
+            retries_max_attempts,
+            connect_timeout_escalation,
+            follow_redirects,
+            max_redirects,
+            user_agent,
+            tls_backend,
+            require_http_version,
+            http2_prior_knowledge,
+            ca_bundle,
+            proxy,
+            verify,
+            auto_stream_threshold,
+            stream_yield_interval,
+            stream_buffer_bytes,
+            default_headers,
+            socket_options,
+            dns,
+            max_concurrent_requests,
+            max_concurrent_per_host,
+            pool_fairness,
+            disable_keepalive,
+            hsts,
+            pins,
+            revocation,
+            capture_certificate,
+            detect_early_responses,
+            early_data,
+            parse_error_headers,
+            redact_extensions,
+            idempotency_key,
+            compress_request,
+            compress_request_min_size,
+            max_response_body_size,
+            truncate_oversized,
+            max_decompressed_size,
+            auth,
+            #[cfg(feature = "sigv4")]
+            sigv4,
+        } = self;
+
+        let mut headers = serde_json::Map::new();
+        for (name, value) in default_headers {
+            let value = if SECRET_HEADERS.contains(name) {
+                "[redacted]".into()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            match headers.get_mut(name.as_str()) {
+                Some(serde_json::Value::String(joined)) => *joined = format!("{}, {}", joined, value),
+                _ => {
+                    headers.insert(name.to_string(), value.into());
+                }
+            }
         }
+        let seconds = |duration: &Option<Duration>| duration.map(|duration| duration.as_secs_f64());
+        let pinned = pins.as_ref().map(|pins| {
+            pins.hosts
+                .iter()
+                .map(|(host, hashes)| {
+                    let hashes: Vec<_> = hashes
+                        .iter()
+                        .map(|hash| format!("sha256/{}", base64::engine::general_purpose::STANDARD.encode(hash)))
+                        .collect();
+                    (host.clone(), json!(hashes))
+                })
+                .collect::<serde_json::Map<_, _>>()
+        });
+
+        #[allow(unused_mut)]
+        let mut config = json!({
+            "pool_name": pool_name,
+            "timeout": timeout.as_secs_f64(),
+            "pool_max_idle_per_host": pool_max_idle_per_host,
+            "pool_idle_timeout": pool_idle_timeout.as_secs_f64(),
+            "retries_max_attempts": retries_max_attempts,
+            "connect_timeout_escalation": connect_timeout_escalation,
+            "follow_redirects": follow_redirects,
+            "max_redirects": max_redirects,
+            "user_agent": user_agent,
+            "tls_backend": match tls_backend {
+                #[cfg(feature = "rustls")]
+                TlsBackend::Rustls => "rustls",
+                #[cfg(feature = "native-tls")]
+                TlsBackend::NativeTls => "native-tls",
+            },
+            "require_http_version": require_http_version
+                .map(|version| String::from_utf8_lossy(crate::utils::http_version_bytes(version)).into_owned()),
+            "http2_prior_knowledge": http2_prior_knowledge,
+            "ca_bundle": ca_bundle.is_some(),
+            "proxy": proxy.as_ref().map(crate::config::redacted_proxy),
+            "verify": verify,
+            "auto_stream_threshold": auto_stream_threshold,
+            "stream_yield_interval": stream_yield_interval,
+            "stream_buffer_bytes": stream_buffer_bytes,
+            "default_headers": headers,
+            "socket_options": {
+                "nodelay": socket_options.nodelay,
+                "keepalive": seconds(&socket_options.keepalive),
+                "keepalive_interval": seconds(&socket_options.keepalive_interval),
+                "keepalive_retries": socket_options.keepalive_retries,
+                "local_address": socket_options.local_address.map(|address| address.to_string()),
+                "interface": socket_options.interface,
+                "user_timeout": seconds(&socket_options.user_timeout),
+                "dscp": socket_options.dscp,
+            },
+            "dns_overrides": dns.overrides
+                .iter()
+                .map(|(host, ips)| (host.clone(), json!(ips.iter().map(|ip| ip.to_string()).collect::<Vec<_>>())))
+                .collect::<serde_json::Map<_, _>>(),
+            "nameservers": dns.nameservers.iter().map(|nameserver| nameserver.to_string()).collect::<Vec<_>>(),
+            "dns_serve_stale": seconds(&dns.serve_stale),
+            "dns_cache_size": dns.cache_size,
+            "dns_min_ttl": seconds(&dns.min_ttl),
+            "dns_max_ttl": seconds(&dns.max_ttl),
+            "dns_negative_ttl": seconds(&dns.negative_ttl),
+            "max_concurrent_requests": max_concurrent_requests,
+            "max_concurrent_per_host": max_concurrent_per_host,
+            "pool_fairness": match pool_fairness {
+                Fairness::Fifo => "fifo",
+                Fairness::Lifo => "lifo",
+            },
+            "disable_keepalive": disable_keepalive,
+            "hsts": hsts.as_ref().map(|hsts| json!({
+                "path": hsts.path.as_ref().map(|path| path.display().to_string()),
+            })),
+            "pins": pinned,
+            "pins_report_only": pins.as_ref().is_some_and(|pins| pins.report_only),
+            "crls": revocation.as_ref().map_or(0, |revocation| revocation.crls.len()),
+            "require_ocsp_staple": revocation.as_ref().map(|revocation| revocation.staple_hosts.clone()),
+            "capture_certificate": capture_certificate,
+            "detect_early_responses": detect_early_responses,
+            "early_data": early_data,
+            "parse_error_headers": parse_error_headers,
+            "redact_extensions": redact_extensions,
+            "idempotency_key_header": idempotency_key.as_ref().map(|header| header.as_str()),
+            "compress_request": compress_request.map(|compression| match compression {
+                Compression::Gzip { level } => json!({"algorithm": "gzip", "level": level}),
+                Compression::Zstd { level } => json!({"algorithm": "zstd", "level": level}),
+            }),
+            "compress_request_min_size": compress_request_min_size,
+            "max_response_body_size": max_response_body_size,
+            "truncate_oversized": truncate_oversized,
+            "max_decompressed_size": max_decompressed_size,
+            "auth": auth.as_ref().map(|auth| json!({"refresh_on": auth.refresh_on()})),
+        });
+        #[cfg(feature = "sigv4")]
+        {
+            config["sigv4"] = json!(sigv4.as_ref().map(|sigv4| json!({
+                "region": sigv4.region,
+                "service": sigv4.service,
+            })));
+        }
+        config
+    }
+}
+
+impl Hash for ClientConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let ClientConfig {
+            pool_name,
+            timeout,
+            pool_max_idle_per_host,
+            pool_idle_timeout,
+            retries_max_attempts,
+            connect_timeout_escalation,
+            follow_redirects,
+            max_redirects,
+            user_agent,
+            tls_backend,
+            require_http_version,
+            http2_prior_knowledge,
+            ca_bundle,
+            proxy,
+            verify,
+            auto_stream_threshold,
+            stream_yield_interval,
+            stream_buffer_bytes,
+            default_headers,
+            socket_options,
+            dns,
+            max_concurrent_requests,
+            max_concurrent_per_host,
+            pool_fairness,
+            disable_keepalive,
+            hsts,
+            pins,
+            revocation,
+            capture_certificate,
+            detect_early_responses,
+            early_data,
+            parse_error_headers,
+            redact_extensions,
+            idempotency_key,
+            compress_request,
+            compress_request_min_size,
+            max_response_body_size,
+            truncate_oversized,
+            max_decompressed_size,
+            auth,
+            #[cfg(feature = "sigv4")]
+            sigv4,
+        } = self;
+        pool_name.hash(state);
+        timeout.hash(state);
+        pool_max_idle_per_host.hash(state);
+        pool_idle_timeout.hash(state);
+        retries_max_attempts.hash(state);
+        connect_timeout_escalation.map(f64::to_bits).hash(state);
+        follow_redirects.hash(state);
+        max_redirects.hash(state);
+        user_agent.hash(state);
+        tls_backend.hash(state);
+        require_http_version.hash(state);
+        http2_prior_knowledge.hash(state);
+        ca_bundle.hash(state);
+        proxy.hash(state);
+        verify.hash(state);
+        auto_stream_threshold.hash(state);
+        stream_yield_interval.hash(state);
+        stream_buffer_bytes.hash(state);
+        // HeaderMap isn't Hash; sort by name (keeping each name's values in
+        // order) so maps that compare equal hash equally
+        let mut headers: Vec<_> =
+            default_headers.iter().map(|(name, value)| (name.as_str(), value.as_bytes())).collect();
+        headers.sort_by_key(|(name, _)| *name);
+        headers.hash(state);
+        socket_options.hash(state);
+        dns.hash(state);
+        max_concurrent_requests.hash(state);
+        max_concurrent_per_host.hash(state);
+        pool_fairness.hash(state);
+        disable_keepalive.hash(state);
+        hsts.hash(state);
+        pins.hash(state);
+        revocation.hash(state);
+        capture_certificate.hash(state);
+        detect_early_responses.hash(state);
+        early_data.hash(state);
+        parse_error_headers.hash(state);
+        redact_extensions.hash(state);
+        idempotency_key.hash(state);
+        compress_request.hash(state);
+        compress_request_min_size.hash(state);
+        max_response_body_size.hash(state);
+        truncate_oversized.hash(state);
+        max_decompressed_size.hash(state);
+        auth.hash(state);
+        #[cfg(feature = "sigv4")]
+        sigv4.hash(state);
     }
 }
 
-/// Get or create the singleton HTTP client
-pub fn get_client() -> Arc<ClientWithMiddleware> {
-    CLIENT.get_or_init(|| {
-        create_client(ClientConfig::default())
-    }).clone()
+/// Get or create the shared connection pool for `config`
+///
+/// `shared=True` transports created with equal configurations share one
+/// pool (and so one set of connections) for as long as any of them is
+/// alive; a `pool_name` sets one apart from otherwise equal ones.
+pub fn get_pool(config: ClientConfig) -> Arc<ConnectionPool> {
+    let mut pools = POOLS.lock().unwrap();
+    if let Some(pool) = pools.get(&config).and_then(Weak::upgrade) {
+        return pool;
+    }
+    pools.retain(|_, pool| pool.strong_count() > 0);
+    let pool = Arc::new(ConnectionPool::new(config.clone()));
+    pools.insert(config, Arc::downgrade(&pool));
+    pool
 }
 
 /// Create a new HTTP client with middleware stack
-fn create_client(config: ClientConfig) -> Arc<ClientWithMiddleware> {
+///
+/// The client speaks only `http_version` when set: HTTP/1.1, or HTTP/2
+/// with prior knowledge. Otherwise it negotiates HTTP/2 or HTTP/1.1 over
+/// TLS with ALPN, and speaks HTTP/1.1 over cleartext. The handshakes of
+/// its connections are filed with `handshakes`.
+pub fn create_client(
+    config: ClientConfig,
+    resolver: DnsResolver,
+    handshakes: Handshakes,
+    http_version: Option<http::Version>,
+) -> Arc<ClientWithMiddleware> {
     // Build the base reqwest client
-    let base_client = reqwest::Client::builder()
+    let builder = reqwest::Client::builder()
+        .timeout(config.timeout)
+        .pool_max_idle_per_host(config.pool_max_idle_per_host)
+        .pool_idle_timeout(config.pool_idle_timeout)
+        .user_agent(config.user_agent.clone())
+        .redirect(redirect_policy(&config))
+        .tls_info(true)
+        .dns_resolver(Arc::new(resolver));
+    let builder = match http_version {
+        Some(http::Version::HTTP_11) => builder.http1_only(),
+        Some(_) => builder.http2_prior_knowledge(),
+        None => builder,
+    };
+
+    let builder = match config.tls_backend {
+        #[cfg(feature = "rustls")]
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+        #[cfg(feature = "native-tls")]
+        TlsBackend::NativeTls => builder.use_native_tls(),
+    };
+
+    let builder =
+        root_certificates(&config).into_iter().fold(builder, |builder, cert| builder.add_root_certificate(cert));
+    let builder = apply_socket_options!(builder, &config.socket_options);
+    // Limits connecting to each attempt's connect timeout, see `connect.rs`
+    let builder = builder.connector_layer(ConnectTimeoutLayer);
+    let builder = builder.connector_layer(HandshakeLayer::new(handshakes.clone()));
+    let builder = if config.disable_keepalive { without_keepalive(builder) } else { builder };
+    let builder = match &config.proxy {
+        Some(proxy) => builder.proxy(reqwest::Proxy::all(proxy.clone()).expect("Invalid proxy URL")),
+        None => builder,
+    };
+    // Over TLS the client offers the protocols it was built for
+    let alpn = match http_version {
+        Some(http::Version::HTTP_11) => vec![b"http/1.1".to_vec()],
+        Some(_) => vec![b"h2".to_vec()],
+        None => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+    };
+    let builder = with_tls_config(builder, &config, &handshakes, alpn);
+
+    let base_client =
+        builder.danger_accept_invalid_certs(!config.verify).build().expect("Failed to create reqwest client");
+
+    // Outside the retry middleware, so a rejected token is refreshed once
+    // rather than on every retried attempt
+    let client = ClientBuilder::new(base_client);
+    let client = match &config.auth {
+        Some(auth) => client.with(AuthMiddleware::new(auth.clone())),
+        None => client,
+    };
+    let client = client.with(RetryMiddleware::new(
+        config.retries_max_attempts,
+        config.idempotency_key.clone(),
+        config.connect_timeout_escalation,
+    ));
+    // Inside the retry middleware, so every attempt is signed afresh
+    #[cfg(feature = "sigv4")]
+    let client = match &config.sigv4 {
+        Some(sigv4) => client.with(SigV4Middleware::new(sigv4.clone())),
+        None => client,
+    };
+    let client = client.with(DecompressMiddleware::new(config.max_decompressed_size));
+    let client = client.with(WireCaptureMiddleware::new(config.user_agent.clone()));
+    // Innermost, tracking the body of each attempt as it is sent
+    let client = if config.detect_early_responses { client.with(EarlyResponseMiddleware) } else { client };
+
+    Arc::new(client.build())
+}
+
+/// Create the HTTP client for the sync transport
+///
+/// The sync transport blocks on this client's futures on the shared runtime
+/// rather than using `reqwest::blocking`, whose responses discard trailers.
+/// It retries and signs requests as the async client does, and files the
+/// handshakes of its connections with `handshakes`.
+pub fn create_sync_client(
+    config: &ClientConfig,
+    resolver: DnsResolver,
+    handshakes: Handshakes,
+) -> reqwest::Result<ClientWithMiddleware> {
+    let builder = reqwest::Client::builder()
+        .dns_resolver(Arc::new(resolver))
         .timeout(config.timeout)
         .pool_max_idle_per_host(config.pool_max_idle_per_host)
         .pool_idle_timeout(config.pool_idle_timeout)
-        .user_agent(config.user_agent)
-        .http2_prior_knowledge()
-        .use_rustls_tls()
-        .build()
-        .expect("Failed to create reqwest client");
+        .user_agent(config.user_agent.clone())
+        .redirect(redirect_policy(config))
+        .tls_info(true);
+    let builder = if config.http2_prior_knowledge { builder.http2_prior_knowledge() } else { builder };
+
+    let builder = match config.tls_backend {
+        #[cfg(feature = "rustls")]
+        TlsBackend::Rustls => builder.use_rustls_tls(),
+        #[cfg(feature = "native-tls")]
+        TlsBackend::NativeTls => builder.use_native_tls(),
+    };
+
+    let builder =
+        root_certificates(config).into_iter().fold(builder, |builder, cert| builder.add_root_certificate(cert));
+    let builder = apply_socket_options!(builder, &config.socket_options);
+    let builder = builder.connector_layer(ConnectTimeoutLayer);
+    let builder = builder.connector_layer(HandshakeLayer::new(handshakes.clone()));
+    let builder = if config.disable_keepalive { without_keepalive(builder) } else { builder };
+    let builder = match &config.proxy {
+        Some(proxy) => builder.proxy(reqwest::Proxy::all(proxy.clone())?),
+        None => builder,
+    };
+    let alpn =
+        if config.http2_prior_knowledge { vec![b"h2".to_vec()] } else { vec![b"h2".to_vec(), b"http/1.1".to_vec()] };
+    let builder = with_tls_config(builder, config, &handshakes, alpn);
+    let base_client = builder.danger_accept_invalid_certs(!config.verify).build()?;
+
+    let client = ClientBuilder::new(base_client).with(RetryMiddleware::new(
+        config.retries_max_attempts,
+        config.idempotency_key.clone(),
+        config.connect_timeout_escalation,
+    ));
+    // Inside the retry middleware, so every attempt is signed afresh
+    #[cfg(feature = "sigv4")]
+    let client = match &config.sigv4 {
+        Some(sigv4) => client.with(SigV4Middleware::new(sigv4.clone())),
+        None => client,
+    };
+    let client = client.with(WireCaptureMiddleware::new(config.user_agent.clone()));
+    // Innermost, tracking the body of each attempt as it is sent
+    let client = if config.detect_early_responses { client.with(EarlyResponseMiddleware) } else { client };
+
+    Ok(client.build())
+}
+
+/// Fail when the negotiated HTTP version is below the configured minimum
+pub fn check_http_version(required: Option<http::Version>, negotiated: http::Version) -> Result<(), TransportError> {
+    match required {
+        Some(required) if negotiated < required => Err(TransportError::RemoteProtocolError(format!(
+            "server negotiated {:?} but {:?} is required",
+            negotiated, required
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// How much of an unexpected response's body its error quotes
+const STATUS_SNIPPET_LEN: usize = 512;
 
-    // For now, just use the basic client without complex middleware
-    // TODO: Add proper middleware integration in future versions
-    let client = ClientBuilder::new(base_client).build();
+/// Fail when the response status isn't in the request's `expected_status`
+///
+/// The error carries the status and up to 512 bytes of the body, read on
+/// a best-effort basis; the rest of the body is dropped.
+pub async fn check_expected_status(
+    expected: Option<&[u16]>,
+    mut response: reqwest::Response,
+) -> Result<reqwest::Response, TransportError> {
+    let status = response.status().as_u16();
+    if expected.is_none_or(|expected| expected.contains(&status)) {
+        return Ok(response);
+    }
+    let mut body = Vec::new();
+    while body.len() < STATUS_SNIPPET_LEN {
+        match response.chunk().await {
+            Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+            _ => break,
+        }
+    }
+    Err(unexpected_status(status, &body))
+}
+
+/// The error for an unexpected `status`, quoting the start of `body`
+pub fn unexpected_status(status: u16, body: &[u8]) -> TransportError {
+    TransportError::UnexpectedStatus {
+        status,
+        snippet: String::from_utf8_lossy(&body[..body.len().min(STATUS_SNIPPET_LEN)]).into_owned(),
+    }
+}
+
+/// Keep no idle connections and ask servers to close each connection after
+/// its response, so every request gets a fresh one
+///
+/// hyper drops the `Connection` header from HTTP/2 requests, where it is
+/// not allowed.
+fn without_keepalive(builder: reqwest::ClientBuilder) -> reqwest::ClientBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::CONNECTION, reqwest::header::HeaderValue::from_static("close"));
+    builder.pool_max_idle_per_host(0).default_headers(headers)
+}
+
+/// Follow up to `max_redirects` redirects, as `Policy::limited` does,
+/// noting each one for [`count_redirects`]
+fn redirect_policy(config: &ClientConfig) -> reqwest::redirect::Policy {
+    if !config.follow_redirects {
+        return reqwest::redirect::Policy::none();
+    }
+    let max_redirects = config.max_redirects;
+    reqwest::redirect::Policy::custom(move |attempt| {
+        if !is_redirect_status(attempt.status().as_u16()) {
+            return attempt.stop();
+        }
+        // The URLs redirected from so far, this one included
+        let followed = attempt.previous().len();
+        if followed > max_redirects {
+            return attempt.error("too many redirects");
+        }
+        let _ = REDIRECTS.try_with(|redirects| redirects.set(followed));
+        attempt.follow()
+    })
+}
+
+/// Run `send`, a request being sent, and count the redirects it follows
+///
+/// reqwest follows redirects inside the send future, so the count is kept
+/// in a task-local that `send` sees while it is polled.
+pub async fn count_redirects<F: Future>(send: F) -> (F::Output, usize) {
+    REDIRECTS
+        .scope(Cell::new(0), async {
+            let output = send.await;
+            (output, REDIRECTS.with(Cell::get))
+        })
+        .await
+}
+
+/// Note a redirect that came back as the response although redirects are
+/// followed
+///
+/// reqwest can't send a streamed request body (or one tracked for early
+/// responses) again, so it returns the redirects that keep a request's
+/// body, 307 and 308 ones, instead of following them.
+pub fn note_unfollowed_redirect(method: &reqwest::Method, follow_redirects: bool, response: &reqwest::Response) {
+    let status = response.status().as_u16();
+    let keeps_body = !matches!(*method, reqwest::Method::GET | reqwest::Method::HEAD)
+        && !requires_body_after_redirect(method, status);
+    if follow_redirects
+        && is_redirect_status(status)
+        && keeps_body
+        && response.headers().contains_key(reqwest::header::LOCATION)
+    {
+        tracing::debug!(
+            status,
+            url = %response.url(),
+            "redirect not followed: the request body can't be sent again"
+        );
+    }
+}
+
+/// Parse the configured CA bundle
+///
+/// The bundle is validated when the config is built from Python, so an
+/// unparseable bundle here simply contributes no certificates.
+fn root_certificates(config: &ClientConfig) -> Vec<reqwest::Certificate> {
+    config.ca_bundle.as_deref().and_then(|pem| reqwest::Certificate::from_pem_bundle(pem).ok()).unwrap_or_default()
+}
+
+/// The roots a rustls config built here trusts: the bundled web PKI roots
+/// and the configured CA bundle
+#[cfg(feature = "rustls")]
+pub fn rustls_roots(config: &ClientConfig) -> Result<rustls::RootCertStore, TransportError> {
+    use rustls::pki_types::pem::PemObject;
+    use rustls::pki_types::CertificateDer;
 
-    Arc::new(client)
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    if let Some(pem) = &config.ca_bundle {
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            let cert = cert.map_err(|e| TransportError::SSLError(format!("invalid CA bundle: {}", e)))?;
+            roots.add(cert).map_err(|e| TransportError::SSLError(format!("invalid CA certificate: {}", e)))?;
+        }
+    }
+    Ok(roots)
+}
+
+/// Whether `config` needs a verifier of its own: one that checks `pins`,
+/// `crls` or `require_ocsp_staple`
+pub fn has_custom_verifier(config: &ClientConfig) -> bool {
+    config.pins.is_some() || config.revocation.is_some()
+}
+
+/// The custom verifier of a client that needs one: the transport's roots,
+/// checked against its CRLs, then its pins and stapling requirements, with
+/// `provider`'s crypto
+///
+/// CRLs only cover the server's own certificate, and one no CRL covers is
+/// accepted, so CRLs for a private CA leave other hosts alone.
+#[cfg(feature = "rustls")]
+pub fn custom_verifier(
+    config: &ClientConfig,
+    provider: Arc<rustls::crypto::CryptoProvider>,
+) -> Result<Arc<dyn rustls::client::danger::ServerCertVerifier>, TransportError> {
+    use rustls::client::danger::ServerCertVerifier;
+    use rustls::pki_types::CertificateRevocationListDer;
+
+    let mut webpki =
+        rustls::client::WebPkiServerVerifier::builder_with_provider(Arc::new(rustls_roots(config)?), provider.clone());
+    if let Some(revocation) = config.revocation.as_ref().filter(|revocation| !revocation.crls.is_empty()) {
+        webpki = webpki
+            .with_crls(revocation.crls.iter().map(|crl| CertificateRevocationListDer::from(crl.clone())))
+            .only_check_end_entity_revocation()
+            .allow_unknown_revocation_status();
+    }
+    let webpki = webpki.build().map_err(|e| TransportError::SSLError(e.to_string()))?;
+    let mut verifier: Arc<dyn ServerCertVerifier> = match &config.pins {
+        Some(pins) => Arc::new(crate::pin::PinningVerifier::new(webpki, pins.clone())),
+        None => webpki,
+    };
+    if let Some(revocation) = config.revocation.as_ref().filter(|revocation| !revocation.staple_hosts.is_empty()) {
+        verifier = Arc::new(crate::revocation::StapleVerifier::new(verifier, revocation.clone()));
+    }
+    Ok(verifier)
+}
+
+/// Have `builder` use the rustls config `handshakes` shares with the
+/// rustls backend, offering `alpn_protocols` as reqwest's own TLS config
+/// would
+///
+/// reqwest can neither add a verifier to the TLS config it builds nor have
+/// it note the handshakes it makes (see `handshake.rs`), so a rustls
+/// client gets one built here instead. A custom verifier's options are
+/// only accepted from Python once the config builds, so building it can't
+/// fail.
+fn with_tls_config(
+    builder: reqwest::ClientBuilder,
+    config: &ClientConfig,
+    #[cfg_attr(not(feature = "rustls"), allow(unused_variables))] handshakes: &Handshakes,
+    #[cfg_attr(not(feature = "rustls"), allow(unused_variables))] alpn_protocols: Vec<Vec<u8>>,
+) -> reqwest::ClientBuilder {
+    match config.tls_backend {
+        #[cfg(feature = "rustls")]
+        TlsBackend::Rustls => builder
+            .use_preconfigured_tls(handshakes.tls_config(config, alpn_protocols).expect("Failed to create TLS config")),
+        #[cfg(feature = "native-tls")]
+        TlsBackend::NativeTls => builder,
+    }
+}
+
+/// Run every transport on `runtime` rather than on a runtime of their own
+///
+/// For Rust applications that embed this crate alongside their own tokio
+/// runtime; it isn't exposed to Python, since a `Runtime` (or `Handle`)
+/// can't be extracted from a Python object. The runtime is fixed on first
+/// use, so this must be called before the first request; later calls fail
+/// with a `RuntimeError`. pyo3-asyncio keeps the `Runtime` itself, so a
+/// `Handle` won't do.
+#[cfg(feature = "custom-runtime")]
+pub fn set_tokio_runtime(runtime: &'static tokio::runtime::Runtime) -> pyo3::PyResult<()> {
+    pyo3_asyncio::tokio::init_with_runtime(runtime).map_err(|()| {
+        pyo3::exceptions::PyRuntimeError::new_err(
+            "set_tokio_runtime must be called before the tokio runtime is first used",
+        )
+    })
 }
 
 /// Initialize tracing subscriber for observability
 pub fn init_tracing() {
     use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
-    
-    let _guard = tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().with_target(false))
-        .try_init();
-} 
\ No newline at end of file
+
+    let _guard = tracing_subscriber::registry().with(tracing_subscriber::fmt::layer().with_target(false)).try_init();
+}
+
+#[cfg(all(test, feature = "custom-runtime"))]
+mod tests {
+    use super::*;
+
+    fn runtime() -> &'static tokio::runtime::Runtime {
+        Box::leak(Box::new(tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap()))
+    }
+
+    #[test]
+    fn tokio_runtime_is_set_once() {
+        let first = runtime();
+        assert!(set_tokio_runtime(first).is_ok());
+        assert!(std::ptr::eq(pyo3_asyncio::tokio::get_runtime(), first));
+        assert!(set_tokio_runtime(runtime()).is_err());
+        assert!(std::ptr::eq(pyo3_asyncio::tokio::get_runtime(), first));
+    }
+}
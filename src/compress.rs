@@ -0,0 +1,196 @@
+//! Compression of request bodies for the `compress_request` option
+
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use async_compression::tokio::bufread::{GzipEncoder, ZstdEncoder};
+use bytes::Bytes;
+use futures::{Stream, StreamExt, TryStreamExt};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// Smallest buffered body that is compressed, unless configured otherwise
+pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+/// The gzip level used unless configured otherwise, balancing speed and size
+pub const DEFAULT_GZIP_LEVEL: u32 = 6;
+
+/// The zstd level used unless configured otherwise, zstd's own default
+pub const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+/// A `Content-Encoding` the transport can apply to request bodies, with its
+/// compression level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Compression {
+    /// Levels 0 (store only) to 9 (smallest)
+    Gzip { level: u32 },
+    /// Levels 1 (fastest) to 22 (smallest)
+    Zstd { level: u32 },
+}
+
+impl Compression {
+    fn content_encoding(self) -> &'static str {
+        match self {
+            Compression::Gzip { .. } => "gzip",
+            Compression::Zstd { .. } => "zstd",
+        }
+    }
+
+    fn level(self) -> u32 {
+        match self {
+            Compression::Gzip { level } | Compression::Zstd { level } => level,
+        }
+    }
+}
+
+/// Sizes of a compressed request body
+///
+/// A streamed body is compressed as it is sent, so its counts grow until
+/// the last chunk has gone out.
+#[derive(Debug, Clone)]
+pub struct CompressionStats {
+    compression: Compression,
+    original: Arc<AtomicU64>,
+    compressed: Arc<AtomicU64>,
+}
+
+impl CompressionStats {
+    fn new(compression: Compression) -> Self {
+        Self { compression, original: Default::default(), compressed: Default::default() }
+    }
+
+    /// The `request_compression` response extension
+    pub fn to_json(&self) -> serde_json::Value {
+        let original = self.original.load(Ordering::Relaxed);
+        let compressed = self.compressed.load(Ordering::Relaxed);
+        serde_json::json!({
+            "encoding": self.compression.content_encoding(),
+            "level": self.compression.level(),
+            "original_bytes": original,
+            "compressed_bytes": compressed,
+            "ratio": if original == 0 { 1.0 } else { compressed as f64 / original as f64 },
+        })
+    }
+}
+
+/// The compression for a request's body, if any
+///
+/// A `compress_request` extension (a name, an `{"algorithm", "level"}`
+/// dict, or `None` to opt out), as `requested`, overrides the transport's
+/// option. Bodies that already carry a `Content-Encoding` are sent as they
+/// are.
+pub fn request_compression(
+    configured: Option<Compression>,
+    requested: Option<Option<Compression>>,
+    headers: &HeaderMap,
+) -> Option<Compression> {
+    if headers.contains_key(CONTENT_ENCODING) {
+        return None;
+    }
+    requested.unwrap_or(configured)
+}
+
+/// Mark `headers` as describing a body compressed with `compression`
+///
+/// The caller's `Content-Length` described the original body; the new one
+/// is set from the compressed body, or the body is sent chunked.
+fn set_encoding(compression: Compression, headers: &mut HeaderMap) {
+    headers.remove(CONTENT_LENGTH);
+    headers.insert(CONTENT_ENCODING, HeaderValue::from_static(compression.content_encoding()));
+}
+
+/// Compress a buffered body and update `headers` to match
+pub fn compress_body(
+    compression: Compression,
+    body: &[u8],
+    headers: &mut HeaderMap,
+) -> std::io::Result<(Vec<u8>, CompressionStats)> {
+    let compressed = match compression {
+        Compression::Gzip { level } => {
+            let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+            encoder.write_all(body)?;
+            encoder.finish()?
+        }
+        Compression::Zstd { level } => zstd::bulk::compress(body, level as i32)?,
+    };
+    set_encoding(compression, headers);
+
+    let stats = CompressionStats::new(compression);
+    stats.original.store(body.len() as u64, Ordering::Relaxed);
+    stats.compressed.store(compressed.len() as u64, Ordering::Relaxed);
+    Ok((compressed, stats))
+}
+
+/// Compress a streamed body chunk by chunk as it is sent, and update
+/// `headers` to match
+pub fn compress_stream<S>(
+    compression: Compression,
+    stream: S,
+    headers: &mut HeaderMap,
+) -> (reqwest::Body, CompressionStats)
+where
+    S: Stream<Item = Result<Bytes, reqwest::Error>> + Send + 'static,
+{
+    set_encoding(compression, headers);
+
+    let stats = CompressionStats::new(compression);
+    let (original, compressed) = (stats.original.clone(), stats.compressed.clone());
+    let input = stream
+        .inspect_ok(move |chunk| {
+            original.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        })
+        .map_err(std::io::Error::other);
+    let input = StreamReader::new(input);
+    let level = async_compression::Level::Precise(compression.level() as i32);
+    let encoded = match compression {
+        Compression::Gzip { .. } => ReaderStream::new(GzipEncoder::with_quality(input, level)).left_stream(),
+        Compression::Zstd { .. } => ReaderStream::new(ZstdEncoder::with_quality(input, level)).right_stream(),
+    };
+    let output = encoded.inspect_ok(move |chunk| {
+        compressed.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+    });
+    (reqwest::Body::wrap_stream(output), stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload() -> Vec<u8> {
+        (0..2000).flat_map(|i| format!("{{\"id\": {}, \"name\": \"item-{}\"}},", i, i).into_bytes()).collect()
+    }
+
+    #[test]
+    fn zstd_round_trips_buffered_bodies() {
+        let body = payload();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(body.len()));
+        let (compressed, stats) = compress_body(Compression::Zstd { level: 3 }, &body, &mut headers).unwrap();
+        assert_eq!(zstd::stream::decode_all(&compressed[..]).unwrap(), body);
+        assert_eq!(headers.get(CONTENT_ENCODING).unwrap(), "zstd");
+        assert!(!headers.contains_key(CONTENT_LENGTH));
+        let stats = stats.to_json();
+        assert_eq!(stats["encoding"], "zstd");
+        assert_eq!(stats["original_bytes"], body.len());
+        assert_eq!(stats["compressed_bytes"], compressed.len());
+    }
+
+    #[tokio::test]
+    async fn zstd_round_trips_streamed_bodies() {
+        use http_body_util::BodyExt;
+
+        let body = payload();
+        let chunks = body.chunks(4096).map(|chunk| Ok(Bytes::copy_from_slice(chunk))).collect::<Vec<_>>();
+        let mut headers = HeaderMap::new();
+        let (compressed, stats) =
+            compress_stream(Compression::Zstd { level: 19 }, futures::stream::iter(chunks), &mut headers);
+        assert_eq!(headers.get(CONTENT_ENCODING).unwrap(), "zstd");
+        let compressed = compressed.collect().await.unwrap().to_bytes();
+        assert_eq!(zstd::stream::decode_all(&compressed[..]).unwrap(), body);
+        let stats = stats.to_json();
+        assert_eq!(stats["level"], 19);
+        assert_eq!(stats["original_bytes"], body.len());
+        assert_eq!(stats["compressed_bytes"], compressed.len());
+    }
+}
@@ -0,0 +1,104 @@
+//! Per-attempt connect timeouts, from the `connect` entry of the httpx
+//! `timeout` extension, escalated across retries by the
+//! `connect_timeout_escalation` option
+//!
+//! reqwest only has a client-wide connect timeout. The timeout for an
+//! attempt is kept in a task-local while the retry middleware sends it,
+//! and [`ConnectTimeoutLayer`] reads it when reqwest asks its connector
+//! for a new connection. hyper calls the connector from the task sending
+//! the request, so it sees the attempt's timeout; a pooled connection
+//! needs no connecting and isn't limited.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tower::{Layer, Service};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+tokio::task_local! {
+    /// Connect timeout of the attempt being sent, see [`with_connect_timeout`]
+    static CONNECT_TIMEOUT: Option<Duration>;
+}
+
+/// A request's connect timeout, carried in its extensions to the retry
+/// middleware
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectTimeout {
+    /// The timeout of the first attempt
+    pub base: Duration,
+    /// The request's overall timeout, which no attempt's connect timeout
+    /// exceeds
+    pub deadline: Duration,
+}
+
+impl ConnectTimeout {
+    /// The connect timeout of the `attempt`th attempt (from 1): the base
+    /// timeout times `escalation` for each earlier attempt, up to the
+    /// deadline
+    pub fn for_attempt(&self, escalation: Option<f64>, attempt: u32) -> Duration {
+        let factor = escalation.unwrap_or(1.0).powi(attempt.saturating_sub(1) as i32);
+        Duration::try_from_secs_f64(self.base.as_secs_f64() * factor).unwrap_or(Duration::MAX).min(self.deadline)
+    }
+}
+
+/// Run `send`, an attempt at a request, limiting each connection it opens
+/// to `timeout`
+pub async fn with_connect_timeout<F: Future>(timeout: Option<Duration>, send: F) -> F::Output {
+    CONNECT_TIMEOUT.scope(timeout, send).await
+}
+
+/// Connector layer failing a connection that takes longer than the
+/// attempt's connect timeout
+///
+/// The error is a `TimedOut` I/O error, which reqwest reports as a connect
+/// timeout, so the attempt is retried like any connect failure.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConnectTimeoutLayer;
+
+impl<S> Layer<S> for ConnectTimeoutLayer {
+    type Service = ConnectTimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConnectTimeoutService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConnectTimeoutService<S> {
+    inner: S,
+}
+
+impl<S, Req> Service<Req> for ConnectTimeoutService<S>
+where
+    S: Service<Req, Error = BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // Read now: the connection may finish on a task of its own
+        let timeout = CONNECT_TIMEOUT.try_with(|timeout| *timeout).ok().flatten();
+        let connecting = self.inner.call(req);
+        Box::pin(async move {
+            let Some(timeout) = timeout else {
+                return connecting.await;
+            };
+            match tokio::time::timeout(timeout, connecting).await {
+                Ok(connected) => connected,
+                Err(_) => Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("connecting timed out after {:?}", timeout),
+                ))),
+            }
+        })
+    }
+}
@@ -0,0 +1,184 @@
+//! Decoding of compressed response bodies, with the `max_decompressed_size`
+//! guard against decompression bombs
+//!
+//! reqwest's own decoding is left disabled because it gives no way to count
+//! the decoded bytes of compressed bodies only; this does what it did (ask
+//! for gzip, brotli and deflate, then decode and strip `Content-Encoding`
+//! and `Content-Length`) and stops once a body decodes past the limit.
+//!
+//! Chunked bodies are decoded over the de-chunked data hyper yields, so
+//! chunk boundaries may fall anywhere in the compressed stream. A gzip or
+//! deflate transfer coding (`Transfer-Encoding: gzip, chunked`), which
+//! hyper leaves in place under the chunking it removes, is decoded the same
+//! way, so neither framing reaches Python.
+
+use async_compression::tokio::bufread::{BrotliDecoder, GzipDecoder, ZlibDecoder};
+use bytes::Bytes;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use http::Extensions;
+use http_body_util::BodyDataStream;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING,
+};
+use reqwest::{Request, Response, ResponseBuilderExt};
+use reqwest_middleware::{Middleware, Next, Result};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+/// Largest decoded size of a compressed response body, unless configured
+/// otherwise
+pub const DEFAULT_MAX_DECOMPRESSED_SIZE: u64 = 1024 * 1024 * 1024;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// The `Accept-Encoding` sent with requests that don't set their own
+const ACCEPT: &str = "gzip, br, deflate";
+
+/// A compressed response body that decoded past `max_decompressed_size`
+#[derive(Debug, thiserror::Error)]
+#[error("decompressed response body exceeds max_decompressed_size ({max_bytes} bytes)")]
+pub struct DecompressedSizeExceeded {
+    pub max_bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Encoding {
+    Gzip,
+    Brotli,
+    Deflate,
+}
+
+impl Encoding {
+    /// The encoding of a body compressed once with a supported algorithm,
+    /// and the header that says so
+    ///
+    /// Other encodings, and bodies encoded more than once (including with
+    /// both a content and a transfer coding), are passed through for the
+    /// caller to decode.
+    fn of(headers: &HeaderMap) -> Option<(Self, HeaderName)> {
+        let mut codings = [CONTENT_ENCODING, TRANSFER_ENCODING]
+            .into_iter()
+            .flat_map(|name| codings(headers, &name).into_iter().map(move |coding| (coding, name.clone())))
+            .filter(|(coding, name)| !(name == TRANSFER_ENCODING && coding == "chunked"));
+        let (Some((coding, name)), None) = (codings.next(), codings.next()) else {
+            return None;
+        };
+        let encoding = match coding.as_str() {
+            "gzip" | "x-gzip" => Encoding::Gzip,
+            "br" => Encoding::Brotli,
+            "deflate" => Encoding::Deflate,
+            _ => return None,
+        };
+        Some((encoding, name))
+    }
+}
+
+/// The lowercase codings listed in the `name` headers, in order
+fn codings(headers: &HeaderMap, name: &HeaderName) -> Vec<String> {
+    headers
+        .get_all(name)
+        .iter()
+        .flat_map(|value| value.to_str().unwrap_or("unknown").split(','))
+        .map(|coding| coding.trim().to_ascii_lowercase())
+        .filter(|coding| !coding.is_empty())
+        .collect()
+}
+
+/// Ask for compressed responses, unless the request already says what it accepts
+pub fn accept_compressed(headers: &mut HeaderMap) {
+    if !headers.contains_key(ACCEPT_ENCODING) {
+        headers.insert(ACCEPT_ENCODING, HeaderValue::from_static(ACCEPT));
+    }
+}
+
+/// Decode `response` if its body is compressed, failing once more than
+/// `max_bytes` have been decoded
+///
+/// The body is decoded as it is read, so this returns at once; reading a
+/// body over the limit ends in a `DecompressedSizeExceeded` error.
+pub fn decompress(response: Response, max_bytes: Option<u64>) -> Response {
+    let Some((encoding, header)) = Encoding::of(response.headers()) else {
+        return response;
+    };
+    let url = response.url().clone();
+    let (mut parts, body) = http::Response::<reqwest::Body>::from(response).into_parts();
+    let chunked = codings(&parts.headers, &TRANSFER_ENCODING).iter().any(|coding| coding == "chunked");
+    parts.headers.remove(header);
+    parts.headers.remove(CONTENT_LENGTH);
+    // What's left of the transfer codings once the compression is undone
+    if chunked && !parts.headers.contains_key(TRANSFER_ENCODING) {
+        parts.headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+    }
+
+    let body = BodyDataStream::new(body).map_err(std::io::Error::other);
+    let mut body = Box::pin(body.peekable());
+    // An empty body (e.g. answering HEAD) has nothing to decode
+    let decoded = futures::stream::once(async move {
+        if body.as_mut().peek().await.is_none() {
+            return futures::stream::empty().boxed();
+        }
+        let reader = StreamReader::new(body);
+        let decoded: BoxStream<'static, std::io::Result<Bytes>> = match encoding {
+            Encoding::Gzip => {
+                let mut decoder = GzipDecoder::new(reader);
+                decoder.multiple_members(true);
+                ReaderStream::new(decoder).boxed()
+            }
+            Encoding::Brotli => ReaderStream::new(BrotliDecoder::new(reader)).boxed(),
+            Encoding::Deflate => ReaderStream::new(ZlibDecoder::new(reader)).boxed(),
+        };
+        decoded
+    })
+    .flatten();
+
+    // Count decoded bytes and end the body at the first error
+    let limited = decoded.scan((0u64, false), move |(total, failed), chunk| {
+        if *failed {
+            return futures::future::ready(None);
+        }
+        let chunk = chunk.map_err(BoxError::from).and_then(|chunk| {
+            *total += chunk.len() as u64;
+            match max_bytes {
+                Some(max_bytes) if *total > max_bytes => Err(DecompressedSizeExceeded { max_bytes }.into()),
+                _ => Ok(chunk),
+            }
+        });
+        *failed = chunk.is_err();
+        futures::future::ready(Some(chunk))
+    });
+
+    let mut decompressed = http::Response::builder()
+        .url(url)
+        .body(reqwest::Body::wrap_stream(limited))
+        .expect("a response with only a URL is valid");
+    *decompressed.status_mut() = parts.status;
+    *decompressed.version_mut() = parts.version;
+    *decompressed.headers_mut() = parts.headers;
+    // Connection info and the like, for the pool's tracker
+    decompressed.extensions_mut().extend(parts.extensions);
+    decompressed.into()
+}
+
+/// Asks for compressed responses and decodes them, see [`decompress`]
+///
+/// Inside signing and retries, so they see requests and responses as they
+/// would without it, as with reqwest's own decoding.
+#[derive(Debug, Clone)]
+pub struct DecompressMiddleware {
+    max_bytes: Option<u64>,
+}
+
+impl DecompressMiddleware {
+    pub fn new(max_bytes: Option<u64>) -> Self {
+        Self { max_bytes }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for DecompressMiddleware {
+    async fn handle(&self, mut req: Request, extensions: &mut Extensions, next: Next<'_>) -> Result<Response> {
+        accept_compressed(req.headers_mut());
+        let response = next.run(req, extensions).await?;
+        Ok(decompress(response, self.max_bytes))
+    }
+}
@@ -0,0 +1,503 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hickory_resolver::config::{
+    LookupIpStrategy, NameServerConfig, NameServerConfigGroup, Protocol, ResolverConfig, ResolverOpts,
+};
+use hickory_resolver::error::ResolveErrorKind;
+use hickory_resolver::proto::op::ResponseCode;
+use hickory_resolver::TokioAsyncResolver;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// DNS settings for a transport
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DnsConfig {
+    /// Fixed addresses for hostnames, consulted before any lookup
+    pub overrides: BTreeMap<String, Vec<IpAddr>>,
+    /// Query these servers (UDP, falling back to TCP) instead of the system
+    /// resolver; answers are then cached for their TTL
+    pub nameservers: Vec<SocketAddr>,
+    /// Keep serving a cached answer for up to this long past its TTL when
+    /// refreshing it fails (RFC 8767)
+    pub serve_stale: Option<Duration>,
+    /// Most hostnames the cache holds (unbounded when None, no caching at 0)
+    pub cache_size: Option<usize>,
+    /// Cache answers for at least this long, whatever their TTL
+    pub min_ttl: Option<Duration>,
+    /// Cache answers for at most this long, whatever their TTL
+    pub max_ttl: Option<Duration>,
+    /// Cache NXDOMAIN answers for up to this long (or the zone's negative
+    /// TTL, if shorter)
+    pub negative_ttl: Option<Duration>,
+}
+
+/// Default staleness limit for `dns_serve_stale=True`
+pub const DEFAULT_MAX_STALE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long a stale answer is served without retrying after a failed refresh
+const STALE_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest a record may be cached, the largest TTL DNS allows (RFC 2181),
+/// and longest it may be served stale, so large `dns_min_ttl`,
+/// `dns_negative_ttl` or `dns_serve_stale` values can't push an expiry past
+/// what `Instant` can hold
+const LONGEST_TTL: Duration = Duration::from_secs(i32::MAX as u64);
+
+/// How long a refresh may take before a stale answer is served instead
+const STALE_ANSWER_TIMEOUT: Duration = Duration::from_millis(1800);
+
+/// One resolved address
+#[derive(Debug, Clone)]
+pub struct ResolvedAddr {
+    pub ip: IpAddr,
+    /// Remaining TTL, or None when the source doesn't report one
+    pub ttl: Option<Duration>,
+    pub record_type: &'static str,
+}
+
+/// Where a resolution was answered from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolveSource {
+    Override,
+    Cache,
+    /// An expired cache entry, served because refreshing it failed
+    Stale,
+    Nameserver,
+    System,
+}
+
+impl ResolveSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResolveSource::Override => "override",
+            ResolveSource::Cache => "cache",
+            ResolveSource::Stale => "stale",
+            ResolveSource::Nameserver => "nameserver",
+            ResolveSource::System => "system",
+        }
+    }
+}
+
+/// The outcome of resolving a hostname
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    pub addresses: Vec<ResolvedAddr>,
+    /// Record types present in the answer, e.g. `["CNAME", "A"]`
+    pub record_types: Vec<String>,
+    pub source: ResolveSource,
+}
+
+impl Resolution {
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let addresses = pyo3::types::PyList::empty(py);
+        for addr in &self.addresses {
+            let address = PyDict::new(py);
+            address.set_item("address", addr.ip.to_string())?;
+            address.set_item("ttl", addr.ttl.map(|ttl| ttl.as_secs()))?;
+            address.set_item("record_type", addr.record_type)?;
+            addresses.append(address)?;
+        }
+
+        let result = PyDict::new(py);
+        result.set_item("addresses", addresses)?;
+        result.set_item("record_types", &self.record_types)?;
+        result.set_item("source", self.source.as_str())?;
+        Ok(result.into())
+    }
+}
+
+/// Cache counters reported by `resolve_stats`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResolveStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub overrides: u64,
+    /// Lookups answered from expired entries under `serve_stale`
+    pub stale: u64,
+    /// Hits on cached NXDOMAIN answers, also counted in `hits`
+    pub negative: u64,
+    pub entries: usize,
+}
+
+impl ResolveStats {
+    pub fn to_dict(self, py: Python) -> PyResult<PyObject> {
+        let lookups = self.hits + self.misses;
+        let result = PyDict::new(py);
+        result.set_item("hits", self.hits)?;
+        result.set_item("misses", self.misses)?;
+        result.set_item("overrides", self.overrides)?;
+        result.set_item("stale", self.stale)?;
+        result.set_item("negative", self.negative)?;
+        result.set_item("entries", self.entries)?;
+        result.set_item("hit_rate", (lookups > 0).then(|| self.hits as f64 / lookups as f64))?;
+        Ok(result.into())
+    }
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    /// Empty for a cached NXDOMAIN
+    addresses: Vec<ResolvedAddr>,
+    record_types: Vec<String>,
+    expires: Instant,
+    /// Past this, the entry is dropped even under `serve_stale`
+    stale_until: Instant,
+    /// While stale, don't query the nameservers again before this
+    retry_at: Option<Instant>,
+    /// The name doesn't exist: a hit fails the lookup
+    negative: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    config: DnsConfig,
+    nameservers: Option<TokioAsyncResolver>,
+    cache: Mutex<HashMap<String, CacheEntry>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    overrides: AtomicU64,
+    stale: AtomicU64,
+    negative: AtomicU64,
+}
+
+/// The resolver used for every connection a transport makes
+///
+/// Resolution order is: overrides, then the TTL cache, then the configured
+/// nameservers, or the system resolver (`getaddrinfo`) when none are
+/// configured. System answers carry no TTL and are left to the OS to cache.
+/// Cloning is cheap and clones share the cache.
+///
+/// An answer is cached for its TTL, clamped to `min_ttl` and `max_ttl`.
+/// With `negative_ttl`, a name the nameservers say doesn't exist is cached
+/// too, and lookups of it fail from the cache until the entry expires; a
+/// failed lookup for any other reason (a timeout, SERVFAIL) isn't cached.
+/// When the cache holds `cache_size` hosts, adding one evicts the entry
+/// closest to expiring.
+///
+/// With `serve_stale`, an expired entry is kept: if refreshing it fails,
+/// or takes longer than `STALE_ANSWER_TIMEOUT`, the old answer is served
+/// (with a warning) and the nameservers are left alone for
+/// `STALE_RETRY_INTERVAL` before the next refresh attempt. Negative
+/// entries are never served stale.
+#[derive(Debug, Clone)]
+pub struct DnsResolver {
+    inner: Arc<Inner>,
+}
+
+impl DnsResolver {
+    pub fn new(config: DnsConfig) -> Self {
+        let nameservers = (!config.nameservers.is_empty()).then(|| {
+            let mut group = NameServerConfigGroup::with_capacity(config.nameservers.len() * 2);
+            for addr in &config.nameservers {
+                group.push(NameServerConfig::new(*addr, Protocol::Udp));
+                group.push(NameServerConfig::new(*addr, Protocol::Tcp));
+            }
+
+            let mut opts = ResolverOpts::default();
+            opts.ip_strategy = LookupIpStrategy::Ipv4AndIpv6;
+            // Answers are cached here, where hits are counted
+            opts.cache_size = 0;
+            TokioAsyncResolver::tokio(ResolverConfig::from_parts(None, vec![], group), opts)
+        });
+
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                nameservers,
+                cache: Mutex::new(HashMap::new()),
+                hits: AtomicU64::new(0),
+                misses: AtomicU64::new(0),
+                overrides: AtomicU64::new(0),
+                stale: AtomicU64::new(0),
+                negative: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Resolve `host`, going through overrides and the cache
+    pub async fn resolve(&self, host: &str) -> std::io::Result<Resolution> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let inner = &self.inner;
+
+        if let Some(ips) = inner.config.overrides.get(&host) {
+            inner.overrides.fetch_add(1, Ordering::Relaxed);
+            let addresses: Vec<ResolvedAddr> =
+                ips.iter().map(|ip| ResolvedAddr { ip: *ip, ttl: None, record_type: record_type_of(ip) }).collect();
+            return Ok(Resolution {
+                record_types: distinct_record_types(&addresses),
+                addresses,
+                source: ResolveSource::Override,
+            });
+        }
+
+        let Some(resolver) = &inner.nameservers else {
+            let addresses: Vec<ResolvedAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .map(|addr| ResolvedAddr { ip: addr.ip(), ttl: None, record_type: record_type_of(&addr.ip()) })
+                .collect();
+            return Ok(Resolution {
+                record_types: distinct_record_types(&addresses),
+                addresses,
+                source: ResolveSource::System,
+            });
+        };
+
+        match self.cached(&host) {
+            Some(Cached::Answer(resolution)) => {
+                let counter = if resolution.source == ResolveSource::Stale { &inner.stale } else { &inner.hits };
+                counter.fetch_add(1, Ordering::Relaxed);
+                return Ok(resolution);
+            }
+            Some(Cached::Nxdomain) => {
+                inner.hits.fetch_add(1, Ordering::Relaxed);
+                inner.negative.fetch_add(1, Ordering::Relaxed);
+                return Err(nxdomain(&host));
+            }
+            None => {}
+        }
+        inner.misses.fetch_add(1, Ordering::Relaxed);
+
+        let lookup = resolver.lookup_ip(format!("{}.", host));
+        let lookup = if self.has_stale(&host) {
+            let result = match tokio::time::timeout(STALE_ANSWER_TIMEOUT, lookup).await {
+                Ok(result) => result.map_err(std::io::Error::other),
+                Err(_) => Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "dns refresh timed out")),
+            };
+            match result {
+                Ok(lookup) => lookup,
+                Err(e) => {
+                    // The entry may have aged out of its window meanwhile
+                    let Some(resolution) = self.stale_after_failure(&host) else {
+                        return Err(e);
+                    };
+                    tracing::warn!(host, error = %e, "dns refresh failed, serving stale answer");
+                    inner.stale.fetch_add(1, Ordering::Relaxed);
+                    return Ok(resolution);
+                }
+            }
+        } else {
+            match lookup.await {
+                Ok(lookup) => lookup,
+                Err(e) => {
+                    if let Some(ttl) = self.negative_ttl(&e) {
+                        let expires = Instant::now() + ttl;
+                        self.insert(
+                            host,
+                            CacheEntry {
+                                addresses: Vec::new(),
+                                record_types: Vec::new(),
+                                expires,
+                                stale_until: expires,
+                                retry_at: None,
+                                negative: true,
+                            },
+                        );
+                    }
+                    return Err(std::io::Error::other(e));
+                }
+            }
+        };
+        let now = Instant::now();
+        let mut addresses = Vec::new();
+        let mut record_types = Vec::new();
+        for record in lookup.as_lookup().records() {
+            let record_type = record.record_type().to_string();
+            if !record_types.contains(&record_type) {
+                record_types.push(record_type);
+            }
+            if let Some(ip) = record.data().and_then(|data| data.ip_addr()) {
+                addresses.push(ResolvedAddr {
+                    ip,
+                    ttl: Some(self.clamp_ttl(Duration::from_secs(u64::from(record.ttl())))),
+                    record_type: record_type_of(&ip),
+                });
+            }
+        }
+
+        let expires = now + self.clamp_ttl(lookup.valid_until().saturating_duration_since(now));
+        let stale_until = expires + inner.config.serve_stale.unwrap_or_default().min(LONGEST_TTL);
+        self.insert(
+            host,
+            CacheEntry {
+                addresses: addresses.clone(),
+                record_types: record_types.clone(),
+                expires,
+                stale_until,
+                retry_at: None,
+                negative: false,
+            },
+        );
+        Ok(Resolution { addresses, record_types, source: ResolveSource::Nameserver })
+    }
+
+    /// `ttl` within the configured `min_ttl` and `max_ttl`
+    fn clamp_ttl(&self, ttl: Duration) -> Duration {
+        let config = &self.inner.config;
+        let ttl = config.min_ttl.map_or(ttl, |min_ttl| ttl.max(min_ttl));
+        config.max_ttl.map_or(ttl, |max_ttl| ttl.min(max_ttl)).min(LONGEST_TTL)
+    }
+
+    /// How long to cache the failed lookup `error`: set for NXDOMAIN when
+    /// `negative_ttl` is configured, and at most the zone's negative TTL
+    fn negative_ttl(&self, error: &hickory_resolver::error::ResolveError) -> Option<Duration> {
+        let negative_ttl = self.inner.config.negative_ttl?.min(LONGEST_TTL);
+        match error.kind() {
+            ResolveErrorKind::NoRecordsFound {
+                response_code: ResponseCode::NXDomain, negative_ttl: soa_ttl, ..
+            } => Some(soa_ttl.map_or(negative_ttl, |ttl| negative_ttl.min(Duration::from_secs(u64::from(ttl))))),
+            _ => None,
+        }
+    }
+
+    /// Cache `entry` for `host`, making room within `cache_size`
+    fn insert(&self, host: String, entry: CacheEntry) {
+        let mut cache = self.inner.cache.lock().unwrap();
+        if let Some(size) = self.inner.config.cache_size {
+            if size == 0 {
+                return;
+            }
+            if !cache.contains_key(&host) && cache.len() >= size {
+                let now = Instant::now();
+                cache.retain(|_, entry| now < entry.stale_until);
+                if cache.len() >= size {
+                    let soonest = cache.iter().min_by_key(|(_, entry)| entry.expires).map(|(host, _)| host.clone());
+                    if let Some(soonest) = soonest {
+                        cache.remove(&soonest);
+                    }
+                }
+            }
+        }
+        cache.insert(host, entry);
+    }
+
+    /// Forget every cached answer, positive and negative, returning how
+    /// many hosts were cached
+    pub fn clear(&self) -> usize {
+        let mut cache = self.inner.cache.lock().unwrap();
+        let entries = cache.len();
+        cache.clear();
+        entries
+    }
+
+    /// A cached answer with TTLs counted down to now, unless expired
+    ///
+    /// A stale entry is returned only while a failed refresh is backing off.
+    fn cached(&self, host: &str) -> Option<Cached> {
+        let mut cache = self.inner.cache.lock().unwrap();
+        let now = Instant::now();
+        let entry = cache.get(host)?;
+        if entry.stale_until <= now {
+            cache.remove(host);
+            return None;
+        }
+        if entry.expires <= now {
+            return entry.retry_at.filter(|retry_at| now < *retry_at).map(|_| Cached::Answer(entry.stale()));
+        }
+        if entry.negative {
+            return Some(Cached::Nxdomain);
+        }
+
+        let remaining = entry.expires - now;
+        let addresses = entry
+            .addresses
+            .iter()
+            .map(|addr| ResolvedAddr { ttl: addr.ttl.map(|ttl| ttl.min(remaining)), ..addr.clone() })
+            .collect();
+        Some(Cached::Answer(Resolution {
+            addresses,
+            record_types: entry.record_types.clone(),
+            source: ResolveSource::Cache,
+        }))
+    }
+
+    /// Whether an expired entry for `host` may still be served
+    fn has_stale(&self, host: &str) -> bool {
+        let now = Instant::now();
+        self.inner.cache.lock().unwrap().get(host).is_some_and(|entry| now < entry.stale_until)
+    }
+
+    /// The stale answer for `host` after a failed refresh, backing off further refreshes
+    fn stale_after_failure(&self, host: &str) -> Option<Resolution> {
+        let mut cache = self.inner.cache.lock().unwrap();
+        let now = Instant::now();
+        let entry = cache.get_mut(host).filter(|entry| now < entry.stale_until)?;
+        entry.retry_at = Some(now + STALE_RETRY_INTERVAL);
+        Some(entry.stale())
+    }
+
+    /// Resolve each of `hosts` concurrently, filling the cache
+    ///
+    /// Fails with the first host that can't be resolved.
+    pub async fn warm(&self, hosts: &[String]) -> std::io::Result<Vec<Resolution>> {
+        futures::future::try_join_all(hosts.iter().map(|host| self.resolve(host))).await
+    }
+
+    pub fn stats(&self) -> ResolveStats {
+        let now = Instant::now();
+        let entries = self.inner.cache.lock().unwrap().values().filter(|entry| entry.expires > now).count();
+        ResolveStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            overrides: self.inner.overrides.load(Ordering::Relaxed),
+            stale: self.inner.stale.load(Ordering::Relaxed),
+            negative: self.inner.negative.load(Ordering::Relaxed),
+            entries,
+        }
+    }
+}
+
+/// What the cache holds for a host
+enum Cached {
+    Answer(Resolution),
+    Nxdomain,
+}
+
+impl CacheEntry {
+    /// This entry's answer served past its TTL, with TTLs of zero
+    fn stale(&self) -> Resolution {
+        let addresses = self
+            .addresses
+            .iter()
+            .map(|addr| ResolvedAddr { ttl: addr.ttl.map(|_| Duration::ZERO), ..addr.clone() })
+            .collect();
+        Resolution { addresses, record_types: self.record_types.clone(), source: ResolveSource::Stale }
+    }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.clone();
+        Box::pin(async move {
+            let resolution = resolver.resolve(name.as_str()).await?;
+            let addrs: Addrs = Box::new(resolution.addresses.into_iter().map(|addr| SocketAddr::new(addr.ip, 0)));
+            Ok(addrs)
+        })
+    }
+}
+
+/// The error for a lookup of `host` answered by a cached NXDOMAIN
+fn nxdomain(host: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::NotFound, format!("no record found for {} (cached NXDOMAIN)", host))
+}
+
+fn record_type_of(ip: &IpAddr) -> &'static str {
+    match ip {
+        IpAddr::V4(_) => "A",
+        IpAddr::V6(_) => "AAAA",
+    }
+}
+
+fn distinct_record_types(addresses: &[ResolvedAddr]) -> Vec<String> {
+    let mut record_types: Vec<String> = Vec::new();
+    for addr in addresses {
+        if !record_types.iter().any(|record_type| record_type == addr.record_type) {
+            record_types.push(addr.record_type.to_string());
+        }
+    }
+    record_types
+}
@@ -0,0 +1,48 @@
+//! Structured details of error responses, for the `parse_error_headers`
+//! option: the status and the rate limit headers servers send with it,
+//! parsed so retry decisions in Python needn't parse header strings
+
+use std::time::SystemTime;
+
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+/// `response.extensions["error_detail"]` for a non-2xx response: its
+/// `status`, its `Retry-After` in seconds, and its `X-RateLimit-Limit`,
+/// `-Remaining` and `-Reset` (or the unprefixed `RateLimit-*` headers)
+///
+/// A header that is missing or isn't a number is `null`. `Retry-After`
+/// given as an HTTP date is the seconds left until then, at least 0;
+/// `rate_limit_reset` is as sent, which some servers give in seconds from
+/// now and others as a Unix time.
+pub fn error_detail(status: reqwest::StatusCode, headers: &HeaderMap) -> Option<serde_json::Value> {
+    if status.is_success() {
+        return None;
+    }
+    Some(serde_json::json!({
+        "status": status.as_u16(),
+        "retry_after": retry_after(headers, SystemTime::now()),
+        "rate_limit_limit": rate_limit(headers, "limit"),
+        "rate_limit_remaining": rate_limit(headers, "remaining"),
+        "rate_limit_reset": rate_limit(headers, "reset"),
+    }))
+}
+
+/// Seconds to wait before retrying, from `Retry-After` as seconds or as an
+/// HTTP date after `now`
+fn retry_after(headers: &HeaderMap, now: SystemTime) -> Option<u64> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse() {
+        return Some(seconds);
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).map_or(0, |wait| wait.as_secs()))
+}
+
+/// The `X-RateLimit-{name}` header as a number, or `RateLimit-{name}`
+/// without it
+fn rate_limit(headers: &HeaderMap, name: &str) -> Option<u64> {
+    [format!("x-ratelimit-{}", name), format!("ratelimit-{}", name)]
+        .iter()
+        .find_map(|header| headers.get(header.as_str()))
+        .and_then(|value| value.to_str().ok()?.trim().parse().ok())
+}
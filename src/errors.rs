@@ -1,62 +1,217 @@
 use pyo3::{exceptions::*, prelude::*};
 use reqwest_middleware::Error as MiddlewareError;
 
+use crate::config::ConfigError;
+use crate::decompress::DecompressedSizeExceeded;
+
+pyo3::create_exception!(
+    _rust_httpx,
+    UnexpectedStatusError,
+    PyRuntimeError,
+    "A response status outside the request's `expected_status` list"
+);
+
+pyo3::create_exception!(
+    _rust_httpx,
+    DecodingError,
+    PyValueError,
+    "A response body that isn't valid JSON, with its `lineno`, `colno` and byte offset `pos`"
+);
+
 /// Custom error types for the transport
 #[derive(Debug, Clone, thiserror::Error)]
 pub enum TransportError {
     #[error("Request timeout: {0}")]
     RequestTimeout(String),
-    
+
     #[error("Connect timeout: {0}")]
     ConnectTimeout(String),
-    
+
     #[error("Read timeout: {0}")]
     ReadTimeout(String),
-    
+
     #[error("Connection error: {0}")]
     ConnectError(String),
-    
+
     #[error("Read error: {0}")]
     ReadError(String),
-    
+
     #[error("Write error: {0}")]
     WriteError(String),
-    
+
     #[error("Pool timeout: {0}")]
     PoolTimeout(String),
-    
+
     #[error("SSL error: {0}")]
     SSLError(String),
-    
+
     #[error("Proxy error: {0}")]
     ProxyError(String),
-    
+
     #[error("Local protocol error: {0}")]
     LocalProtocolError(String),
-    
+
     #[error("Remote protocol error: {0}")]
     RemoteProtocolError(String),
-    
+
     #[error("Invalid URL: {0}")]
     InvalidURL(String),
-    
+
     #[error("Too many redirects")]
     TooManyRedirects,
-    
+
+    #[error("Unexpected status {status}: {snippet}")]
+    UnexpectedStatus { status: u16, snippet: String },
+
     #[error("Other error: {0}")]
     Other(String),
 }
 
+impl TransportError {
+    /// The variant name, e.g. `"ConnectTimeout"`, for reporting outside Python
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TransportError::RequestTimeout(_) => "RequestTimeout",
+            TransportError::ConnectTimeout(_) => "ConnectTimeout",
+            TransportError::ReadTimeout(_) => "ReadTimeout",
+            TransportError::ConnectError(_) => "ConnectError",
+            TransportError::ReadError(_) => "ReadError",
+            TransportError::WriteError(_) => "WriteError",
+            TransportError::PoolTimeout(_) => "PoolTimeout",
+            TransportError::SSLError(_) => "SSLError",
+            TransportError::ProxyError(_) => "ProxyError",
+            TransportError::LocalProtocolError(_) => "LocalProtocolError",
+            TransportError::RemoteProtocolError(_) => "RemoteProtocolError",
+            TransportError::InvalidURL(_) => "InvalidURL",
+            TransportError::TooManyRedirects => "TooManyRedirects",
+            TransportError::UnexpectedStatus { .. } => "UnexpectedStatus",
+            TransportError::Other(_) => "Other",
+        }
+    }
+}
+
+/// Whether reading the response body failed because the connection closed
+/// before the whole body arrived (e.g. the server crashed mid-response)
+fn is_truncated_body(err: &reqwest::Error) -> bool {
+    if !(err.is_body() || err.is_decode()) {
+        return false;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if cause.downcast_ref::<hyper::Error>().is_some_and(hyper::Error::is_incomplete_message)
+            || cause.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::UnexpectedEof)
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
+/// The `max_decompressed_size` error that stopped reading the body, if any
+fn decompressed_size_exceeded(err: &reqwest::Error) -> Option<&DecompressedSizeExceeded> {
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if let Some(exceeded) = cause.downcast_ref::<DecompressedSizeExceeded>() {
+            return Some(exceeded);
+        }
+        source = cause.source();
+    }
+    None
+}
+
+/// The message of the `pins`, `crls` or `require_ocsp_staple` check that
+/// failed the TLS handshake, if any
+///
+/// rustls reports it through the IO errors the connection failed with,
+/// which `source()` looks past, so they are unwrapped here.
+#[cfg(feature = "rustls")]
+fn verifier_rejection(err: &reqwest::Error) -> Option<String> {
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        // reqwest wraps the IO error rustls fails with in an IO error of its own
+        let mut inner: &(dyn std::error::Error + 'static) = cause;
+        while let Some(io) = inner.downcast_ref::<std::io::Error>().and_then(std::io::Error::get_ref) {
+            inner = io;
+        }
+        match inner.downcast_ref::<rustls::Error>() {
+            Some(rustls::Error::General(msg))
+                if msg.starts_with(crate::pin::PIN_MISMATCH)
+                    || msg.starts_with(crate::revocation::OCSP_STAPLE_REQUIRED) =>
+            {
+                return Some(msg.clone());
+            }
+            Some(rustls::Error::InvalidCertificate(rustls::CertificateError::Revoked)) => {
+                return Some(format!(
+                    "{}: the server's certificate is in a configured CRL",
+                    crate::revocation::CERTIFICATE_REVOKED
+                ));
+            }
+            _ => {}
+        }
+        source = cause.source();
+    }
+    None
+}
+
+/// [`verifier_rejection`], for builds without rustls, which can't have
+/// those checks
+#[cfg(not(feature = "rustls"))]
+fn verifier_rejection(_err: &reqwest::Error) -> Option<String> {
+    None
+}
+
+/// Whether sending the request failed because the connection broke while
+/// its body was being written (e.g. the server closed it after reading the
+/// headers)
+///
+/// hyper has no public check for its body write errors, so this looks for
+/// the IO error underneath them.
+fn is_body_write_error(err: &reqwest::Error) -> bool {
+    if !err.is_request() || err.is_connect() {
+        return false;
+    }
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if cause.downcast_ref::<hyper::Error>().is_some_and(hyper::Error::is_body_write_aborted)
+            || cause.downcast_ref::<std::io::Error>().is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::BrokenPipe
+                        | std::io::ErrorKind::ConnectionReset
+                        | std::io::ErrorKind::ConnectionAborted
+                        | std::io::ErrorKind::WriteZero
+                )
+            })
+        {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
+}
+
 impl From<reqwest::Error> for TransportError {
     fn from(err: reqwest::Error) -> Self {
         if err.is_timeout() {
             if err.is_connect() {
                 TransportError::ConnectTimeout(err.to_string())
+            } else if err.is_body() {
+                // The request's timeout ran out while its body was read
+                TransportError::ReadTimeout(err.to_string())
             } else {
                 TransportError::RequestTimeout(err.to_string())
             }
+        } else if let Some(exceeded) = decompressed_size_exceeded(&err) {
+            TransportError::RemoteProtocolError(exceeded.to_string())
+        } else if is_truncated_body(&err) {
+            TransportError::RemoteProtocolError(format!("response body truncated: {}", err))
+        } else if let Some(rejection) = verifier_rejection(&err) {
+            TransportError::SSLError(rejection)
         } else if err.is_connect() {
             TransportError::ConnectError(err.to_string())
+        } else if is_body_write_error(&err) {
+            TransportError::WriteError(format!("connection closed while sending the request body: {}", err))
         } else if err.is_redirect() {
             TransportError::TooManyRedirects
         } else if err.is_request() {
@@ -70,7 +225,12 @@ impl From<reqwest::Error> for TransportError {
 impl From<MiddlewareError> for TransportError {
     fn from(err: MiddlewareError) -> Self {
         match err {
-            MiddlewareError::Middleware(e) => TransportError::Other(e.to_string()),
+            // Middleware failing with a transport error of its own, such as
+            // the retry middleware's read timeout
+            MiddlewareError::Middleware(e) => match e.downcast::<TransportError>() {
+                Ok(e) => e,
+                Err(e) => TransportError::Other(e.to_string()),
+            },
             MiddlewareError::Reqwest(e) => e.into(),
         }
     }
@@ -79,51 +239,43 @@ impl From<MiddlewareError> for TransportError {
 impl From<TransportError> for PyErr {
     fn from(err: TransportError) -> Self {
         match err {
-            TransportError::RequestTimeout(msg) => {
-                PyErr::new::<PyTimeoutError, _>(format!("Request timeout: {}", msg))
-            }
+            TransportError::RequestTimeout(msg) => PyErr::new::<PyTimeoutError, _>(format!("Request timeout: {}", msg)),
             TransportError::ConnectTimeout(msg) => {
                 PyErr::new::<PyConnectionError, _>(format!("Connect timeout: {}", msg))
             }
-            TransportError::ReadTimeout(msg) => {
-                PyErr::new::<PyTimeoutError, _>(format!("Read timeout: {}", msg))
-            }
-            TransportError::ConnectError(msg) => {
-                PyErr::new::<PyConnectionError, _>(format!("Connect error: {}", msg))
-            }
-            TransportError::ReadError(msg) => {
-                PyErr::new::<PyIOError, _>(format!("Read error: {}", msg))
-            }
-            TransportError::WriteError(msg) => {
-                PyErr::new::<PyIOError, _>(format!("Write error: {}", msg))
-            }
-            TransportError::PoolTimeout(msg) => {
-                PyErr::new::<PyTimeoutError, _>(format!("Pool timeout: {}", msg))
-            }
-            TransportError::SSLError(msg) => {
-                PyErr::new::<PyConnectionError, _>(format!("SSL error: {}", msg))
-            }
-            TransportError::ProxyError(msg) => {
-                PyErr::new::<PyConnectionError, _>(format!("Proxy error: {}", msg))
-            }
+            TransportError::ReadTimeout(msg) => PyErr::new::<PyTimeoutError, _>(format!("Read timeout: {}", msg)),
+            TransportError::ConnectError(msg) => PyErr::new::<PyConnectionError, _>(format!("Connect error: {}", msg)),
+            TransportError::ReadError(msg) => PyErr::new::<PyIOError, _>(format!("Read error: {}", msg)),
+            TransportError::WriteError(msg) => PyErr::new::<PyIOError, _>(format!("Write error: {}", msg)),
+            TransportError::PoolTimeout(msg) => PyErr::new::<PyTimeoutError, _>(format!("Pool timeout: {}", msg)),
+            TransportError::SSLError(msg) => PyErr::new::<PyConnectionError, _>(format!("SSL error: {}", msg)),
+            TransportError::ProxyError(msg) => PyErr::new::<PyConnectionError, _>(format!("Proxy error: {}", msg)),
             TransportError::LocalProtocolError(msg) => {
                 PyErr::new::<PyValueError, _>(format!("Local protocol error: {}", msg))
             }
             TransportError::RemoteProtocolError(msg) => {
                 PyErr::new::<PyValueError, _>(format!("Remote protocol error: {}", msg))
             }
-            TransportError::InvalidURL(msg) => {
-                PyErr::new::<PyValueError, _>(format!("Invalid URL: {}", msg))
-            }
-            TransportError::TooManyRedirects => {
-                PyErr::new::<PyValueError, _>("Too many redirects")
-            }
-            TransportError::Other(msg) => {
-                PyErr::new::<PyRuntimeError, _>(format!("HTTP error: {}", msg))
-            }
+            TransportError::InvalidURL(msg) => PyErr::new::<PyValueError, _>(format!("Invalid URL: {}", msg)),
+            TransportError::TooManyRedirects => PyErr::new::<PyValueError, _>("Too many redirects"),
+            TransportError::UnexpectedStatus { status, snippet } => Python::with_gil(|py| {
+                let err = UnexpectedStatusError::new_err(format!("Unexpected status {}: {}", status, snippet));
+                // Setting attributes on a fresh exception instance can't fail
+                let value = err.value(py);
+                let _ = value.setattr("status", status);
+                let _ = value.setattr("body", snippet);
+                err
+            }),
+            TransportError::Other(msg) => PyErr::new::<PyRuntimeError, _>(format!("HTTP error: {}", msg)),
         }
     }
 }
 
-/// Result type for transport operations
-pub type TransportResult<T> = Result<T, TransportError>; 
\ No newline at end of file
+impl From<ConfigError> for PyErr {
+    fn from(err: ConfigError) -> Self {
+        match err {
+            ConfigError::Invalid(msg) => PyErr::new::<PyValueError, _>(msg),
+            ConfigError::Io(e) => e.into(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! `application/x-www-form-urlencoded` request bodies encoded from Python
+//! objects, for the `form_body` extension
+
+use std::borrow::Cow;
+use std::fmt::Write;
+
+use pyo3::exceptions::PyTypeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+
+/// The body from a request's `form_body` extension, if it set one
+///
+/// The form is a dict, or a list of `(key, value)` pairs to repeat keys.
+/// Keys and values are `str`, `int`, `float`, `bool` or `None`; anything
+/// else, nested lists and dicts included, raises `TypeError`.
+pub fn form_body_from_extensions(py_extensions: &PyAny) -> PyResult<Option<Vec<u8>>> {
+    let Ok(extensions) = py_extensions.downcast::<PyDict>() else {
+        return Ok(None);
+    };
+    let Some(form) = extensions.get_item("form_body")? else {
+        return Ok(None);
+    };
+    to_form_bytes(form).map(Some)
+}
+
+/// Encode `form` the way `urllib.parse.urlencode(form, doseq=True)` does
+pub fn to_form_bytes(form: &PyAny) -> PyResult<Vec<u8>> {
+    let pairs: Vec<(&PyAny, &PyAny)> = if let Ok(dict) = form.downcast::<PyDict>() {
+        dict.iter().collect()
+    } else if form.is_instance_of::<PyList>() || form.is_instance_of::<PyTuple>() {
+        form.iter()?
+            .map(|pair| match pair?.extract::<Vec<&PyAny>>().as_deref() {
+                Ok([key, value]) => Ok((*key, *value)),
+                _ => Err(PyTypeError::new_err("Invalid form_body: a list must hold (key, value) pairs")),
+            })
+            .collect::<PyResult<_>>()?
+    } else {
+        return Err(PyTypeError::new_err(format!(
+            "Invalid form_body: expected a dict or a list of (key, value) pairs, got {}",
+            form.get_type().name()?
+        )));
+    };
+
+    let mut body = String::new();
+    for (key, value) in pairs {
+        if !body.is_empty() {
+            body.push('&');
+        }
+        encode_into(&mut body, &field_text(key, "key")?);
+        body.push('=');
+        encode_into(&mut body, &field_text(value, "value")?);
+    }
+    Ok(body.into_bytes())
+}
+
+/// The text of a form key or value, as `str()` gives it
+fn field_text<'py>(obj: &'py PyAny, what: &str) -> PyResult<Cow<'py, str>> {
+    if let Ok(s) = obj.downcast::<PyString>() {
+        return s.to_str().map(Into::into);
+    }
+    let scalar = obj.is_none()
+        || obj.is_instance_of::<PyBool>()
+        || obj.is_instance_of::<PyLong>()
+        || obj.is_instance_of::<PyFloat>();
+    if !scalar {
+        return Err(PyTypeError::new_err(format!(
+            "Invalid form_body {}: {} is not supported (expected str, int, float, bool or None; \
+             nested containers can't be form-encoded)",
+            what,
+            obj.get_type().name()?
+        )));
+    }
+    Ok(obj.str()?.to_str()?.to_owned().into())
+}
+
+/// Percent-encode `text` as `urllib.parse.quote_plus` does: UTF-8 bytes
+/// other than letters, digits and `_.-~` become `%XX`, and spaces `+`
+fn encode_into(out: &mut String, text: &str) {
+    for &byte in text.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'.' | b'-' | b'~' => out.push(byte as char),
+            b' ' => out.push('+'),
+            _ => {
+                let _ = write!(out, "%{:02X}", byte);
+            }
+        }
+    }
+}
+
+/// Describe a form body in `headers`: `Content-Type` unless the caller set
+/// one, and no stale `Content-Length` from the request's original content
+pub fn set_form_headers(headers: &mut HeaderMap) {
+    headers.remove(CONTENT_LENGTH);
+    if !headers.contains_key(CONTENT_TYPE) {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/x-www-form-urlencoded"));
+    }
+}
@@ -0,0 +1,440 @@
+//! TLS details of the connections a client opens, for the `tls_handshake`
+//! and `cipher_suite` response extensions and `tls_stats`
+//!
+//! reqwest's connections are sealed, so what their handshakes negotiated
+//! can't be read back from them. Instead the client's rustls config notes
+//! it as the handshake goes: each cipher suite it offers is wrapped so
+//! that installing the suite's keys, which only happens for the suite the
+//! server picked, notes the suite, and its verifier notes that it was
+//! asked, which a resumed handshake never does. rustls runs the handshake
+//! inside reqwest's connector, so [`HandshakeLayer`] collects the notes
+//! while it connects, and files them under the connection's local and
+//! remote addresses, which every response on the connection carries in its
+//! `HttpInfo`. Connections made with native-tls note nothing.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpInfo};
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tower::{Layer, Service};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+tokio::task_local! {
+    /// Notes on the handshake of the connection being made, see [`HandshakeLayer`]
+    static NOTES: Arc<Mutex<Notes>>;
+}
+
+#[derive(Debug, Default)]
+struct Notes {
+    /// The protocol and cipher suite, once the suite's keys are made
+    negotiated: Option<(&'static str, String)>,
+    /// Whether the server's certificate was verified
+    verified: bool,
+}
+
+/// What a connection's TLS handshake negotiated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    /// `"TLSv1.2"` or `"TLSv1.3"`
+    pub protocol: &'static str,
+    /// The negotiated cipher suite, as rustls names it (e.g.
+    /// `"TLS13_AES_256_GCM_SHA384"`)
+    pub cipher_suite: String,
+    /// The protocol the connection speaks, `"h2"` or `"http/1.1"`
+    pub alpn: &'static str,
+    /// The handshake resumed an earlier session rather than verifying the
+    /// server's certificate
+    pub resumed: bool,
+}
+
+impl Handshake {
+    /// The `tls_handshake` response extension
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "tls_protocol": self.protocol,
+            "cipher_suite": self.cipher_suite,
+            "alpn": self.alpn,
+            "resumed": self.resumed,
+        })
+    }
+}
+
+/// Handshake counters reported by `tls_stats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsStats {
+    pub full: u64,
+    pub resumed: u64,
+}
+
+impl TlsStats {
+    pub fn to_dict(self, py: Python) -> PyResult<PyObject> {
+        let handshakes = self.full + self.resumed;
+        let result = PyDict::new(py);
+        result.set_item("handshakes", handshakes)?;
+        result.set_item("full", self.full)?;
+        result.set_item("resumed", self.resumed)?;
+        result.set_item("resumption_rate", (handshakes > 0).then(|| self.resumed as f64 / handshakes as f64))?;
+        Ok(result.into())
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    handshake: Handshake,
+    last_used: Instant,
+    /// A response on the connection has asked for the handshake
+    reported: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    connections: Mutex<HashMap<(SocketAddr, SocketAddr), Entry>>,
+    idle_timeout: Duration,
+    full: AtomicU64,
+    resumed: AtomicU64,
+    /// The rustls config every client filing here shares, see [`Handshakes::tls_config`]
+    #[cfg(feature = "rustls")]
+    tls_config: Mutex<Option<Arc<rustls::ClientConfig>>>,
+}
+
+/// The handshakes of a client's connections, by local and remote address
+///
+/// As with `ConnectionTracker`, a connection is considered gone once its
+/// handshake has gone unasked for longer than the pool idle timeout.
+#[derive(Debug, Clone)]
+pub struct Handshakes {
+    inner: Arc<Inner>,
+}
+
+impl Handshakes {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                connections: Default::default(),
+                idle_timeout,
+                full: AtomicU64::new(0),
+                resumed: AtomicU64::new(0),
+                #[cfg(feature = "rustls")]
+                tls_config: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// The handshake of the connection `response` arrived on, if it was
+    /// made over TLS, and whether `response` is the first on the
+    /// connection to ask for it
+    pub fn of(&self, response: &reqwest::Response) -> Option<(Handshake, bool)> {
+        let info = response.extensions().get::<HttpInfo>()?;
+        let mut connections = self.inner.connections.lock().unwrap();
+        let entry = connections.get_mut(&(info.local_addr(), info.remote_addr()))?;
+        entry.last_used = Instant::now();
+        let first = !std::mem::replace(&mut entry.reported, true);
+        Some((entry.handshake.clone(), first))
+    }
+
+    /// How many full and resumed handshakes were filed here
+    pub fn stats(&self) -> TlsStats {
+        TlsStats { full: self.inner.full.load(Ordering::Relaxed), resumed: self.inner.resumed.load(Ordering::Relaxed) }
+    }
+
+    /// The rustls config of a client filing here, offering `alpn_protocols`
+    ///
+    /// rustls only resumes a session under the verifier and client auth it
+    /// was made with, so every client shares one config, built on first
+    /// use, and with it one session store: a client created after `evict`
+    /// resumes the sessions of the one it replaces.
+    #[cfg(feature = "rustls")]
+    pub fn tls_config(
+        &self,
+        config: &crate::client::ClientConfig,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<rustls::ClientConfig, crate::errors::TransportError> {
+        let mut shared = self.inner.tls_config.lock().unwrap();
+        let base = match shared.as_ref() {
+            Some(base) => base.clone(),
+            None => {
+                let provider = Arc::new(noting_provider());
+                let verifier = Arc::new(NotingVerifier(crate::probe::server_verifier(config, provider.clone())?));
+                let base = Arc::new(crate::probe::tls_config(provider, verifier, Vec::new())?);
+                shared.insert(base).clone()
+            }
+        };
+        let mut tls_config = (*base).clone();
+        tls_config.alpn_protocols = alpn_protocols;
+        Ok(tls_config)
+    }
+
+    /// File the handshake noted in `notes` under the addresses of the
+    /// connection `connected` describes
+    fn insert(&self, connected: &Connected, notes: Notes) {
+        let Some((protocol, cipher_suite)) = notes.negotiated else {
+            return;
+        };
+        let resumed = !notes.verified;
+        let counter = if resumed { &self.inner.resumed } else { &self.inner.full };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        let mut extras = http::Extensions::new();
+        connected.get_extras(&mut extras);
+        let Some(info) = extras.get::<HttpInfo>() else {
+            return;
+        };
+        let alpn = if connected.is_negotiated_h2() { "h2" } else { "http/1.1" };
+        let handshake = Handshake { protocol, cipher_suite, alpn, resumed };
+        let mut connections = self.inner.connections.lock().unwrap();
+        connections.retain(|_, entry| entry.last_used.elapsed() < self.inner.idle_timeout);
+        let entry = Entry { handshake, last_used: Instant::now(), reported: false };
+        connections.insert((info.local_addr(), info.remote_addr()), entry);
+    }
+}
+
+/// Connector layer filing the handshake of each connection it makes with
+/// [`Handshakes`]
+#[derive(Debug, Clone)]
+pub struct HandshakeLayer {
+    handshakes: Handshakes,
+}
+
+impl HandshakeLayer {
+    pub fn new(handshakes: Handshakes) -> Self {
+        Self { handshakes }
+    }
+}
+
+impl<S> Layer<S> for HandshakeLayer {
+    type Service = HandshakeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HandshakeService { inner, handshakes: self.handshakes.clone() }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HandshakeService<S> {
+    inner: S,
+    handshakes: Handshakes,
+}
+
+impl<S, Req> Service<Req> for HandshakeService<S>
+where
+    S: Service<Req, Error = BoxError>,
+    S::Response: Connection + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        // Scoped to the connecting future rather than the task, which may
+        // change if the connection finishes on a task of its own
+        let notes = Arc::new(Mutex::new(Notes::default()));
+        let connecting = NOTES.scope(notes.clone(), self.inner.call(req));
+        let handshakes = self.handshakes.clone();
+        Box::pin(async move {
+            let connection = connecting.await?;
+            let notes = std::mem::take(&mut *notes.lock().unwrap());
+            handshakes.insert(&connection.connected(), notes);
+            Ok(connection)
+        })
+    }
+}
+
+/// Note that the connection being made negotiated `suite` over `protocol`
+#[cfg(feature = "rustls")]
+fn note(protocol: &'static str, suite: rustls::CipherSuite) {
+    let _ = NOTES.try_with(|notes| {
+        notes.lock().unwrap().negotiated = Some((protocol, format!("{:?}", suite)));
+    });
+}
+
+/// A verifier noting that the connection being made verified the server's
+/// certificate
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+struct NotingVerifier(Arc<dyn rustls::client::danger::ServerCertVerifier>);
+
+#[cfg(feature = "rustls")]
+impl rustls::client::danger::ServerCertVerifier for NotingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &rustls::pki_types::CertificateDer<'_>,
+        intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        server_name: &rustls::pki_types::ServerName<'_>,
+        ocsp_response: &[u8],
+        now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        let _ = NOTES.try_with(|notes| notes.lock().unwrap().verified = true);
+        self.0.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        self.0.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.supported_verify_schemes()
+    }
+
+    fn requires_raw_public_keys(&self) -> bool {
+        self.0.requires_raw_public_keys()
+    }
+
+    fn root_hint_subjects(&self) -> Option<&[rustls::DistinguishedName]> {
+        self.0.root_hint_subjects()
+    }
+}
+
+/// ring's crypto provider, with cipher suites that note the handshakes
+/// negotiating them
+#[cfg(feature = "rustls")]
+fn noting_provider() -> rustls::crypto::CryptoProvider {
+    rustls::crypto::CryptoProvider { cipher_suites: NOTING_SUITES.clone(), ..rustls::crypto::ring::default_provider() }
+}
+
+/// ring's cipher suites, each with an AEAD algorithm noting the suite
+///
+/// rustls keeps suites by `'static` reference, so they are built once and
+/// kept for the life of the process. Sessions remember the suite they were
+/// made with, so every config built from them can resume any of them.
+#[cfg(feature = "rustls")]
+static NOTING_SUITES: once_cell::sync::Lazy<Vec<rustls::SupportedCipherSuite>> = once_cell::sync::Lazy::new(|| {
+    use rustls::crypto::CipherSuiteCommon;
+    use rustls::{SupportedCipherSuite, Tls12CipherSuite, Tls13CipherSuite};
+
+    fn common(common: &CipherSuiteCommon) -> CipherSuiteCommon {
+        CipherSuiteCommon {
+            suite: common.suite,
+            hash_provider: common.hash_provider,
+            confidentiality_limit: common.confidentiality_limit,
+        }
+    }
+
+    rustls::crypto::ring::default_provider()
+        .cipher_suites
+        .into_iter()
+        .map(|suite| match suite {
+            SupportedCipherSuite::Tls13(suite) => SupportedCipherSuite::Tls13(Box::leak(Box::new(Tls13CipherSuite {
+                common: common(&suite.common),
+                hkdf_provider: suite.hkdf_provider,
+                aead_alg: Box::leak(Box::new(Noting { inner: suite.aead_alg, suite: suite.common.suite })),
+                quic: suite.quic,
+            }))),
+            SupportedCipherSuite::Tls12(suite) => SupportedCipherSuite::Tls12(Box::leak(Box::new(Tls12CipherSuite {
+                common: common(&suite.common),
+                prf_provider: suite.prf_provider,
+                kx: suite.kx,
+                sign: suite.sign,
+                aead_alg: Box::leak(Box::new(Noting { inner: suite.aead_alg, suite: suite.common.suite })),
+            }))),
+        })
+        .collect()
+});
+
+/// A suite's AEAD algorithm, noting the suite when rustls makes its keys
+#[cfg(feature = "rustls")]
+struct Noting<A: ?Sized + 'static> {
+    inner: &'static A,
+    suite: rustls::CipherSuite,
+}
+
+#[cfg(feature = "rustls")]
+impl rustls::crypto::cipher::Tls13AeadAlgorithm for Noting<dyn rustls::crypto::cipher::Tls13AeadAlgorithm> {
+    fn encrypter(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: rustls::crypto::cipher::Iv,
+    ) -> Box<dyn rustls::crypto::cipher::MessageEncrypter> {
+        note("TLSv1.3", self.suite);
+        self.inner.encrypter(key, iv)
+    }
+
+    fn decrypter(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: rustls::crypto::cipher::Iv,
+    ) -> Box<dyn rustls::crypto::cipher::MessageDecrypter> {
+        self.inner.decrypter(key, iv)
+    }
+
+    fn key_len(&self) -> usize {
+        self.inner.key_len()
+    }
+
+    fn extract_keys(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: rustls::crypto::cipher::Iv,
+    ) -> Result<rustls::ConnectionTrafficSecrets, rustls::crypto::cipher::UnsupportedOperationError> {
+        self.inner.extract_keys(key, iv)
+    }
+
+    fn fips(&self) -> bool {
+        self.inner.fips()
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl rustls::crypto::cipher::Tls12AeadAlgorithm for Noting<dyn rustls::crypto::cipher::Tls12AeadAlgorithm> {
+    fn encrypter(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: &[u8],
+        extra: &[u8],
+    ) -> Box<dyn rustls::crypto::cipher::MessageEncrypter> {
+        note("TLSv1.2", self.suite);
+        self.inner.encrypter(key, iv, extra)
+    }
+
+    fn decrypter(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: &[u8],
+    ) -> Box<dyn rustls::crypto::cipher::MessageDecrypter> {
+        self.inner.decrypter(key, iv)
+    }
+
+    fn key_block_shape(&self) -> rustls::crypto::cipher::KeyBlockShape {
+        self.inner.key_block_shape()
+    }
+
+    fn extract_keys(
+        &self,
+        key: rustls::crypto::cipher::AeadKey,
+        iv: &[u8],
+        explicit: &[u8],
+    ) -> Result<rustls::ConnectionTrafficSecrets, rustls::crypto::cipher::UnsupportedOperationError> {
+        self.inner.extract_keys(key, iv, explicit)
+    }
+
+    fn fips(&self) -> bool {
+        self.inner.fips()
+    }
+}
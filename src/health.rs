@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::pool::ConnectionPool;
+
+/// A minimal HTTP server answering Kubernetes liveness probes
+///
+/// Runs on the same tokio runtime as the transport, so a wedged runtime
+/// also fails the probe.
+#[derive(Debug)]
+pub struct HealthServer {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+impl HealthServer {
+    /// Bind `127.0.0.1:{port}` and serve `GET /health` until stopped
+    ///
+    /// Port 0 binds an ephemeral port; see `addr` for the one chosen.
+    pub async fn start(port: u16, pool: Arc<ConnectionPool>) -> std::io::Result<Self> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", port)).await?;
+        let addr = listener.local_addr()?;
+        let (shutdown, signal) = oneshot::channel();
+
+        let app = Router::new().route("/health", get(health)).with_state(pool);
+        let task = tokio::spawn(async move {
+            let server = axum::serve(listener, app).with_graceful_shutdown(async {
+                let _ = signal.await;
+            });
+            if let Err(e) = server.await {
+                tracing::warn!(error = %e, "health server failed");
+            }
+        });
+
+        Ok(Self { addr, shutdown, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting probes and wait for open probe connections to finish
+    pub async fn stop(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+    }
+}
+
+async fn health(State(pool): State<Arc<ConnectionPool>>) -> Json<Value> {
+    let connections = pool.tracker().snapshot();
+    let origins: HashSet<&str> = connections.iter().map(|record| record.partition.origin.as_str()).collect();
+
+    Json(json!({
+        "status": "ok",
+        "version": env!("CARGO_PKG_VERSION"),
+        "pool": {
+            "connections": connections.len(),
+            "origins": origins.len(),
+            "requests": connections.iter().map(|record| record.requests).sum::<u64>(),
+        },
+    }))
+}
@@ -0,0 +1,53 @@
+use std::time::Duration;
+
+use reqwest::Response;
+use reqwest_middleware::{RequestBuilder, Result};
+
+/// Which attempt of a hedged request produced the response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Attempt {
+    Primary,
+    Hedge,
+}
+
+impl Attempt {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Attempt::Primary => "primary",
+            Attempt::Hedge => "hedge",
+        }
+    }
+}
+
+/// Send `request`, and a duplicate of it if no response has arrived after `hedge_after`
+///
+/// Whichever attempt gets its response headers first wins; if one attempt
+/// fails, the other is awaited instead. The losing attempt is dropped,
+/// which aborts it and releases its connection. Requests whose body cannot
+/// be cloned (streaming uploads) are sent once, without hedging.
+pub async fn send_hedged(request: RequestBuilder, hedge_after: Duration) -> (Result<Response>, Attempt) {
+    let Some(hedge) = request.try_clone() else {
+        return (request.send().await, Attempt::Primary);
+    };
+
+    let primary = request.send();
+    tokio::pin!(primary);
+    tokio::select! {
+        result = &mut primary => return (result, Attempt::Primary),
+        _ = tokio::time::sleep(hedge_after) => {}
+    }
+
+    tracing::debug!(?hedge_after, "no response yet, sending hedged request");
+    let hedge = hedge.send();
+    tokio::pin!(hedge);
+    tokio::select! {
+        result = &mut primary => match result {
+            Ok(response) => (Ok(response), Attempt::Primary),
+            Err(_) => (hedge.await, Attempt::Hedge),
+        },
+        result = &mut hedge => match result {
+            Ok(response) => (Ok(response), Attempt::Hedge),
+            Err(_) => (primary.await, Attempt::Primary),
+        },
+    }
+}
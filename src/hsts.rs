@@ -0,0 +1,242 @@
+//! HTTP Strict Transport Security (RFC 6797): hosts that asked to be
+//! reached over HTTPS only, for the `hsts` option
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reqwest::header::{HeaderMap, STRICT_TRANSPORT_SECURITY};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use url::Host;
+
+use crate::errors::TransportError;
+
+/// How far a refreshed expiry may drift from the saved one before the file
+/// is rewritten, so a busy host doesn't cost a write per response
+const SAVE_SLACK_SECS: u64 = 3600;
+
+/// Where a transport keeps its HSTS hosts
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct HstsConfig {
+    /// JSON file the hosts are loaded from and saved to, if any
+    pub path: Option<PathBuf>,
+}
+
+/// A known HSTS host's policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HstsEntry {
+    /// Unix time (seconds) at which the policy expires
+    pub expires: u64,
+    /// Whether the policy covers the host's subdomains too
+    pub include_subdomains: bool,
+}
+
+/// The policy a `Strict-Transport-Security` header sets
+#[derive(Debug, PartialEq, Eq)]
+struct Policy {
+    max_age: u64,
+    include_subdomains: bool,
+}
+
+impl Policy {
+    /// Parse a header value as RFC 6797 §6.1 has it: `;`-separated
+    /// directives with case-insensitive names and optionally quoted
+    /// values, each at most once, and a required `max-age`
+    ///
+    /// Unknown directives are skipped; a malformed header sets nothing.
+    fn parse(value: &str) -> Option<Self> {
+        let mut max_age = None;
+        let mut include_subdomains = false;
+        let mut seen = HashSet::new();
+        for directive in value.split(';').map(str::trim).filter(|directive| !directive.is_empty()) {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim())),
+                None => (directive, None),
+            };
+            let name = name.to_ascii_lowercase();
+            if !seen.insert(name.clone()) {
+                return None;
+            }
+            match (name.as_str(), value) {
+                ("max-age", Some(value)) => {
+                    let value = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value);
+                    max_age = Some(value.parse().ok()?);
+                }
+                ("includesubdomains", None) => include_subdomains = true,
+                ("max-age", None) | ("includesubdomains", Some(_)) => return None,
+                _ => {}
+            }
+        }
+        Some(Self { max_age: max_age?, include_subdomains })
+    }
+}
+
+/// The hosts a transport has seen HSTS policies from
+///
+/// Policies are only taken from HTTPS responses of hosts named by domain,
+/// and not at all with `verify=False`, which can't tell a genuine host from
+/// an impostor (RFC 6797 §8.1).
+#[derive(Debug)]
+pub struct HstsStore {
+    path: Option<PathBuf>,
+    verify: bool,
+    hosts: Mutex<BTreeMap<String, HstsEntry>>,
+}
+
+impl HstsStore {
+    /// A store holding the unexpired hosts saved at `config.path`, if any
+    ///
+    /// A missing file starts the store empty. So does an unreadable one,
+    /// with a warning; it is rewritten on the next change.
+    pub fn new(config: &HstsConfig, verify: bool) -> Self {
+        let mut hosts = config.path.as_deref().map(load).unwrap_or_default();
+        let now = unix_now();
+        hosts.retain(|_, entry| entry.expires > now);
+        Self { path: config.path.clone(), verify, hosts: Mutex::new(hosts) }
+    }
+
+    /// Upgrade `url` to HTTPS if its host is a known HSTS host
+    ///
+    /// The default port becomes 443 and others are kept (RFC 6797 §8.3).
+    /// With `verify=False`, a request to a known host is refused instead of
+    /// being sent without certificate checks.
+    pub fn enforce(&self, url: &mut Url) -> Result<(), TransportError> {
+        let Some(Host::Domain(host)) = url.host() else {
+            return Ok(());
+        };
+        if !self.is_known(host) {
+            return Ok(());
+        }
+        if !self.verify {
+            return Err(TransportError::SSLError(format!(
+                "refusing to connect to HSTS host {} with verify=False",
+                host
+            )));
+        }
+        if url.scheme() == "http" {
+            // Always allowed between two special schemes
+            let _ = url.set_scheme("https");
+        }
+        Ok(())
+    }
+
+    /// Take the policy from the `Strict-Transport-Security` header of a
+    /// response from `url`; `max-age=0` forgets the host
+    pub fn record(&self, url: &Url, headers: &HeaderMap) {
+        if !self.verify || url.scheme() != "https" {
+            return;
+        }
+        let Some(Host::Domain(host)) = url.host() else {
+            return;
+        };
+        // Only the first header counts (RFC 6797 §8.1)
+        let Some(policy) =
+            headers.get(STRICT_TRANSPORT_SECURITY).and_then(|value| value.to_str().ok()).and_then(Policy::parse)
+        else {
+            return;
+        };
+
+        let host = normalize(host);
+        let mut hosts = self.hosts.lock().unwrap();
+        let changed = if policy.max_age == 0 {
+            hosts.remove(&host).is_some()
+        } else {
+            let entry = HstsEntry {
+                expires: unix_now().saturating_add(policy.max_age),
+                include_subdomains: policy.include_subdomains,
+            };
+            hosts.insert(host, entry).is_none_or(|old| {
+                old.include_subdomains != entry.include_subdomains
+                    || old.expires.abs_diff(entry.expires) >= SAVE_SLACK_SECS
+            })
+        };
+        if changed {
+            if let Some(path) = &self.path {
+                save(path, &hosts);
+            }
+        }
+    }
+
+    /// Whether requests to `host` must use HTTPS: it, or a parent domain
+    /// whose policy includes subdomains, has an unexpired policy
+    pub fn is_known(&self, host: &str) -> bool {
+        let host = normalize(host);
+        let now = unix_now();
+        let hosts = self.hosts.lock().unwrap();
+        let mut domain = host.as_str();
+        let mut exact = true;
+        loop {
+            if let Some(entry) = hosts.get(domain) {
+                if entry.expires > now && (exact || entry.include_subdomains) {
+                    return true;
+                }
+            }
+            match domain.split_once('.') {
+                Some((_, parent)) => {
+                    domain = parent;
+                    exact = false;
+                }
+                None => return false,
+            }
+        }
+    }
+
+    /// `hsts_hosts()`'s result: each unexpired host mapped to a dict with
+    /// `include_subdomains` and the seconds until it expires, `expires_in`
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let now = unix_now();
+        let result = PyDict::new(py);
+        for (host, entry) in self.hosts.lock().unwrap().iter().filter(|(_, entry)| entry.expires > now) {
+            let policy = PyDict::new(py);
+            policy.set_item("include_subdomains", entry.include_subdomains)?;
+            policy.set_item("expires_in", entry.expires - now)?;
+            result.set_item(host, policy)?;
+        }
+        Ok(result.into())
+    }
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Hosts compare case-insensitively and without a trailing dot
+fn normalize(host: &str) -> String {
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn load(path: &Path) -> BTreeMap<String, HstsEntry> {
+    let contents = match std::fs::read(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return BTreeMap::new(),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "can't read hsts file, starting empty");
+            return BTreeMap::new();
+        }
+    };
+    serde_json::from_slice(&contents).unwrap_or_else(|e| {
+        tracing::warn!(path = %path.display(), error = %e, "invalid hsts file, starting empty");
+        BTreeMap::new()
+    })
+}
+
+/// Write the unexpired `hosts` to `path`, through a temporary file so a
+/// crash mid-write leaves the old contents
+fn save(path: &Path, hosts: &BTreeMap<String, HstsEntry>) {
+    let now = unix_now();
+    let live: BTreeMap<_, _> = hosts.iter().filter(|(_, entry)| entry.expires > now).collect();
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let result = serde_json::to_vec_pretty(&live)
+        .map_err(std::io::Error::from)
+        .and_then(|contents| std::fs::write(&temporary, contents))
+        .and_then(|()| std::fs::rename(&temporary, path));
+    if let Err(e) = result {
+        tracing::warn!(path = %path.display(), error = %e, "can't save hsts file");
+    }
+}
@@ -0,0 +1,457 @@
+//! JSON request bodies serialized from Python objects, and JSON response
+//! bodies parsed into them, in Rust
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE};
+use serde::de::{DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::{SerializeMap, SerializeSeq};
+use serde::{Deserializer, Serialize, Serializer};
+
+use crate::errors::{DecodingError, TransportError};
+
+/// Deepest nesting serialized, which also stops circular references
+const MAX_DEPTH: usize = 512;
+
+/// How to serialize integers outside the 64-bit range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BigInts {
+    /// Raise `ValueError`, since many JSON parsers would lose precision
+    Error,
+    /// Write the digits as a JSON string
+    String,
+}
+
+/// How to serialize `nan` and the infinities, which JSON can't represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonFinite {
+    /// Raise `ValueError`
+    Error,
+    /// Write `null`
+    Null,
+}
+
+/// Options for `json_dumps` and the `json_options` extension
+#[derive(Debug, Clone)]
+pub struct JsonOptions {
+    pub big_ints: BigInts,
+    pub nan: NonFinite,
+    /// Called with any other object; its result is serialized instead, like
+    /// `json.dumps(default=...)`
+    pub default: Option<PyObject>,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self { big_ints: BigInts::Error, nan: NonFinite::Error, default: None }
+    }
+}
+
+impl JsonOptions {
+    /// Parse the options from their string forms
+    pub fn new(big_ints: &str, nan: &str, default: Option<PyObject>) -> PyResult<Self> {
+        let big_ints = match big_ints {
+            "error" => BigInts::Error,
+            "string" => BigInts::String,
+            _ => {
+                return Err(PyValueError::new_err(format!(
+                    "Invalid big_ints: {:?} (expected \"error\" or \"string\")",
+                    big_ints
+                )));
+            }
+        };
+        let nan = match nan {
+            "error" => NonFinite::Error,
+            "null" => NonFinite::Null,
+            _ => {
+                return Err(PyValueError::new_err(format!("Invalid nan: {:?} (expected \"error\" or \"null\")", nan)));
+            }
+        };
+        Ok(Self { big_ints, nan, default })
+    }
+
+    /// Parse the `json_options` extension:
+    /// `{"big_ints": "error" | "string", "nan": "error" | "null", "default": callable}`
+    fn from_py(value: &PyAny) -> PyResult<Self> {
+        if value.is_none() {
+            return Ok(Self::default());
+        }
+        let options: &PyDict = value.downcast()?;
+        let (mut big_ints, mut nan, mut default) = ("error", "error", None);
+        for (key, value) in options {
+            match key.extract::<&str>()? {
+                "big_ints" => big_ints = value.extract()?,
+                "nan" => nan = value.extract()?,
+                "default" if value.is_none() => default = None,
+                "default" if value.is_callable() => default = Some(value.into_py(value.py())),
+                "default" => return Err(PyValueError::new_err("Invalid json_options default: expected a callable")),
+                key => {
+                    return Err(PyValueError::new_err(format!(
+                        "Unsupported json_options key: {} (supported: big_ints, nan, default)",
+                        key
+                    )));
+                }
+            }
+        }
+        Self::new(big_ints, nan, default)
+    }
+}
+
+/// Serialize `obj` to JSON bytes
+///
+/// Supports `dict` (with `str` or `int` keys), `list`, `tuple`, `str`,
+/// `int`, `float`, `bool` and `None`; other objects go through
+/// `options.default` or raise `TypeError`. Strings are written as UTF-8
+/// rather than `\u` escapes.
+pub fn to_json_bytes(obj: &PyAny, options: &JsonOptions) -> PyResult<Vec<u8>> {
+    let error = RefCell::new(None);
+    let mut body = Vec::new();
+    let value = PyJson { obj, options, depth: 0, error: &error };
+    match serde_json::to_writer(&mut body, &value) {
+        Ok(()) => Ok(body),
+        Err(e) => Err(error.into_inner().unwrap_or_else(|| PyValueError::new_err(e.to_string()))),
+    }
+}
+
+/// The body from a request's `json_body` extension, if it set one
+///
+/// `json_options` configures the serialization (see `JsonOptions::from_py`).
+pub fn json_body_from_extensions(py_extensions: &PyAny) -> PyResult<Option<Vec<u8>>> {
+    let Ok(extensions) = py_extensions.downcast::<PyDict>() else {
+        return Ok(None);
+    };
+    let Some(obj) = extensions.get_item("json_body")? else {
+        return Ok(None);
+    };
+    let options = match extensions.get_item("json_options")? {
+        Some(options) => JsonOptions::from_py(options)?,
+        None => JsonOptions::default(),
+    };
+    to_json_bytes(obj, &options).map(Some)
+}
+
+/// Describe a JSON body in `headers`: `Content-Type` unless the caller set
+/// one, and no stale `Content-Length` from the request's original content
+pub fn set_json_headers(headers: &mut HeaderMap) {
+    headers.remove(CONTENT_LENGTH);
+    if !headers.contains_key(CONTENT_TYPE) {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    }
+}
+
+/// Fail unless `headers` describe a JSON body: `application/json` or a
+/// `+json` type such as `application/problem+json`, whatever its parameters
+pub fn check_json_content_type(headers: &HeaderMap) -> Result<(), TransportError> {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    if essence == "application/json" || (essence.starts_with("application/") && essence.ends_with("+json")) {
+        return Ok(());
+    }
+    Err(TransportError::RemoteProtocolError(match content_type {
+        "" => "expected a JSON response but it has no Content-Type".to_string(),
+        content_type => format!("expected a JSON response but got Content-Type {:?}", content_type),
+    }))
+}
+
+/// A Python object being serialized
+///
+/// serde errors can't carry a `PyErr`, so the first one is parked in
+/// `error` and returned once serialization has unwound.
+struct PyJson<'a, 'py> {
+    obj: &'py PyAny,
+    options: &'a JsonOptions,
+    depth: usize,
+    error: &'a RefCell<Option<PyErr>>,
+}
+
+impl<'a, 'py> PyJson<'a, 'py> {
+    fn child(&self, obj: &'py PyAny) -> Self {
+        Self { obj, options: self.options, depth: self.depth + 1, error: self.error }
+    }
+
+    fn fail<E: serde::ser::Error>(&self, err: PyErr) -> E {
+        let message = err.to_string();
+        self.error.borrow_mut().get_or_insert(err);
+        E::custom(message)
+    }
+
+    fn key(&self, key: &'py PyAny) -> PyResult<Cow<'py, str>> {
+        if let Ok(key) = key.downcast::<PyString>() {
+            return Ok(Cow::Borrowed(key.to_str()?));
+        }
+        if key.is_instance_of::<PyLong>() && !key.is_instance_of::<PyBool>() {
+            return Ok(Cow::Owned(key.str()?.extract()?));
+        }
+        Err(PyTypeError::new_err(format!("json_body keys must be str or int, not {}", key.get_type().name()?)))
+    }
+}
+
+impl Serialize for PyJson<'_, '_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let obj = self.obj;
+        if self.depth > MAX_DEPTH {
+            return Err(self.fail(PyValueError::new_err(format!(
+                "json_body nests deeper than {} levels (circular reference?)",
+                MAX_DEPTH
+            ))));
+        }
+
+        if let Ok(s) = obj.downcast::<PyString>() {
+            serializer.serialize_str(s.to_str().map_err(|e| self.fail(e))?)
+        } else if obj.is_none() {
+            serializer.serialize_unit()
+        } else if let Ok(b) = obj.downcast::<PyBool>() {
+            serializer.serialize_bool(b.is_true())
+        } else if let Ok(i) = obj.downcast::<PyLong>() {
+            if let Ok(i) = i.extract::<i64>() {
+                serializer.serialize_i64(i)
+            } else if let Ok(u) = i.extract::<u64>() {
+                serializer.serialize_u64(u)
+            } else {
+                let digits = i.str().map_err(|e| self.fail(e))?.to_str().map_err(|e| self.fail(e))?;
+                match self.options.big_ints {
+                    BigInts::String => serializer.serialize_str(digits),
+                    BigInts::Error => Err(self.fail(PyValueError::new_err(format!(
+                        "json_body integer {} doesn't fit in 64 bits (set big_ints=\"string\" to send it as a string)",
+                        digits
+                    )))),
+                }
+            }
+        } else if let Ok(f) = obj.downcast::<PyFloat>() {
+            let f = f.value();
+            match (f.is_finite(), self.options.nan) {
+                (true, _) => serializer.serialize_f64(f),
+                (false, NonFinite::Null) => serializer.serialize_unit(),
+                (false, NonFinite::Error) => Err(self.fail(PyValueError::new_err(format!(
+                    "json_body float {} is not valid JSON (set nan=\"null\" to send null)",
+                    f
+                )))),
+            }
+        } else if let Ok(dict) = obj.downcast::<PyDict>() {
+            let mut map = serializer.serialize_map(Some(dict.len()))?;
+            for (key, value) in dict {
+                map.serialize_entry(&self.key(key).map_err(|e| self.fail(e))?, &self.child(value))?;
+            }
+            map.end()
+        } else if let Ok(list) = obj.downcast::<PyList>() {
+            let mut seq = serializer.serialize_seq(Some(list.len()))?;
+            for item in list {
+                seq.serialize_element(&self.child(item))?;
+            }
+            seq.end()
+        } else if let Ok(tuple) = obj.downcast::<PyTuple>() {
+            let mut seq = serializer.serialize_seq(Some(tuple.len()))?;
+            for item in tuple {
+                seq.serialize_element(&self.child(item))?;
+            }
+            seq.end()
+        } else if let Some(default) = &self.options.default {
+            let replacement = default.call1(obj.py(), (obj,)).map_err(|e| self.fail(e))?;
+            self.child(replacement.into_ref(obj.py())).serialize(serializer)
+        } else {
+            let name = obj.get_type().name().map_err(|e| self.fail(e))?;
+            Err(self.fail(PyTypeError::new_err(format!("Object of type {} is not JSON serializable", name))))
+        }
+    }
+}
+
+/// The key under which serde_json's `arbitrary_precision` mode hands over
+/// numbers that don't fit `i64`/`u64` (floats and big integers) as text
+const NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Parse a JSON document into Python objects, as `json.loads` would
+///
+/// Objects become dicts (the last of duplicate keys wins), integers of any
+/// size ints and other numbers floats. Unlike `json.loads`, `NaN` and
+/// `Infinity` and unpaired surrogate escapes are rejected, and nesting is
+/// limited to 128 levels. Invalid JSON raises `DecodingError`.
+pub fn from_json_bytes(py: Python, data: &[u8]) -> PyResult<PyObject> {
+    let error = RefCell::new(None);
+    let keys = RefCell::new(HashMap::new());
+    let mut deserializer = serde_json::Deserializer::from_slice(data);
+    let seed = PyObjectSeed { py, input: data, keys: &keys, error: &error };
+    match seed.deserialize(&mut deserializer).and_then(|obj| deserializer.end().map(|()| obj)) {
+        Ok(obj) => Ok(obj),
+        Err(e) => Err(error.into_inner().unwrap_or_else(|| decoding_error(py, &e, data))),
+    }
+}
+
+/// `DecodingError` for `err`, with its position in `data`
+fn decoding_error(py: Python, err: &serde_json::Error, data: &[u8]) -> PyErr {
+    let (line, column) = (err.line(), err.column());
+    let line_start: usize = data.split(|b| *b == b'\n').take(line.saturating_sub(1)).map(|l| l.len() + 1).sum();
+    let pos = (line_start + column.saturating_sub(1)).min(data.len());
+    let error = DecodingError::new_err(format!("Invalid JSON response body: {}", err));
+    // Setting attributes on a fresh exception instance can't fail
+    let value = error.value(py);
+    let _ = value.setattr("lineno", line);
+    let _ = value.setattr("colno", column);
+    let _ = value.setattr("pos", pos);
+    error
+}
+
+/// Builds each JSON value straight into a Python object
+///
+/// Objects are made through the C API rather than pyo3's `&PyAny` wrappers,
+/// which would hold every string, list and dict of the document in the GIL
+/// pool until the call returns. As when serializing, Python errors are
+/// parked in `error`.
+#[derive(Clone, Copy)]
+struct PyObjectSeed<'a, 'py> {
+    py: Python<'py>,
+    /// The document, to tell its keys apart from serde_json's number token
+    input: &'a [u8],
+    /// Key strings already made, shared by every object like `json.loads`
+    keys: &'a RefCell<HashMap<String, PyObject>>,
+    error: &'a RefCell<Option<PyErr>>,
+}
+
+impl PyObjectSeed<'_, '_> {
+    /// Take ownership of a new reference returned by the C API
+    fn owned<E: serde::de::Error>(&self, ptr: *mut ffi::PyObject) -> Result<PyObject, E> {
+        // SAFETY: only called with the result of a C API call returning a new
+        // reference, or null with an exception set
+        unsafe { PyObject::from_owned_ptr_or_err(self.py, ptr) }.map_err(|e| self.fail(e))
+    }
+
+    /// Check the status returned by a C API call that sets an exception on -1
+    fn check<E: serde::de::Error>(&self, status: std::os::raw::c_int) -> Result<(), E> {
+        if status == -1 {
+            return Err(self.fail(PyErr::fetch(self.py)));
+        }
+        Ok(())
+    }
+
+    fn string<E: serde::de::Error>(&self, v: &str) -> Result<PyObject, E> {
+        // SAFETY: `v` is valid UTF-8 of the given length
+        self.owned(unsafe { ffi::PyUnicode_FromStringAndSize(v.as_ptr().cast(), v.len() as ffi::Py_ssize_t) })
+    }
+
+    fn key<E: serde::de::Error>(&self, key: &str) -> Result<PyObject, E> {
+        if let Some(cached) = self.keys.borrow().get(key) {
+            return Ok(cached.clone_ref(self.py));
+        }
+        let string = self.string(key)?;
+        self.keys.borrow_mut().insert(key.to_owned(), string.clone_ref(self.py));
+        Ok(string)
+    }
+
+    fn fail<E: serde::de::Error>(&self, err: PyErr) -> E {
+        let message = err.to_string();
+        self.error.borrow_mut().get_or_insert(err);
+        E::custom(message)
+    }
+
+    /// A float, or an integer too big for 64 bits, from its JSON text
+    fn number<E: serde::de::Error>(&self, text: &str) -> Result<PyObject, E> {
+        if text.contains(['.', 'e', 'E']) {
+            let f: f64 = text.parse().map_err(E::custom)?;
+            return Ok(f.into_py(self.py));
+        }
+        let int = self.py.get_type::<PyLong>().call1((text,)).map_err(|e| self.fail(e))?;
+        Ok(int.into_py(self.py))
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for PyObjectSeed<'_, '_> {
+    type Value = PyObject;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<PyObject, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PyObjectSeed<'_, '_> {
+    type Value = PyObject;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<PyObject, E> {
+        Ok(self.py.None())
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<PyObject, E> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<PyObject, E> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<PyObject, E> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<PyObject, E> {
+        Ok(v.into_py(self.py))
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<PyObject, E> {
+        self.string(v)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PyObject, A::Error> {
+        // SAFETY: plain C API calls with the GIL held; PyList_Append takes its
+        // own reference to `item`
+        let list = self.owned(unsafe { ffi::PyList_New(0) })?;
+        while let Some(item) = seq.next_element_seed(self)? {
+            self.check(unsafe { ffi::PyList_Append(list.as_ptr(), item.as_ptr()) })?;
+        }
+        Ok(list)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<PyObject, A::Error> {
+        // SAFETY: as for lists, PyDict_SetItem takes its own references
+        let dict = self.owned(unsafe { ffi::PyDict_New() })?;
+        while let Some(key) = map.next_key_seed(KeySeed)? {
+            // serde_json's own key points outside the document
+            if key == NUMBER_TOKEN && !self.input.as_ptr_range().contains(&key.as_ptr()) {
+                return self.number(&map.next_value::<String>()?);
+            }
+            let key = self.key(&key)?;
+            let value = map.next_value_seed(self)?;
+            self.check(unsafe { ffi::PyDict_SetItem(dict.as_ptr(), key.as_ptr(), value.as_ptr()) })?;
+        }
+        Ok(dict)
+    }
+}
+
+/// An object key, borrowed from the document unless it has escapes
+struct KeySeed;
+
+impl<'de> DeserializeSeed<'de> for KeySeed {
+    type Value = Cow<'de, str>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Cow<'de, str>, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeySeed {
+    type Value = Cow<'de, str>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an object key")
+    }
+
+    fn visit_borrowed_str<E>(self, v: &'de str) -> Result<Cow<'de, str>, E> {
+        Ok(Cow::Borrowed(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Cow<'de, str>, E> {
+        Ok(Cow::Owned(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Cow<'de, str>, E> {
+        Ok(Cow::Owned(v))
+    }
+}
@@ -1,21 +1,79 @@
+// pyo3 0.20's `#[pymethods]` expands to impls that newer compilers flag
+#![allow(non_local_definitions)]
+// `ClientConfig::to_json` builds its whole object in one `json!`
+#![recursion_limit = "256"]
+
 use pyo3::prelude::*;
 
-mod transport;
-mod client;
+mod accumulate;
+mod auth;
+mod batch;
+pub mod cert;
+pub mod client;
+pub mod compress;
+pub mod config;
+mod connect;
+mod decompress;
+pub mod dns;
+mod error_detail;
+pub mod errors;
+mod form;
+pub mod handshake;
+#[cfg(feature = "health-server")]
+mod health;
+mod hedge;
+mod hsts;
+mod json;
+mod limit;
+mod mock;
+mod multipart;
+mod pin;
+pub mod pool;
+pub mod probe;
+mod retry;
+mod revocation;
+#[cfg(feature = "sigv4")]
+mod sigv4;
+mod sniff;
+mod socket;
 mod streaming;
-mod errors;
+mod tap;
+mod transport;
+mod upload;
 mod utils;
+mod wire;
 
+use accumulate::ResponseBodyAccumulator;
+use mock::MockTransport;
+use multipart::MultipartBody;
+use streaming::AsyncBodyWriter;
 use transport::{AsyncTransport, SyncTransport};
 
 /// High-performance Rust transport for Python httpx
 #[pymodule]
-fn _rust_httpx(_py: Python, m: &PyModule) -> PyResult<()> {
+fn _rust_httpx(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<AsyncTransport>()?;
     m.add_class::<SyncTransport>()?;
-    
+    m.add_class::<MultipartBody>()?;
+    m.add_class::<MockTransport>()?;
+    m.add_class::<AsyncBodyWriter>()?;
+    m.add_class::<ResponseBodyAccumulator>()?;
+    m.add_function(wrap_pyfunction!(utils::encode_query_params, m)?)?;
+    m.add("UnexpectedStatusError", py.get_type::<errors::UnexpectedStatusError>())?;
+    m.add("DecodingError", py.get_type::<errors::DecodingError>())?;
+
+    // Register with httpx's transport ABCs so isinstance checks pass; httpx
+    // is only needed for that, so the module still loads without it
+    if let Ok(httpx) = py.import("httpx") {
+        httpx.getattr("AsyncBaseTransport")?.call_method1("register", (m.getattr("AsyncTransport")?,))?;
+        httpx.getattr("BaseTransport")?.call_method1("register", (m.getattr("SyncTransport")?,))?;
+        for abc in ["BaseTransport", "AsyncBaseTransport"] {
+            httpx.getattr(abc)?.call_method1("register", (m.getattr("MockTransport")?,))?;
+        }
+    }
+
     // Add version info
     m.add("__version__", env!("CARGO_PKG_VERSION"))?;
-    
+
     Ok(())
-} 
\ No newline at end of file
+}
@@ -0,0 +1,189 @@
+//! Limits on the requests a pool has in flight
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+
+/// Order in which waiting requests are given a freed slot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Fairness {
+    /// Longest-waiting first, so no request starves
+    #[default]
+    Fifo,
+    /// Most recent first, favouring requests whose callers are still warm
+    /// at the cost of tail latency for the oldest
+    Lifo,
+}
+
+/// A request's RFC 9218 priority, from its `priority` extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Priority {
+    /// 0 (most urgent) to 7
+    pub urgency: u8,
+    /// Whether the response is useful as it arrives, rather than only whole
+    pub incremental: bool,
+}
+
+impl Priority {
+    /// The urgency of requests that don't set one
+    pub const DEFAULT_URGENCY: u8 = 3;
+
+    /// The `Priority` header field, e.g. `u=1, i`
+    pub fn header_value(&self) -> String {
+        if self.incremental {
+            format!("u={}, i", self.urgency)
+        } else {
+            format!("u={}", self.urgency)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct State {
+    available: usize,
+    waiters: VecDeque<(u8, oneshot::Sender<Permit>)>,
+}
+
+/// A counting semaphore whose waiters are woken most urgent first, and in
+/// `Fairness` order among equally urgent ones
+///
+/// A freed slot is handed straight to the chosen waiter, so a request
+/// arriving meanwhile can't overtake the queue. Waiters that were
+/// cancelled are skipped.
+#[derive(Debug)]
+pub struct RequestLimiter {
+    fairness: Fairness,
+    state: Mutex<State>,
+}
+
+/// One in-flight slot, released when dropped
+#[derive(Debug)]
+pub struct Permit {
+    limiter: Option<Arc<RequestLimiter>>,
+}
+
+impl RequestLimiter {
+    pub fn new(limit: usize, fairness: Fairness) -> Arc<Self> {
+        Arc::new(Self { fairness, state: Mutex::new(State { available: limit, waiters: VecDeque::new() }) })
+    }
+
+    /// Wait for a slot, ahead of waiters with a higher `urgency` (less urgent)
+    pub async fn acquire(self: &Arc<Self>, urgency: u8) -> Permit {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.available > 0 {
+                state.available -= 1;
+                return Permit { limiter: Some(self.clone()) };
+            }
+            state.waiters.retain(|(_, waiter)| !waiter.is_canceled());
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.push_back((urgency, sender));
+            receiver
+        };
+        // A permit sent to a receiver that is then dropped is dropped with
+        // it, which releases the slot again
+        receiver.await.expect("waiters are only dropped after being sent a permit")
+    }
+
+    fn release(self: &Arc<Self>) {
+        loop {
+            let waiter = {
+                let mut state = self.state.lock().unwrap();
+                let most_urgent = state.waiters.iter().map(|(urgency, _)| *urgency).min();
+                let mut candidates = (0..state.waiters.len()).filter(|&i| Some(state.waiters[i].0) == most_urgent);
+                let next = match self.fairness {
+                    Fairness::Fifo => candidates.next(),
+                    Fairness::Lifo => candidates.next_back(),
+                };
+                match next.and_then(|i| state.waiters.remove(i)).map(|(_, waiter)| waiter) {
+                    Some(waiter) => waiter,
+                    None => {
+                        state.available += 1;
+                        return;
+                    }
+                }
+            };
+            match waiter.send(Permit { limiter: Some(self.clone()) }) {
+                Ok(()) => return,
+                // Cancelled while waiting; disarm the permit and try the next
+                Err(mut permit) => permit.limiter = None,
+            }
+        }
+    }
+}
+
+/// A `RequestLimiter` per host, created on first use
+#[derive(Debug)]
+pub struct HostLimiters {
+    limit: usize,
+    fairness: Fairness,
+    hosts: Mutex<HashMap<String, Arc<RequestLimiter>>>,
+}
+
+impl HostLimiters {
+    pub fn new(limit: usize, fairness: Fairness) -> Self {
+        Self { limit, fairness, hosts: Mutex::new(HashMap::new()) }
+    }
+
+    /// The limiter for `host`
+    ///
+    /// Callers hold it while they wait and their permits hold it after, so
+    /// a limiter only this map holds is idle; those are dropped whenever a
+    /// new host is added, keeping the map to the hosts in use.
+    pub fn get(&self, host: &str) -> Arc<RequestLimiter> {
+        let mut hosts = self.hosts.lock().unwrap();
+        if let Some(limiter) = hosts.get(host) {
+            return limiter.clone();
+        }
+        hosts.retain(|_, limiter| Arc::strong_count(limiter) > 1);
+        let limiter = RequestLimiter::new(self.limit, self.fairness);
+        hosts.insert(host.to_string(), limiter.clone());
+        limiter
+    }
+}
+
+/// The limits on a transport's requests in flight: `max_concurrent_requests`
+/// overall and `max_concurrent_per_host`
+#[derive(Debug)]
+pub struct RequestLimits {
+    limiter: Option<Arc<RequestLimiter>>,
+    host_limiters: Option<HostLimiters>,
+}
+
+impl RequestLimits {
+    pub fn new(
+        max_concurrent_requests: Option<usize>,
+        max_concurrent_per_host: Option<usize>,
+        fairness: Fairness,
+    ) -> Self {
+        Self {
+            limiter: max_concurrent_requests.map(|limit| RequestLimiter::new(limit, fairness)),
+            host_limiters: max_concurrent_per_host.map(|limit| HostLimiters::new(limit, fairness)),
+        }
+    }
+
+    /// Wait for the slots a request to `host` needs: its host's, then the
+    /// overall one
+    ///
+    /// The host's slot comes first so requests queued behind a saturated
+    /// host don't hold slots that requests to other hosts could use.
+    pub async fn acquire(&self, host: Option<&str>, urgency: u8) -> Vec<Permit> {
+        let mut permits = Vec::new();
+        if let (Some(host_limiters), Some(host)) = (&self.host_limiters, host) {
+            permits.push(host_limiters.get(host).acquire(urgency).await);
+        }
+        if let Some(limiter) = &self.limiter {
+            permits.push(limiter.acquire(urgency).await);
+        }
+        permits
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release();
+        }
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::Bytes;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList};
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+
+use crate::errors::TransportError;
+use crate::streaming::{extract_body_bytes_from_python, ByteStream, SyncByteStream};
+use crate::utils::{
+    convert_headers_to_python, create_response_object, extract_extensions, extract_headers, extract_method,
+    extract_url, get_request_body, json_to_python,
+};
+
+/// A transport failure a route can inject
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MockError {
+    ConnectTimeout,
+    ConnectError,
+    ReadTimeout,
+    /// The connection resets after the route's body (or chunks) was sent
+    Reset,
+}
+
+impl MockError {
+    fn parse(name: &str) -> PyResult<Self> {
+        match name {
+            "connect_timeout" => Ok(MockError::ConnectTimeout),
+            "connect_error" => Ok(MockError::ConnectError),
+            "read_timeout" => Ok(MockError::ReadTimeout),
+            "reset" => Ok(MockError::Reset),
+            _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid error: {:?} (expected \"connect_timeout\", \"connect_error\", \"read_timeout\" or \"reset\")",
+                name
+            ))),
+        }
+    }
+
+    fn to_transport_error(self, url: &str) -> TransportError {
+        match self {
+            MockError::ConnectTimeout => {
+                TransportError::ConnectTimeout(format!("mock: connecting to {} timed out", url))
+            }
+            MockError::ConnectError => TransportError::ConnectError(format!("mock: connecting to {} failed", url)),
+            MockError::ReadTimeout => TransportError::ReadTimeout(format!("mock: reading from {} timed out", url)),
+            MockError::Reset => TransportError::ReadError(format!("mock: connection to {} reset by peer", url)),
+        }
+    }
+}
+
+/// A registered route and its canned response
+#[derive(Debug)]
+struct MockRoute {
+    /// None matches any method
+    method: Option<Method>,
+    /// URL glob, where `*` matches any run of characters
+    pattern: String,
+    status: u16,
+    headers: HeaderMap,
+    /// bytes, str, or a callable taking the request and returning either
+    body: Option<PyObject>,
+    /// Stream these chunks instead of returning a buffered body
+    chunks: Option<Vec<Bytes>>,
+    delay: Option<Duration>,
+    error: Option<MockError>,
+}
+
+impl MockRoute {
+    fn matches(&self, method: &Method, url: &str) -> bool {
+        self.method.as_ref().is_none_or(|m| m == method) && glob_match(&self.pattern, url)
+    }
+}
+
+/// A request received by a `MockTransport`
+#[derive(Debug, Clone)]
+struct RecordedRequest {
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    body: Vec<u8>,
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+/// The response a route produced for one request
+struct MockResponse {
+    url: String,
+    status: u16,
+    headers: HeaderMap,
+    body: Bytes,
+    chunks: Option<Vec<Bytes>>,
+    delay: Option<Duration>,
+    error: Option<MockError>,
+    extensions: HashMap<String, serde_json::Value>,
+}
+
+impl MockResponse {
+    /// Fail before any response is returned, for all but mid-body resets
+    fn early_error(&self) -> Option<TransportError> {
+        self.error.filter(|error| *error != MockError::Reset).map(|error| error.to_transport_error(&self.url))
+    }
+
+    /// Body chunks, ending with the reset error when one is injected
+    fn into_chunks(self) -> Vec<Result<Bytes, TransportError>> {
+        let reset = (self.error == Some(MockError::Reset)).then(|| MockError::Reset.to_transport_error(&self.url));
+        let chunks = self.chunks.unwrap_or_else(|| vec![self.body]);
+        chunks.into_iter().map(Ok).chain(reset.map(Err)).collect()
+    }
+
+    fn is_streaming(&self) -> bool {
+        self.chunks.is_some()
+    }
+}
+
+/// A transport answering from registered routes instead of the network
+///
+/// Routes are tried in registration order. Unmatched requests go to the
+/// `passthrough` transport if one was given, and raise `LookupError`
+/// otherwise. Every request is recorded, whether matched or not.
+#[pyclass]
+pub struct MockTransport {
+    routes: Mutex<Vec<MockRoute>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    passthrough: Option<PyObject>,
+}
+
+#[pymethods]
+impl MockTransport {
+    #[new]
+    #[pyo3(signature = (passthrough=None))]
+    fn new(passthrough: Option<PyObject>) -> Self {
+        Self { routes: Mutex::new(Vec::new()), requests: Mutex::new(Vec::new()), passthrough }
+    }
+
+    /// Register a canned response for `method` (or `"*"`) and a URL glob
+    ///
+    /// `body` is bytes, str, or a callable taking the request and returning
+    /// either. `chunks` streams a list of byte chunks instead. `delay` (in
+    /// seconds) waits before responding. `error` injects a failure:
+    /// `"connect_timeout"`, `"connect_error"` or `"read_timeout"` raise
+    /// instead of responding, and `"reset"` fails the body after it was sent.
+    #[pyo3(signature = (method, url, status=200, headers=None, body=None, chunks=None, delay=None, error=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn add_route(
+        &self,
+        method: &str,
+        url: String,
+        status: u16,
+        headers: Option<&PyAny>,
+        body: Option<PyObject>,
+        chunks: Option<Vec<Vec<u8>>>,
+        delay: Option<f64>,
+        error: Option<&str>,
+    ) -> PyResult<()> {
+        let method =
+            match method {
+                "*" => None,
+                method => Some(method.to_ascii_uppercase().parse::<Method>().map_err(|_| {
+                    pyo3::exceptions::PyValueError::new_err(format!("Invalid HTTP method: {}", method))
+                })?),
+            };
+        let delay = delay
+            .map(|seconds| {
+                Duration::try_from_secs_f64(seconds)
+                    .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid delay: {}", e)))
+            })
+            .transpose()?;
+
+        self.routes.lock().unwrap().push(MockRoute {
+            method,
+            pattern: url,
+            status,
+            headers: headers.map(extract_headers).transpose()?.unwrap_or_default(),
+            body,
+            chunks: chunks.map(|chunks| chunks.into_iter().map(Bytes::from).collect()),
+            delay,
+            error: error.map(MockError::parse).transpose()?,
+        });
+        Ok(())
+    }
+
+    /// Requests received so far, as dicts of method, url, headers, content
+    /// and extensions
+    #[getter]
+    fn requests(&self, py: Python) -> PyResult<PyObject> {
+        let requests = PyList::empty(py);
+        for recorded in self.requests.lock().unwrap().iter() {
+            let request = PyDict::new(py);
+            request.set_item("method", recorded.method.as_str())?;
+            request.set_item("url", &recorded.url)?;
+            request.set_item("headers", convert_headers_to_python(&recorded.headers, py)?)?;
+            request.set_item("content", PyBytes::new(py, &recorded.body))?;
+            let extensions = PyDict::new(py);
+            for (key, value) in &recorded.extensions {
+                extensions.set_item(key, json_to_python(py, value))?;
+            }
+            request.set_item("extensions", extensions)?;
+            requests.append(request)?;
+        }
+        Ok(requests.into())
+    }
+
+    /// Forget recorded requests; routes are kept
+    fn reset(&self) {
+        self.requests.lock().unwrap().clear();
+    }
+
+    /// Handle a sync HTTP request
+    fn handle_request(&self, py: Python, request: &PyAny) -> PyResult<PyObject> {
+        let response = match self.dispatch(py, request)? {
+            Some(response) => response,
+            None => {
+                return Ok(self.passthrough(py)?.into_ref(py).call_method1("handle_request", (request,))?.into());
+            }
+        };
+
+        if let Some(delay) = response.delay {
+            py.allow_threads(|| std::thread::sleep(delay));
+        }
+        if let Some(error) = response.early_error() {
+            return Err(error.into());
+        }
+
+        let (status, headers, extensions) = (response.status, response.headers.clone(), response.extensions.clone());
+        if response.is_streaming() {
+            let stream = Py::new(py, SyncByteStream::from_chunks(response.into_chunks()))?;
+            create_response_object(
+                py,
+                status,
+                http::Version::HTTP_11,
+                headers,
+                None,
+                Some(stream.to_object(py)),
+                Some(extensions),
+            )
+        } else {
+            let body = buffer(response.into_chunks())?;
+            create_response_object(
+                py,
+                status,
+                http::Version::HTTP_11,
+                headers,
+                Some(PyBytes::new(py, &body).into()),
+                None,
+                Some(extensions),
+            )
+        }
+    }
+
+    /// Handle an async HTTP request
+    ///
+    /// Delays are tokio sleeps, so they can be cancelled by `asyncio`
+    /// timeouts like a real request.
+    fn handle_async_request<'py>(&self, py: Python<'py>, request: &'py PyAny) -> PyResult<&'py PyAny> {
+        let response = match self.dispatch(py, request)? {
+            Some(response) => response,
+            None => {
+                return self.passthrough(py)?.into_ref(py).call_method1("handle_async_request", (request,));
+            }
+        };
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            if let Some(delay) = response.delay {
+                tokio::time::sleep(delay).await;
+            }
+            if let Some(error) = response.early_error() {
+                return Err(error.into());
+            }
+
+            let (status, headers, extensions) =
+                (response.status, response.headers.clone(), response.extensions.clone());
+            if response.is_streaming() {
+                let chunks = response.into_chunks();
+                let stream = match chunks.as_slice() {
+                    [Ok(chunk)] => ByteStream::from_bytes(chunk.clone()),
+                    _ => ByteStream::from_bytes_iter(chunks.into_iter()),
+                };
+                Python::with_gil(|py| {
+                    let stream = Py::new(py, stream)?;
+                    create_response_object(
+                        py,
+                        status,
+                        http::Version::HTTP_11,
+                        headers,
+                        None,
+                        Some(stream.to_object(py)),
+                        Some(extensions),
+                    )
+                })
+            } else {
+                let body = buffer(response.into_chunks())?;
+                Python::with_gil(|py| {
+                    create_response_object(
+                        py,
+                        status,
+                        http::Version::HTTP_11,
+                        headers,
+                        Some(PyBytes::new(py, &body).into()),
+                        None,
+                        Some(extensions),
+                    )
+                })
+            }
+        })
+    }
+
+    fn close(&self) {}
+
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        pyo3_asyncio::tokio::future_into_py(py, async move { Ok(()) })
+    }
+}
+
+impl MockTransport {
+    /// Record `request` and build the response of the first matching route
+    ///
+    /// Returns None when no route matches and a passthrough transport is set.
+    fn dispatch(&self, py: Python, request: &PyAny) -> PyResult<Option<MockResponse>> {
+        let method = extract_method(request.getattr("method")?)?;
+        let url = extract_url(request.getattr("url")?)?.to_string();
+        let recorded = RecordedRequest {
+            method,
+            url,
+            headers: extract_headers(request.getattr("headers")?)?,
+            body: get_request_body(request).map(extract_body_bytes_from_python).transpose()?.unwrap_or_default(),
+            extensions: extract_extensions(request.getattr("extensions")?)?.values,
+        };
+        self.requests.lock().unwrap().push(recorded.clone());
+
+        let routes = self.routes.lock().unwrap();
+        let Some(route) = routes.iter().find(|route| route.matches(&recorded.method, &recorded.url)) else {
+            return match self.passthrough {
+                Some(_) => Ok(None),
+                None => Err(pyo3::exceptions::PyLookupError::new_err(format!(
+                    "No mock route matches {} {}",
+                    recorded.method, recorded.url
+                ))),
+            };
+        };
+
+        let body = match &route.body {
+            None => Bytes::new(),
+            Some(body) => {
+                let body = body.as_ref(py);
+                let body = if body.is_callable() { body.call1((request,))? } else { body };
+                Bytes::from(extract_body_bytes_from_python(body)?)
+            }
+        };
+        Ok(Some(MockResponse {
+            url: recorded.url,
+            status: route.status,
+            headers: route.headers.clone(),
+            body,
+            chunks: route.chunks.clone(),
+            delay: route.delay,
+            error: route.error,
+            extensions: recorded.extensions,
+        }))
+    }
+
+    fn passthrough(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.passthrough.as_ref().expect("dispatched to passthrough").clone_ref(py))
+    }
+}
+
+/// Join chunks into a buffered body, failing on an injected error
+fn buffer(chunks: Vec<Result<Bytes, TransportError>>) -> Result<Bytes, TransportError> {
+    let mut body = Vec::new();
+    for chunk in chunks {
+        body.extend_from_slice(&chunk?);
+    }
+    Ok(Bytes::from(body))
+}
+
+/// Match `text` against `pattern`, where `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use pyo3::prelude::*;
+use reqwest::multipart::{Form, Part};
+use tokio_util::io::ReaderStream;
+
+/// A single part of a multipart body
+#[derive(Debug, Clone)]
+enum MultipartPart {
+    Field { name: String, value: String },
+    File { name: String, path: PathBuf, filename: Option<String>, content_type: Option<String> },
+}
+
+/// A multipart/form-data request body built and streamed in Rust
+///
+/// Boundary generation and part headers are handled by reqwest. File parts
+/// are opened when the request is sent and streamed from disk, so large
+/// uploads never pass through Python. Content-Length is computed because
+/// every part has a known size.
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct MultipartBody {
+    parts: Vec<MultipartPart>,
+}
+
+#[pymethods]
+impl MultipartBody {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a text field
+    fn add_field(&mut self, name: String, value: String) {
+        self.parts.push(MultipartPart::Field { name, value });
+    }
+
+    /// Add a file field streamed from `path`
+    ///
+    /// `filename` defaults to the file name of `path` and `content_type`
+    /// to `application/octet-stream`.
+    #[pyo3(signature = (name, path, filename=None, content_type=None))]
+    fn add_file(
+        &mut self,
+        name: String,
+        path: PathBuf,
+        filename: Option<String>,
+        content_type: Option<String>,
+    ) -> PyResult<()> {
+        if !path.is_file() {
+            return Err(pyo3::exceptions::PyFileNotFoundError::new_err(format!("No such file: {}", path.display())));
+        }
+        if let Some(content_type) = &content_type {
+            content_type
+                .parse::<mime::Mime>()
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid content type: {}", e)))?;
+        }
+
+        self.parts.push(MultipartPart::File { name, path, filename, content_type });
+        Ok(())
+    }
+
+    fn __len__(&self) -> usize {
+        self.parts.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("MultipartBody(parts={})", self.parts.len())
+    }
+}
+
+impl MultipartBody {
+    /// Build a reqwest form, opening file parts for streaming
+    pub async fn to_form(&self) -> std::io::Result<Form> {
+        let mut form = Form::new();
+
+        for part in &self.parts {
+            form = match part {
+                MultipartPart::Field { name, value } => form.text(name.clone(), value.clone()),
+                MultipartPart::File { name, path, filename, content_type } => {
+                    let file = tokio::fs::File::open(path).await?;
+                    let length = file.metadata().await?.len();
+                    let body = reqwest::Body::wrap_stream(ReaderStream::new(file));
+
+                    let filename = filename.clone().unwrap_or_else(|| {
+                        path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_else(|| name.clone())
+                    });
+                    let content_type = content_type.as_deref().unwrap_or("application/octet-stream");
+
+                    let part = Part::stream_with_length(body, length)
+                        .file_name(filename)
+                        .mime_str(content_type)
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+                    form.part(name.clone(), part)
+                }
+            };
+        }
+
+        Ok(form)
+    }
+}
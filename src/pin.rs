@@ -0,0 +1,111 @@
+//! Certificate pinning for the `pins` option: a rustls verifier that, on
+//! top of the usual chain validation, requires a pinned public key in the
+//! chain each pinned host presents
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "rustls")]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(feature = "rustls")]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+
+#[cfg(feature = "rustls")]
+use crate::cert::subject_public_key_info;
+
+/// Start of the SSL error a handshake fails with when no pin matches
+#[cfg(feature = "rustls")]
+pub const PIN_MISMATCH: &str = "pin_mismatch";
+
+/// The `pins` option
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PinConfig {
+    /// SHA-256 hashes of the SubjectPublicKeyInfos accepted for each
+    /// (lowercase) host
+    pub hosts: BTreeMap<String, Vec<[u8; 32]>>,
+    /// Log mismatches instead of failing the handshake
+    pub report_only: bool,
+}
+
+/// The lowercase host a certificate is verified for, as hosts are keyed
+/// in [`PinConfig`]
+#[cfg(feature = "rustls")]
+pub fn server_host(server_name: &ServerName<'_>) -> Option<String> {
+    match server_name {
+        ServerName::DnsName(name) => Some(name.as_ref().trim_end_matches('.').to_ascii_lowercase()),
+        ServerName::IpAddress(ip) => Some(std::net::IpAddr::from(*ip).to_string()),
+        _ => None,
+    }
+}
+
+/// Validates the chain as usual, then checks the pins of pinned hosts
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+pub struct PinningVerifier {
+    inner: std::sync::Arc<rustls::client::WebPkiServerVerifier>,
+    pins: PinConfig,
+}
+
+#[cfg(feature = "rustls")]
+impl PinningVerifier {
+    pub fn new(inner: std::sync::Arc<rustls::client::WebPkiServerVerifier>, pins: PinConfig) -> Self {
+        Self { inner, pins }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let Some(host) = server_host(server_name) else {
+            return Ok(verified);
+        };
+        let Some(pins) = self.pins.hosts.get(&host) else {
+            return Ok(verified);
+        };
+        let pinned = std::iter::once(end_entity)
+            .chain(intermediates)
+            .filter_map(|cert| subject_public_key_info(cert))
+            .map(|spki| ring::digest::digest(&ring::digest::SHA256, spki))
+            .any(|hash| pins.iter().any(|pin| pin[..] == *hash.as_ref()));
+        if pinned {
+            Ok(verified)
+        } else if self.pins.report_only {
+            tracing::warn!(host, "certificate pin mismatch, allowed by pins_report_only");
+            Ok(verified)
+        } else {
+            Err(rustls::Error::General(format!(
+                "{}: no certificate presented by {} matches its pins",
+                PIN_MISMATCH, host
+            )))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use hyper_util::client::legacy::connect::HttpInfo;
+use reqwest::Url;
+use reqwest_middleware::ClientWithMiddleware;
+
+use crate::client::{create_client, ClientConfig};
+use crate::dns::DnsResolver;
+use crate::handshake::Handshakes;
+use crate::hsts::HstsStore;
+use crate::limit::{Permit, RequestLimits};
+use crate::probe::ProbeSessions;
+
+/// A connection observed by the transport
+#[derive(Debug, Clone)]
+pub struct ConnectionRecord {
+    pub partition: PartitionKey,
+    pub local_addr: SocketAddr,
+    pub remote_addr: SocketAddr,
+    pub protocol: http::Version,
+    pub established: Instant,
+    pub last_used: Instant,
+    pub requests: u64,
+}
+
+/// Tracks connections from the `HttpInfo` attached to each response
+///
+/// hyper's pool is opaque, so a connection is identified by its
+/// local/remote address pair and considered gone once it has been idle
+/// longer than the pool idle timeout.
+#[derive(Debug)]
+pub struct ConnectionTracker {
+    connections: Mutex<HashMap<(SocketAddr, SocketAddr), ConnectionRecord>>,
+    idle_timeout: Duration,
+}
+
+impl ConnectionTracker {
+    pub fn new(idle_timeout: Duration) -> Self {
+        Self { connections: Mutex::new(HashMap::new()), idle_timeout }
+    }
+
+    /// Record that `response` was received on one of `partition`'s connections
+    pub fn record(&self, partition: &PartitionKey, response: &reqwest::Response) {
+        let Some(info) = response.extensions().get::<HttpInfo>() else {
+            return;
+        };
+        let now = Instant::now();
+        let key = (info.local_addr(), info.remote_addr());
+
+        let mut connections = self.connections.lock().unwrap();
+        let record = connections.entry(key).or_insert_with(|| ConnectionRecord {
+            partition: partition.clone(),
+            local_addr: info.local_addr(),
+            remote_addr: info.remote_addr(),
+            protocol: response.version(),
+            established: now,
+            last_used: now,
+            requests: 0,
+        });
+        record.last_used = now;
+        record.requests += 1;
+    }
+
+    /// Copy out the live connections
+    ///
+    /// The lock is only held for the copy, so requests are never blocked on
+    /// Python-side conversion of the snapshot.
+    pub fn snapshot(&self) -> Vec<ConnectionRecord> {
+        let mut connections = self.connections.lock().unwrap();
+        connections.retain(|_, record| record.last_used.elapsed() < self.idle_timeout);
+        connections.values().cloned().collect()
+    }
+
+    /// Forget all connections to `origin`, returning how many were tracked
+    pub fn remove_origin(&self, origin: &str) -> usize {
+        let mut connections = self.connections.lock().unwrap();
+        let before = connections.len();
+        connections.retain(|_, record| record.partition.origin != origin);
+        before - connections.len()
+    }
+
+    /// Forget all connections
+    pub fn clear(&self) {
+        self.connections.lock().unwrap().clear();
+    }
+}
+
+/// What a connection is pooled under: requests only reuse connections
+/// opened under an equal key
+///
+/// Everything that shapes a connection and is fixed per transport (proxy,
+/// TLS verification, roots and pins, HTTP version and so on) is part of the
+/// `ClientConfig` the pool itself is keyed by in `get_pool`, so transports
+/// that differ in any of it never share connections. The proxy and
+/// verification mode are repeated here so the key shows the security
+/// parameters a connection was made under. A per-request override that
+/// changes how a connection is made or checked (a proxy, verification, a
+/// client identity, an address to connect to) must be added here.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PartitionKey {
+    pub origin: String,
+    /// reqwest fixes the redirect policy per client
+    pub follow_redirects: bool,
+    /// The one HTTP version connections speak, from the request's
+    /// `http_version` extension or `http2_prior_knowledge`; None when it is
+    /// negotiated
+    pub http_version: Option<http::Version>,
+    /// The proxy connections go through, without its credentials
+    pub proxy: Option<String>,
+    /// Whether the server's certificate is verified
+    pub verify: bool,
+}
+
+/// Connection pool partitioned by origin
+///
+/// Each origin gets its own reqwest client (and therefore its own hyper
+/// pool), which lets `evict` drop every connection to one origin without
+/// disturbing the others. Connections are only reused within an origin
+/// anyway, so partitioning does not reduce reuse. Partitions are further
+/// split by [`PartitionKey`], so a request never reuses a connection opened
+/// for an incompatible override.
+#[derive(Debug)]
+pub struct ConnectionPool {
+    config: ClientConfig,
+    resolver: DnsResolver,
+    partitions: Mutex<HashMap<PartitionKey, Arc<ClientWithMiddleware>>>,
+    tracker: ConnectionTracker,
+    /// Bounds in-flight requests across all partitions, and to each host,
+    /// when configured
+    limits: RequestLimits,
+    /// Known HSTS hosts, with the `hsts` option
+    hsts: Option<HstsStore>,
+    /// TLS sessions from `check_origin` probes
+    probe_sessions: ProbeSessions,
+    /// The handshakes of every partition's connections
+    handshakes: Handshakes,
+}
+
+impl ConnectionPool {
+    pub fn new(config: ClientConfig) -> Self {
+        let tracker = ConnectionTracker::new(config.pool_idle_timeout);
+        let limits =
+            RequestLimits::new(config.max_concurrent_requests, config.max_concurrent_per_host, config.pool_fairness);
+        let hsts = config.hsts.as_ref().map(|hsts| HstsStore::new(hsts, config.verify));
+        let handshakes = Handshakes::new(config.pool_idle_timeout);
+        Self {
+            resolver: DnsResolver::new(config.dns.clone()),
+            config,
+            partitions: Mutex::new(HashMap::new()),
+            tracker,
+            limits,
+            hsts,
+            probe_sessions: ProbeSessions::default(),
+            handshakes,
+        }
+    }
+
+    /// The partition of a request to `url`, with or without following
+    /// redirects, and speaking `http_version` (by default HTTP/2 with
+    /// `http2_prior_knowledge`, otherwise negotiated)
+    pub fn partition_key(
+        &self,
+        url: &Url,
+        follow_redirects: bool,
+        http_version: Option<http::Version>,
+    ) -> PartitionKey {
+        let http_version = http_version.or(self.config.http2_prior_knowledge.then_some(http::Version::HTTP_2));
+        let proxy = self.config.proxy.as_ref().map(|proxy| {
+            let mut proxy = proxy.clone();
+            let _ = proxy.set_username("");
+            let _ = proxy.set_password(None);
+            proxy.to_string()
+        });
+        PartitionKey { origin: origin_of(url), follow_redirects, http_version, proxy, verify: self.config.verify }
+    }
+
+    /// Get the client for `url`'s origin, creating its partition on first use
+    pub fn client_for(&self, url: &Url) -> Arc<ClientWithMiddleware> {
+        self.client(&self.partition_key(url, self.config.follow_redirects, None))
+    }
+
+    /// Get the client for `partition`, creating it on first use
+    ///
+    /// reqwest fixes the redirect policy and HTTP version per client, so
+    /// overriding either for a request uses a separate partition with its
+    /// own connections.
+    pub fn client(&self, partition: &PartitionKey) -> Arc<ClientWithMiddleware> {
+        let mut partitions = self.partitions.lock().unwrap();
+        partitions
+            .entry(partition.clone())
+            .or_insert_with(|| {
+                let config = ClientConfig { follow_redirects: partition.follow_redirects, ..self.config.clone() };
+                create_client(config, self.resolver.clone(), self.handshakes.clone(), partition.http_version)
+            })
+            .clone()
+    }
+
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// The resolver shared by every partition
+    pub fn resolver(&self) -> &DnsResolver {
+        &self.resolver
+    }
+
+    /// The TLS sessions `check_origin` probes resume
+    pub fn probe_sessions(&self) -> &ProbeSessions {
+        &self.probe_sessions
+    }
+
+    pub fn hsts(&self) -> Option<&HstsStore> {
+        self.hsts.as_ref()
+    }
+
+    pub fn tracker(&self) -> &ConnectionTracker {
+        &self.tracker
+    }
+
+    /// Wait for the slots a request to `host` needs: its host's, with
+    /// `max_concurrent_per_host`, then the pool's, with
+    /// `max_concurrent_requests`
+    ///
+    /// The host's slot comes first so requests queued behind a saturated
+    /// host don't hold pool slots that requests to other hosts could use.
+    pub async fn acquire(&self, host: Option<&str>, urgency: u8) -> Vec<Permit> {
+        self.limits.acquire(host, urgency).await
+    }
+
+    /// The TLS handshakes of the pool's connections
+    pub fn handshakes(&self) -> &Handshakes {
+        &self.handshakes
+    }
+
+    /// Drop all connections to `origin`
+    ///
+    /// Idle connections close as soon as the partition's client is dropped;
+    /// in-flight requests keep their client alive until they complete. The
+    /// next request to the origin opens a fresh connection.
+    pub fn evict(&self, origin: &str) -> usize {
+        self.partitions.lock().unwrap().retain(|key, _| key.origin != origin);
+        self.tracker.remove_origin(origin)
+    }
+
+    /// Drop all connections, as `evict` does for one origin
+    pub fn close(&self) {
+        self.partitions.lock().unwrap().clear();
+        self.tracker.clear();
+    }
+}
+
+/// Serialize the origin of `url` (scheme, host and non-default port)
+pub fn origin_of(url: &Url) -> String {
+    url.origin().ascii_serialization()
+}
@@ -0,0 +1,465 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use reqwest::Url;
+use tokio::net::TcpStream;
+
+#[cfg(feature = "rustls")]
+use crate::cert::sha256_fingerprint;
+use crate::cert::{subject_alt_names, CertificateSummary};
+use crate::client::ClientConfig;
+use crate::dns::DnsResolver;
+use crate::errors::TransportError;
+
+/// Outcome of a successful origin preflight
+#[derive(Debug, Clone)]
+pub struct OriginCheck {
+    /// Every address the host resolved to, in resolver order
+    pub resolved: Vec<SocketAddr>,
+    /// The address the TCP connection was established to
+    pub address: SocketAddr,
+    pub tls_version: Option<&'static str>,
+    pub cipher_suite: Option<String>,
+    /// Whether the TLS handshake resumed a session from an earlier probe
+    pub tls_resumed: Option<bool>,
+    pub alpn: Option<String>,
+    /// The server's certificate chain, leaf first
+    pub certificates: Vec<CertificateSummary>,
+    /// The same chain, DER-encoded
+    pub chain: Vec<Vec<u8>>,
+    pub timings: ProbeTimings,
+    pub elapsed: Duration,
+}
+
+/// Time spent in each phase of a probe
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProbeTimings {
+    pub dns: Duration,
+    pub connect: Duration,
+    /// TLS handshake, for `https` origins
+    pub tls: Option<Duration>,
+}
+
+impl OriginCheck {
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let result = PyDict::new(py);
+        let resolved: Vec<String> = self.resolved.iter().map(|addr| addr.to_string()).collect();
+        result.set_item("resolved", resolved)?;
+        result.set_item("address", self.address.to_string())?;
+        result.set_item("tls_version", self.tls_version)?;
+        result.set_item("cipher_suite", self.cipher_suite.as_deref())?;
+        result.set_item("tls_resumed", self.tls_resumed)?;
+        result.set_item("alpn", self.alpn.as_deref())?;
+        let certificates = pyo3::types::PyList::empty(py);
+        for certificate in &self.certificates {
+            let summary = PyDict::new(py);
+            summary.set_item("subject", &certificate.subject)?;
+            summary.set_item("issuer", &certificate.issuer)?;
+            summary.set_item("not_before", &certificate.not_before)?;
+            summary.set_item("not_after", &certificate.not_after)?;
+            certificates.append(summary)?;
+        }
+        result.set_item("certificates", certificates)?;
+        let timings = PyDict::new(py);
+        timings.set_item("dns", self.timings.dns.as_secs_f64())?;
+        timings.set_item("connect", self.timings.connect.as_secs_f64())?;
+        timings.set_item("tls", self.timings.tls.map(|tls| tls.as_secs_f64()))?;
+        result.set_item("timings", timings)?;
+        result.set_item("elapsed", self.elapsed.as_secs_f64())?;
+        Ok(result.into())
+    }
+
+    /// The TLS details of an `https` probe, for `get_ssl_info`: the
+    /// negotiated parameters and each certificate of the chain with its
+    /// names, validity and SHA-256 fingerprint
+    pub fn ssl_info_dict(&self, py: Python) -> PyResult<PyObject> {
+        let result = PyDict::new(py);
+        result.set_item("address", self.address.to_string())?;
+        result.set_item("tls_version", self.tls_version)?;
+        result.set_item("cipher_suite", self.cipher_suite.as_deref())?;
+        result.set_item("alpn", self.alpn.as_deref())?;
+        let certificates = pyo3::types::PyList::empty(py);
+        for der in &self.chain {
+            let decoded = CertificateSummary::from_der(der);
+            let summary = PyDict::new(py);
+            summary.set_item("subject", decoded.as_ref().map(|c| &c.subject))?;
+            summary.set_item("issuer", decoded.as_ref().map(|c| &c.issuer))?;
+            summary.set_item("not_before", decoded.as_ref().map(|c| &c.not_before))?;
+            summary.set_item("not_after", decoded.as_ref().map(|c| &c.not_after))?;
+            summary.set_item("subject_alt_names", subject_alt_names(der))?;
+            #[cfg(feature = "rustls")]
+            summary.set_item("sha256_fingerprint", sha256_fingerprint(der))?;
+            certificates.append(summary)?;
+        }
+        result.set_item("certificates", certificates)?;
+        Ok(result.into())
+    }
+}
+
+/// How long a TLS 1.3 probe waits for the server's session tickets after
+/// the handshake, for servers that send none
+#[cfg(feature = "rustls")]
+const TICKET_WAIT: Duration = Duration::from_millis(100);
+
+/// TLS sessions kept from one of a transport's probes (or wiretapped
+/// requests) to the next, so a later one to the same origin can resume one
+///
+/// rustls only resumes a session under the certificate verifier that
+/// established it, so the TLS config is built once and kept here too.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeSessions {
+    #[cfg(feature = "rustls")]
+    store: std::sync::Arc<SessionStore>,
+    #[cfg(feature = "rustls")]
+    tls_config: std::sync::Arc<std::sync::Mutex<Option<std::sync::Arc<rustls::ClientConfig>>>>,
+}
+
+/// rustls's in-memory session cache, announcing new TLS 1.3 tickets
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+struct SessionStore {
+    cache: rustls::client::ClientSessionMemoryCache,
+    tickets: tokio::sync::Notify,
+}
+
+#[cfg(feature = "rustls")]
+impl Default for SessionStore {
+    fn default() -> Self {
+        Self { cache: rustls::client::ClientSessionMemoryCache::new(256), tickets: Default::default() }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl rustls::client::ClientSessionStore for SessionStore {
+    fn set_kx_hint(&self, server_name: rustls::pki_types::ServerName<'static>, group: rustls::NamedGroup) {
+        self.cache.set_kx_hint(server_name, group)
+    }
+
+    fn kx_hint(&self, server_name: &rustls::pki_types::ServerName<'_>) -> Option<rustls::NamedGroup> {
+        self.cache.kx_hint(server_name)
+    }
+
+    fn set_tls12_session(
+        &self,
+        server_name: rustls::pki_types::ServerName<'static>,
+        value: rustls::client::Tls12ClientSessionValue,
+    ) {
+        self.cache.set_tls12_session(server_name, value)
+    }
+
+    fn tls12_session(
+        &self,
+        server_name: &rustls::pki_types::ServerName<'_>,
+    ) -> Option<rustls::client::Tls12ClientSessionValue> {
+        self.cache.tls12_session(server_name)
+    }
+
+    fn remove_tls12_session(&self, server_name: &rustls::pki_types::ServerName<'static>) {
+        self.cache.remove_tls12_session(server_name)
+    }
+
+    fn insert_tls13_ticket(
+        &self,
+        server_name: rustls::pki_types::ServerName<'static>,
+        value: rustls::client::Tls13ClientSessionValue,
+    ) {
+        self.cache.insert_tls13_ticket(server_name, value);
+        self.tickets.notify_waiters();
+    }
+
+    fn take_tls13_ticket(
+        &self,
+        server_name: &rustls::pki_types::ServerName<'static>,
+    ) -> Option<rustls::client::Tls13ClientSessionValue> {
+        self.cache.take_tls13_ticket(server_name)
+    }
+}
+
+/// Verify DNS, TCP, TLS and ALPN for `url`'s origin without sending a request
+///
+/// Resolves the host, connects to the first reachable address and, for
+/// `https` URLs, completes a TLS handshake offering the same ALPN protocols
+/// as the transport, resuming a session from an earlier probe in `sessions`
+/// when the server allows. Resolution goes through the transport's
+/// resolver. The connection is closed afterwards. The whole probe is
+/// bounded by the configured request timeout. A configured proxy is not
+/// used: the probe always connects to the origin directly.
+pub async fn check_origin(
+    config: &ClientConfig,
+    resolver: &DnsResolver,
+    sessions: &ProbeSessions,
+    url: &Url,
+) -> Result<OriginCheck, TransportError> {
+    let started = Instant::now();
+    let mut check = tokio::time::timeout(config.timeout, probe(config, resolver, sessions, url))
+        .await
+        .map_err(|_| TransportError::ConnectTimeout(format!("probing {} timed out", url)))??;
+    check.elapsed = started.elapsed();
+    Ok(check)
+}
+
+/// Complete a TLS handshake with `url`'s origin, as [`check_origin`] does,
+/// for `get_ssl_info`; `url` must be an `https` URL
+pub async fn ssl_info(
+    config: &ClientConfig,
+    resolver: &DnsResolver,
+    sessions: &ProbeSessions,
+    url: &Url,
+) -> Result<OriginCheck, TransportError> {
+    if url.scheme() != "https" {
+        return Err(TransportError::InvalidURL(format!("get_ssl_info needs an https URL, got {}", url)));
+    }
+    check_origin(config, resolver, sessions, url).await
+}
+
+/// `url`'s host, as it is looked up (IPv6 literals keep their brackets in
+/// `host_str`), and port
+pub fn host_and_port(url: &Url) -> Result<(&str, u16), TransportError> {
+    let host = url.host_str().ok_or_else(|| TransportError::InvalidURL(format!("{} has no host", url)))?;
+    let port = url.port_or_known_default().ok_or_else(|| TransportError::InvalidURL(format!("{} has no port", url)))?;
+    Ok((host.trim_start_matches('[').trim_end_matches(']'), port))
+}
+
+/// The addresses of `host` through `resolver`, on `port`; an IP address
+/// is its own
+pub async fn resolve(resolver: &DnsResolver, host: &str, port: u16) -> Result<Vec<SocketAddr>, TransportError> {
+    Ok(match host.parse::<std::net::IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => resolver
+            .resolve(host)
+            .await
+            .map_err(|e| TransportError::ConnectError(format!("dns error: {}", e)))?
+            .addresses
+            .into_iter()
+            .map(|addr| SocketAddr::new(addr.ip, port))
+            .collect(),
+    })
+}
+
+/// Connect to the first of `url`'s `resolved` addresses that accepts
+pub async fn connect(url: &Url, resolved: &[SocketAddr]) -> Result<(TcpStream, SocketAddr), TransportError> {
+    let mut last_error = None;
+    for addr in resolved {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => return Ok((stream, *addr)),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    let reason = last_error.map_or_else(|| "no addresses resolved".to_string(), |e| e.to_string());
+    Err(TransportError::ConnectError(format!("{}: {}", url.origin().ascii_serialization(), reason)))
+}
+
+async fn probe(
+    config: &ClientConfig,
+    resolver: &DnsResolver,
+    sessions: &ProbeSessions,
+    url: &Url,
+) -> Result<OriginCheck, TransportError> {
+    let (lookup_host, port) = host_and_port(url)?;
+    let mut timings = ProbeTimings::default();
+    let started = Instant::now();
+    let resolved = resolve(resolver, lookup_host, port).await?;
+    timings.dns = started.elapsed();
+
+    let started = Instant::now();
+    let (stream, address) = connect(url, &resolved).await?;
+    timings.connect = started.elapsed();
+
+    let mut check = OriginCheck {
+        resolved,
+        address,
+        tls_version: None,
+        cipher_suite: None,
+        tls_resumed: None,
+        alpn: None,
+        certificates: Vec::new(),
+        chain: Vec::new(),
+        timings,
+        elapsed: Duration::ZERO,
+    };
+    if url.scheme() == "https" {
+        handshake(config, lookup_host, stream, sessions, &mut check).await?;
+    }
+    Ok(check)
+}
+
+/// Complete a TLS handshake on `stream`, filling in `check`'s TLS details
+/// and handshake time
+#[cfg(feature = "rustls")]
+async fn handshake(
+    config: &ClientConfig,
+    host: &str,
+    stream: TcpStream,
+    sessions: &ProbeSessions,
+    check: &mut OriginCheck,
+) -> Result<(), TransportError> {
+    use rustls::pki_types::ServerName;
+    use tokio::io::AsyncReadExt;
+
+    if config.tls_backend != crate::client::TlsBackend::Rustls {
+        return Err(TransportError::Other(
+            "check_origin only supports TLS probing with the rustls backend".to_string(),
+        ));
+    }
+
+    let tls_config = std::sync::Arc::new(sessions.tls_config(config, vec![b"h2".to_vec(), b"http/1.1".to_vec()])?);
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| TransportError::InvalidURL(format!("invalid server name {}: {}", host, e)))?;
+    let started = Instant::now();
+    let mut tls = tokio_rustls::TlsConnector::from(tls_config)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| TransportError::SSLError(e.to_string()))?;
+    check.timings.tls = Some(started.elapsed());
+
+    let (_, session) = tls.get_ref();
+    check.tls_version = session.protocol_version().map(|version| match version {
+        rustls::ProtocolVersion::TLSv1_2 => "TLSv1.2",
+        rustls::ProtocolVersion::TLSv1_3 => "TLSv1.3",
+        _ => "unknown",
+    });
+    check.cipher_suite = session.negotiated_cipher_suite().map(|suite| format!("{:?}", suite.suite()));
+    check.tls_resumed = session.handshake_kind().map(|kind| kind == rustls::HandshakeKind::Resumed);
+    check.alpn = session.alpn_protocol().map(|protocol| String::from_utf8_lossy(protocol).into_owned());
+    check.chain = session.peer_certificates().unwrap_or_default().iter().map(|cert| cert.to_vec()).collect();
+    check.certificates = check.chain.iter().filter_map(|cert| CertificateSummary::from_der(cert)).collect();
+
+    // TLS 1.3 tickets follow the handshake; read until one arrives, so the
+    // next probe can resume
+    if check.tls_version == Some("TLSv1.3") {
+        let ticket = sessions.store.tickets.notified();
+        let mut byte = [0; 1];
+        let _ = tokio::time::timeout(TICKET_WAIT, async {
+            tokio::select! {
+                _ = ticket => {}
+                _ = tls.read(&mut byte) => {}
+            }
+        })
+        .await;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "rustls")]
+impl ProbeSessions {
+    /// The rustls config probes and wiretapped requests use: the
+    /// transport's roots or `verify=False`, `alpn_protocols`, and this
+    /// cache
+    pub fn tls_config(
+        &self,
+        config: &ClientConfig,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<rustls::ClientConfig, TransportError> {
+        use std::sync::Arc;
+
+        let mut cached = self.tls_config.lock().unwrap();
+        let base = match &*cached {
+            Some(base) => base.clone(),
+            None => {
+                let provider = Arc::new(rustls::crypto::ring::default_provider());
+                let verifier = server_verifier(config, provider.clone())?;
+                let mut base = tls_config(provider, verifier, Vec::new())?;
+                base.resumption = rustls::client::Resumption::store(self.store.clone());
+                cached.insert(Arc::new(base)).clone()
+            }
+        };
+        let mut tls_config = (*base).clone();
+        tls_config.alpn_protocols = alpn_protocols;
+        Ok(tls_config)
+    }
+}
+
+/// A rustls config verifying servers with `verifier`, offering
+/// `alpn_protocols`, with `provider`'s crypto
+#[cfg(feature = "rustls")]
+pub fn tls_config(
+    provider: std::sync::Arc<rustls::crypto::CryptoProvider>,
+    verifier: std::sync::Arc<dyn rustls::client::danger::ServerCertVerifier>,
+    alpn_protocols: Vec<Vec<u8>>,
+) -> Result<rustls::ClientConfig, TransportError> {
+    let mut tls_config = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| TransportError::SSLError(e.to_string()))?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier)
+        .with_no_client_auth();
+    tls_config.alpn_protocols = alpn_protocols;
+    Ok(tls_config)
+}
+
+/// The verifier of the transport's rustls configs: its roots (through its
+/// custom verifier, when it needs one) or, with `verify=False`, any
+/// certificate, with `provider`'s crypto
+#[cfg(feature = "rustls")]
+pub fn server_verifier(
+    config: &ClientConfig,
+    provider: std::sync::Arc<rustls::crypto::CryptoProvider>,
+) -> Result<std::sync::Arc<dyn rustls::client::danger::ServerCertVerifier>, TransportError> {
+    use std::sync::Arc;
+
+    if !config.verify {
+        return Ok(Arc::new(AcceptAnyCertificate(provider)));
+    }
+    if crate::client::has_custom_verifier(config) {
+        return crate::client::custom_verifier(config, provider);
+    }
+    let roots = Arc::new(crate::client::rustls_roots(config)?);
+    let verifier = rustls::client::WebPkiServerVerifier::builder_with_provider(roots, provider)
+        .build()
+        .map_err(|e| TransportError::SSLError(e.to_string()))?;
+    Ok(verifier)
+}
+
+/// Verifier for `verify=False`: checks handshake signatures, trusts any chain
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+struct AcceptAnyCertificate(std::sync::Arc<rustls::crypto::CryptoProvider>);
+
+#[cfg(feature = "rustls")]
+impl rustls::client::danger::ServerCertVerifier for AcceptAnyCertificate {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &rustls::pki_types::CertificateDer<'_>,
+        _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+        _server_name: &rustls::pki_types::ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: rustls::pki_types::UnixTime,
+    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+        Ok(rustls::client::danger::ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &rustls::pki_types::CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+#[cfg(not(feature = "rustls"))]
+async fn handshake(
+    _config: &ClientConfig,
+    _host: &str,
+    _stream: TcpStream,
+    _sessions: &ProbeSessions,
+    _check: &mut OriginCheck,
+) -> Result<(), TransportError> {
+    Err(TransportError::Other("check_origin requires the rustls feature for TLS probing".to_string()))
+}
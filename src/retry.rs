@@ -0,0 +1,208 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::time::Duration;
+
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, Result};
+
+use crate::connect::{with_connect_timeout, ConnectTimeout};
+use crate::errors::TransportError;
+
+/// Delay before the first retry; doubled for each further attempt
+const BASE_BACKOFF: Duration = Duration::from_millis(100);
+
+tokio::task_local! {
+    /// Attempts made by the request being sent, see [`record_retries`]
+    static RETRY_HISTORY: RefCell<Vec<RetryAttempt>>;
+}
+
+/// One attempt at sending a request, for `extensions["retry_history"]`
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    /// The response status, or why the attempt failed
+    pub outcome: std::result::Result<u16, String>,
+    /// How long the middleware waited before the next attempt, if it made one
+    pub delay: Option<Duration>,
+    /// How long the attempt could take to connect, if the request set a
+    /// connect timeout
+    pub connect_timeout: Option<Duration>,
+}
+
+impl RetryAttempt {
+    /// `{"attempt": n, "status": 503, "delay": 0.1}`, with `"error"` in
+    /// place of `"status"` for a failed attempt, a `null` delay for the
+    /// last attempt, and the attempt's `"connect_timeout"` if it had one
+    pub fn to_json(&self, attempt: usize) -> serde_json::Value {
+        let mut record = serde_json::Map::new();
+        record.insert("attempt".to_string(), attempt.into());
+        match &self.outcome {
+            Ok(status) => record.insert("status".to_string(), (*status).into()),
+            Err(error) => record.insert("error".to_string(), error.clone().into()),
+        };
+        record.insert(
+            "delay".to_string(),
+            self.delay.map_or(serde_json::Value::Null, |delay| delay.as_secs_f64().into()),
+        );
+        if let Some(connect_timeout) = self.connect_timeout {
+            record.insert("connect_timeout".to_string(), connect_timeout.as_secs_f64().into());
+        }
+        record.into()
+    }
+}
+
+/// The JSON list of `attempts`, if there was more than one
+pub fn retry_history_json(attempts: &[RetryAttempt]) -> Option<serde_json::Value> {
+    (attempts.len() > 1).then(|| attempts.iter().enumerate().map(|(i, attempt)| attempt.to_json(i + 1)).collect())
+}
+
+/// Run `send`, a request being sent, and keep what each of its attempts
+/// came to
+///
+/// The retry middleware runs inside the send future, so the attempts are
+/// kept in a task-local that `send` sees while it is polled.
+pub async fn record_retries<F: Future>(send: F) -> (F::Output, Vec<RetryAttempt>) {
+    RETRY_HISTORY
+        .scope(RefCell::new(Vec::new()), async {
+            let output = send.await;
+            (output, RETRY_HISTORY.with(RefCell::take))
+        })
+        .await
+}
+
+/// Note an attempt for [`record_retries`], if the request is recorded
+fn record_attempt(result: &Result<Response>, delay: Option<Duration>, connect_timeout: Option<Duration>) {
+    let outcome = match result {
+        Ok(response) => Ok(response.status().as_u16()),
+        Err(e) => Err(e.to_string()),
+    };
+    let _ =
+        RETRY_HISTORY.try_with(|history| history.borrow_mut().push(RetryAttempt { outcome, delay, connect_timeout }));
+}
+
+/// Retries transient failures, re-sending the original request body
+///
+/// Each attempt sends a clone of the request. Buffered bodies clone
+/// cheaply (`Bytes` is reference counted), so every attempt carries the
+/// same body. Streaming bodies, such as file parts of a multipart upload,
+/// cannot be cloned: those requests are sent exactly once and any failure
+/// is returned as-is rather than retried with a missing body.
+///
+/// Connection failures are retried for every method, since the request
+/// never reached the server. 502/503/504 responses are only retried for
+/// idempotent methods, and for requests carrying an `idempotency_key`
+/// header, which the server uses to recognise a repeat.
+///
+/// Each attempt at a request with a [`ConnectTimeout`] may take that long
+/// to connect; with `connect_timeout_escalation`, each retry gets that
+/// many times longer than the attempt before it, up to the request's
+/// overall timeout. With a [`ResponseTimeout`], an attempt whose response
+/// doesn't arrive within that long after its connect timeout fails with a
+/// read timeout, and isn't retried.
+#[derive(Debug, Clone)]
+pub struct RetryMiddleware {
+    max_attempts: u32,
+    idempotency_key: Option<HeaderName>,
+    connect_timeout_escalation: Option<f64>,
+}
+
+impl RetryMiddleware {
+    pub fn new(
+        max_attempts: u32,
+        idempotency_key: Option<HeaderName>,
+        connect_timeout_escalation: Option<f64>,
+    ) -> Self {
+        Self { max_attempts: max_attempts.max(1), idempotency_key, connect_timeout_escalation }
+    }
+
+    fn should_retry(&self, req: &Request, result: &Result<Response>) -> bool {
+        match result {
+            Ok(response) => {
+                (is_idempotent(req.method())
+                    || self.idempotency_key.as_ref().is_some_and(|key| req.headers().contains_key(key)))
+                    && matches!(
+                        response.status(),
+                        StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+                    )
+            }
+            Err(reqwest_middleware::Error::Reqwest(e)) => e.is_connect(),
+            Err(reqwest_middleware::Error::Middleware(_)) => false,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for RetryMiddleware {
+    async fn handle(&self, req: Request, extensions: &mut Extensions, next: Next<'_>) -> Result<Response> {
+        let mut attempt = 1;
+        let mut req = req;
+        let connect_timeout = extensions.get::<ConnectTimeout>().copied();
+        let response_timeout = extensions.get::<ResponseTimeout>().copied();
+
+        loop {
+            let timeout = connect_timeout.map(|timeout| timeout.for_attempt(self.connect_timeout_escalation, attempt));
+            let wait = response_timeout.map(|ResponseTimeout(read)| read.saturating_add(timeout.unwrap_or_default()));
+
+            // A request whose body can't be cloned gets a single attempt
+            let Some(retry_req) = (attempt < self.max_attempts).then(|| req.try_clone()).flatten() else {
+                let result = within(wait, with_connect_timeout(timeout, next.run(req, extensions))).await;
+                record_attempt(&result, None, timeout);
+                return result;
+            };
+
+            let result = within(wait, with_connect_timeout(timeout, next.clone().run(req, extensions))).await;
+            if !self.should_retry(&retry_req, &result) {
+                record_attempt(&result, None, timeout);
+                return result;
+            }
+
+            let delay = BASE_BACKOFF * 2u32.pow(attempt - 1);
+            record_attempt(&result, Some(delay), timeout);
+            tracing::debug!(attempt, ?delay, "retrying request");
+            tokio::time::sleep(delay).await;
+
+            req = retry_req;
+            attempt += 1;
+        }
+    }
+}
+
+/// How long each attempt at a request may wait for its response once
+/// connected: the `read` limit of its `timeout` extension, carried in its
+/// extensions to the retry middleware
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResponseTimeout(pub Duration);
+
+/// Run `send`, an attempt at a request, failing it with a read timeout if
+/// its response doesn't arrive within `wait`
+async fn within<F: Future<Output = Result<Response>>>(wait: Option<Duration>, send: F) -> Result<Response> {
+    let Some(wait) = wait else {
+        return send.await;
+    };
+    tokio::time::timeout(wait, send).await.unwrap_or_else(|_| {
+        let timeout = TransportError::ReadTimeout(format!("no response within {:?}", wait));
+        Err(reqwest_middleware::Error::Middleware(anyhow::Error::new(timeout)))
+    })
+}
+
+/// Give a non-idempotent request a new key in the `header` it names, for
+/// the `inject_idempotency_key` option, unless the caller set one
+///
+/// The key is made once per request, so every retry of it carries the same
+/// one: a random (version 4) UUID.
+pub fn inject_idempotency_key(header: &HeaderName, method: &Method, headers: &mut HeaderMap) {
+    if is_idempotent(method) || headers.contains_key(header) {
+        return;
+    }
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes).expect("the system's random source is available");
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let key = format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..]);
+    headers.insert(header.clone(), HeaderValue::from_str(&key).expect("a UUID is a valid header value"));
+}
+
+pub(crate) fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS | Method::TRACE)
+}
@@ -0,0 +1,124 @@
+//! Revocation checking for the `crls` and `require_ocsp_staple` options
+//!
+//! CRLs are checked by rustls' own verifier as it validates the chain.
+//! Stapling is checked on top of it: rustls hands the verifier whatever
+//! OCSP response the server stapled, but verifies neither its signature nor
+//! the status in it, so a staple only has to be a successful response.
+
+#[cfg(feature = "rustls")]
+use std::sync::Arc;
+
+#[cfg(feature = "rustls")]
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+#[cfg(feature = "rustls")]
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+
+#[cfg(feature = "rustls")]
+use crate::cert::ocsp_response_successful;
+#[cfg(feature = "rustls")]
+use crate::pin::server_host;
+
+/// Start of the SSL error a handshake fails with when the certificate is
+/// in a configured CRL
+#[cfg(feature = "rustls")]
+pub const CERTIFICATE_REVOKED: &str = "certificate_revoked";
+
+/// Start of the SSL error a handshake fails with when a host that must
+/// staple an OCSP response doesn't
+#[cfg(feature = "rustls")]
+pub const OCSP_STAPLE_REQUIRED: &str = "ocsp_staple_required";
+
+/// The `crls` and `require_ocsp_staple` options
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct RevocationConfig {
+    /// DER-encoded CRLs, checked against each server's certificate
+    pub crls: Vec<Vec<u8>>,
+    /// Patterns of the (lowercase) hosts that must staple an OCSP
+    /// response: `*` for every host, `*.example.com` for its subdomains,
+    /// or a host name
+    pub staple_hosts: Vec<String>,
+}
+
+impl RevocationConfig {
+    pub fn is_empty(&self) -> bool {
+        self.crls.is_empty() && self.staple_hosts.is_empty()
+    }
+
+    /// Whether `host` must staple an OCSP response
+    #[cfg(feature = "rustls")]
+    pub fn requires_staple(&self, host: &str) -> bool {
+        self.staple_hosts.iter().any(|pattern| match pattern.strip_prefix('*') {
+            Some("") => true,
+            Some(suffix) => suffix.starts_with('.') && host.ends_with(suffix),
+            None => pattern == host,
+        })
+    }
+}
+
+/// Validates the chain with the inner verifier, then requires a staple
+/// from the hosts that must send one
+#[cfg(feature = "rustls")]
+#[derive(Debug)]
+pub struct StapleVerifier {
+    inner: Arc<dyn ServerCertVerifier>,
+    revocation: RevocationConfig,
+}
+
+#[cfg(feature = "rustls")]
+impl StapleVerifier {
+    pub fn new(inner: Arc<dyn ServerCertVerifier>, revocation: RevocationConfig) -> Self {
+        Self { inner, revocation }
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl ServerCertVerifier for StapleVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        let Some(host) = server_host(server_name) else {
+            return Ok(verified);
+        };
+        if !self.revocation.requires_staple(&host) {
+            return Ok(verified);
+        }
+        if ocsp_response.is_empty() {
+            return Err(rustls::Error::General(format!("{}: {} stapled no OCSP response", OCSP_STAPLE_REQUIRED, host)));
+        }
+        if !ocsp_response_successful(ocsp_response) {
+            return Err(rustls::Error::General(format!(
+                "{}: {} stapled an unsuccessful OCSP response",
+                OCSP_STAPLE_REQUIRED, host
+            )));
+        }
+        Ok(verified)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &rustls::DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
@@ -0,0 +1,46 @@
+//! Content-Type sniffing for request bodies sent without one, for the
+//! `sniff_content_type` extension
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+
+/// Leading bytes of binary formats and the types they identify
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\x1F\x8B\x08", "application/gzip"),
+    (b"\0asm", "application/wasm"),
+];
+
+/// The type `body` looks like: a known binary signature, JSON (an object or
+/// array that parses), XML (an `<?xml` declaration), or
+/// `application/octet-stream` for anything else
+pub fn sniff_content_type(body: &[u8]) -> &'static str {
+    if let Some((_, content_type)) = SIGNATURES.iter().find(|(magic, _)| body.starts_with(magic)) {
+        return content_type;
+    }
+    if body.len() >= 12 && body.starts_with(b"RIFF") && &body[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    // Text formats may start with a byte order mark and whitespace
+    let text = body.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(body);
+    let text = &text[text.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(text.len())..];
+    match text.first() {
+        Some(b'{' | b'[') if serde_json::from_slice::<serde::de::IgnoredAny>(text).is_ok() => "application/json",
+        Some(b'<') if text.starts_with(b"<?xml") => "application/xml",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Set `Content-Type` in `headers` to the sniffed type of `body`, unless
+/// the caller set one or there is no body
+pub fn set_sniffed_content_type(headers: &mut HeaderMap, body: &[u8]) {
+    if body.is_empty() || headers.contains_key(CONTENT_TYPE) {
+        return;
+    }
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(sniff_content_type(body)));
+}
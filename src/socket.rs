@@ -0,0 +1,209 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Socket-level tuning applied to every connection a client opens
+///
+/// reqwest only exposes a fixed set of pre-connect socket knobs, so these
+/// options map onto those. Portability:
+///
+/// - `nodelay`, `keepalive`, `keepalive_interval`, `keepalive_retries` and
+///   `local_address` work everywhere.
+/// - `interface` (`SO_BINDTODEVICE`/`IP_BOUND_IF`) works on Linux, Android,
+///   Fuchsia, macOS/iOS, illumos and Solaris.
+/// - `user_timeout` (`TCP_USER_TIMEOUT`) works on Linux, Android and Fuchsia.
+/// - `dscp` works on Linux and Android. It is set right after the TCP
+///   handshake (see [`DscpLayer`]), so the SYN itself is sent unmarked.
+///
+/// Options that must be set before connecting but that reqwest doesn't
+/// expose (`SO_REUSEADDR`, `TCP_MAXSEG`) are not supported.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SocketOptions {
+    pub nodelay: Option<bool>,
+    pub keepalive: Option<Duration>,
+    pub keepalive_interval: Option<Duration>,
+    pub keepalive_retries: Option<u32>,
+    pub local_address: Option<IpAddr>,
+    pub interface: Option<String>,
+    pub user_timeout: Option<Duration>,
+    /// Differentiated Services code point (0-63)
+    pub dscp: Option<u8>,
+}
+
+/// Option names accepted on the current platform
+pub const SUPPORTED_OPTIONS: &[&str] = &[
+    "nodelay",
+    "keepalive",
+    "keepalive_interval",
+    "keepalive_retries",
+    "local_address",
+    #[cfg(any(
+        target_os = "android",
+        target_os = "fuchsia",
+        target_os = "illumos",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "solaris",
+        target_os = "tvos",
+        target_os = "visionos",
+        target_os = "watchos",
+    ))]
+    "interface",
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    "user_timeout",
+    #[cfg(any(target_os = "android", target_os = "linux"))]
+    "dscp",
+];
+
+/// Apply [`SocketOptions`] to a reqwest (async or blocking) `ClientBuilder`
+///
+/// Options not supported on the current platform are rejected when the
+/// config is parsed, so they are simply skipped here.
+macro_rules! apply_socket_options {
+    ($builder:expr, $options:expr) => {{
+        let options: &$crate::socket::SocketOptions = $options;
+        let mut builder = $builder;
+        if let Some(nodelay) = options.nodelay {
+            builder = builder.tcp_nodelay(nodelay);
+        }
+        if let Some(keepalive) = options.keepalive {
+            builder = builder.tcp_keepalive(keepalive);
+        }
+        if let Some(interval) = options.keepalive_interval {
+            builder = builder.tcp_keepalive_interval(interval);
+        }
+        if let Some(retries) = options.keepalive_retries {
+            builder = builder.tcp_keepalive_retries(retries);
+        }
+        if let Some(local_address) = options.local_address {
+            builder = builder.local_address(local_address);
+        }
+        #[cfg(any(
+            target_os = "android",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "solaris",
+            target_os = "tvos",
+            target_os = "visionos",
+            target_os = "watchos",
+        ))]
+        if let Some(interface) = &options.interface {
+            builder = builder.interface(interface);
+        }
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(user_timeout) = options.user_timeout {
+            builder = builder.tcp_user_timeout(user_timeout);
+        }
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        if let Some(dscp) = options.dscp {
+            builder = builder.connector_layer($crate::socket::DscpLayer::new(dscp));
+        }
+        builder
+    }};
+}
+pub(crate) use apply_socket_options;
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+pub use dscp::DscpLayer;
+
+#[cfg(any(target_os = "android", target_os = "linux"))]
+mod dscp {
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::os::fd::BorrowedFd;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    use hyper_util::client::legacy::connect::{Connection, HttpInfo};
+    use tower::{Layer, Service};
+
+    type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+    /// Connector layer that marks each new connection with a DSCP value
+    ///
+    /// reqwest has no hook between creating a socket and connecting it, and
+    /// its connections don't expose their file descriptor. Once a connection
+    /// is established, the layer finds the socket whose local/remote address
+    /// pair matches the connection's `HttpInfo` and sets `IP_TOS` (or the
+    /// IPv6 traffic class) on it.
+    #[derive(Debug, Clone, Copy)]
+    pub struct DscpLayer {
+        tos: u32,
+    }
+
+    impl DscpLayer {
+        pub fn new(dscp: u8) -> Self {
+            // DSCP occupies the upper six bits of the TOS byte
+            Self { tos: u32::from(dscp) << 2 }
+        }
+    }
+
+    impl<S> Layer<S> for DscpLayer {
+        type Service = DscpService<S>;
+
+        fn layer(&self, inner: S) -> Self::Service {
+            DscpService { inner, tos: self.tos }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct DscpService<S> {
+        inner: S,
+        tos: u32,
+    }
+
+    impl<S, Req> Service<Req> for DscpService<S>
+    where
+        S: Service<Req, Error = BoxError>,
+        S::Response: Connection,
+        S::Future: Send + 'static,
+    {
+        type Response = S::Response;
+        type Error = BoxError;
+        type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.inner.poll_ready(cx)
+        }
+
+        fn call(&mut self, req: Req) -> Self::Future {
+            let connecting = self.inner.call(req);
+            let tos = self.tos;
+            Box::pin(async move {
+                let conn = connecting.await?;
+                let mut extras = http::Extensions::new();
+                conn.connected().get_extras(&mut extras);
+                if let Some(info) = extras.get::<HttpInfo>() {
+                    set_tos(info.local_addr(), info.remote_addr(), tos)?;
+                }
+                Ok(conn)
+            })
+        }
+    }
+
+    /// Set the TOS byte on this process's socket connected `local` -> `remote`
+    fn set_tos(local: SocketAddr, remote: SocketAddr, tos: u32) -> std::io::Result<()> {
+        for entry in std::fs::read_dir("/proc/self/fd")? {
+            let Some(fd) = entry?.file_name().to_str().and_then(|name| name.parse().ok()) else {
+                continue;
+            };
+            // SAFETY: the fd is only used for getsockname/getpeername/setsockopt
+            // while listed; if it closes meanwhile those calls fail with EBADF
+            // or see a different socket, whose addresses won't match.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let socket = socket2::SockRef::from(&fd);
+            let matches = socket.local_addr().ok().and_then(|addr| addr.as_socket()) == Some(local)
+                && socket.peer_addr().ok().and_then(|addr| addr.as_socket()) == Some(remote);
+            if matches {
+                return match local {
+                    SocketAddr::V4(_) => socket.set_tos(tos),
+                    SocketAddr::V6(_) => socket.set_tclass_v6(tos),
+                };
+            }
+        }
+        Ok(())
+    }
+}
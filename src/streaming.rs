@@ -1,84 +1,625 @@
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
+use http_body_util::BodyExt;
+use pyo3::exceptions::PyStopAsyncIteration;
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
-use tokio::sync::mpsc;
+use reqwest::header::HeaderMap;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot, Semaphore};
 
 use crate::errors::TransportError;
+use crate::limit::Permit;
+use crate::utils::{json_to_python, trailers_to_json};
+
+/// The `max_response_body_size` limit on (decoded) response bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BodyLimit {
+    pub max_bytes: u64,
+    /// Keep the first `max_bytes` and flag the response as truncated
+    /// instead of failing
+    pub truncate: bool,
+}
+
+impl BodyLimit {
+    fn exceeded(&self) -> TransportError {
+        TransportError::RemoteProtocolError(format!(
+            "response body exceeds max_response_body_size ({} bytes)",
+            self.max_bytes
+        ))
+    }
+
+    /// Fail early on a declared `Content-Length` over the limit, unless
+    /// oversized bodies are truncated
+    pub fn check_content_length(&self, content_length: Option<u64>) -> Result<(), TransportError> {
+        match content_length {
+            Some(length) if length > self.max_bytes && !self.truncate => Err(self.exceeded()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Apply the limit to the next chunk, given the bytes already received
+    ///
+    /// Returns the part of the chunk to keep and whether the body ends
+    /// there, truncated.
+    fn admit(&self, received: u64, chunk: Bytes) -> Result<(Bytes, bool), TransportError> {
+        let room = self.max_bytes.saturating_sub(received);
+        if chunk.len() as u64 <= room {
+            Ok((chunk, false))
+        } else if self.truncate {
+            Ok((chunk.slice(..room as usize), true))
+        } else {
+            Err(self.exceeded())
+        }
+    }
+}
+
+/// The receiving end of a stream's chunks
+///
+/// With a byte budget (see [`ByteStream::from_response`]), each chunk's
+/// bytes go back to the budget as it is taken, whoever takes it.
+pub struct Receiver {
+    chunks: mpsc::Receiver<Result<Bytes, TransportError>>,
+    budget: Option<Arc<Semaphore>>,
+}
+
+impl Receiver {
+    pub async fn recv(&mut self) -> Option<Result<Bytes, TransportError>> {
+        let chunk = self.chunks.recv().await;
+        if let (Some(Ok(bytes)), Some(budget)) = (&chunk, &self.budget) {
+            budget.add_permits(bytes.len());
+        }
+        chunk
+    }
+
+    /// Stop receiving; the producer's next send fails
+    pub fn close(&mut self) {
+        self.chunks.close();
+    }
+}
+
+impl From<mpsc::Receiver<Result<Bytes, TransportError>>> for Receiver {
+    fn from(chunks: mpsc::Receiver<Result<Bytes, TransportError>>) -> Self {
+        Self { chunks, budget: None }
+    }
+}
+
+/// Chunks a streamed body's producer task forwards between yields to the
+/// scheduler, unless `stream_yield_interval` says otherwise
+pub const DEFAULT_YIELD_INTERVAL: usize = 16;
+
+/// Lock a stream's receiver, skipping the async acquisition when it is free
+///
+/// Only one coroutine normally reads a stream, so the lock is almost never
+/// contended; waiting on it is left for when it is.
+pub async fn lock(receiver: &tokio::sync::Mutex<Receiver>) -> tokio::sync::MutexGuard<'_, Receiver> {
+    match receiver.try_lock() {
+        Ok(rx) => rx,
+        Err(_) => receiver.lock().await,
+    }
+}
 
 /// A streaming response body that can be consumed from Python
 #[pyclass]
 pub struct ByteStream {
-    receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<Result<Bytes, TransportError>>>>,
+    receiver: Arc<tokio::sync::Mutex<Receiver>>,
+    /// Body length from `Content-Length`, if the server sent one
+    total_bytes: Option<u64>,
+    /// Bytes forwarded by the producer task so far
+    bytes_read: Arc<AtomicU64>,
+    /// Set by the producer task when it cut the body at its limit
+    truncated: Arc<AtomicBool>,
+    /// The response's extensions, which get `"truncated": True` once the
+    /// consumer reaches the end of a truncated body
+    extensions: Arc<OnceLock<PyObject>>,
+    /// Starts reading a lazy body, see [`ByteStream::lazy`]
+    start: std::sync::Mutex<Option<oneshot::Sender<()>>>,
 }
 
 impl ByteStream {
     /// Create a new ByteStream from a reqwest response body
-    pub fn from_response(response: reqwest::Response) -> Self {
+    ///
+    /// When the response is gzip/brotli/deflate encoded, `decompress` has
+    /// already wrapped its body to decode incrementally and stripped the
+    /// `Content-Encoding`/`Content-Length` headers, so each chunk forwarded
+    /// here is already decompressed. The bounded channel keeps at most 32
+    /// chunks buffered ahead of the Python consumer.
+    ///
+    /// With a `limit`, a body that grows past it ends in an error, or is cut
+    /// at the limit if it truncates.
+    ///
+    /// While the consumer keeps up, every send finds room in the channel and
+    /// completes at once, so on a fast connection the producer would run
+    /// without giving other tasks on its worker a turn. It yields to the
+    /// scheduler after every `yield_interval` chunks instead.
+    ///
+    /// With `buffer_bytes`, the chunks buffered ahead also hold no more than
+    /// that many bytes together: the producer waits for the consumer to take
+    /// enough of them before forwarding the next, and splits chunks larger
+    /// than the whole budget.
+    pub fn from_response(
+        response: reqwest::Response,
+        limit: Option<BodyLimit>,
+        yield_interval: usize,
+        buffer_bytes: Option<usize>,
+    ) -> Self {
         let (tx, rx) = mpsc::channel(32);
+        let budget = buffer_bytes.map(|bytes| (Arc::new(Semaphore::new(bytes)), bytes));
+        let receiver = Receiver { chunks: rx, budget: budget.as_ref().map(|(budget, _)| budget.clone()) };
+        // None for decoded bodies, whose Content-Length `decompress` removes
+        let total_bytes = response.content_length();
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let counter = bytes_read.clone();
+        let truncated = Arc::new(AtomicBool::new(false));
+        let truncated_flag = truncated.clone();
         let mut stream = response.bytes_stream();
-        
+
         // Spawn a task to forward the stream to the channel
         tokio::spawn(async move {
+            let mut forwarded = 0;
             while let Some(result) = stream.next().await {
-                let bytes_result = result.map_err(TransportError::from);
-                if tx.send(bytes_result).await.is_err() {
-                    break; // Receiver dropped
+                let (bytes_result, end) = match (result.map_err(TransportError::from), limit) {
+                    (Ok(bytes), Some(limit)) => match limit.admit(counter.load(Ordering::Relaxed), bytes) {
+                        Ok((bytes, truncated)) => {
+                            truncated_flag.store(truncated, Ordering::Relaxed);
+                            (Ok(bytes), truncated)
+                        }
+                        // Stop reading an oversized body
+                        Err(e) => (Err(e), true),
+                    },
+                    (bytes_result, _) => (bytes_result, false),
+                };
+                let sent = match (bytes_result, &budget) {
+                    (Ok(bytes), Some((budget, max_bytes))) => {
+                        send_within_budget(&tx, budget, *max_bytes, bytes, &counter).await
+                    }
+                    (bytes_result, _) => {
+                        if let Ok(bytes) = &bytes_result {
+                            counter.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                        }
+                        tx.send(bytes_result).await.is_ok()
+                    }
+                };
+                if !sent || end {
+                    break; // Receiver dropped, or the body ends here
+                }
+                forwarded += 1;
+                if forwarded % yield_interval == 0 {
+                    tokio::task::yield_now().await;
                 }
             }
         });
-        
+
+        Self {
+            receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
+            total_bytes,
+            bytes_read,
+            truncated,
+            extensions: Default::default(),
+            start: Default::default(),
+        }
+    }
+
+    /// Create a ByteStream that reads `response`'s whole body, applying
+    /// `limit`, when it is first read from, for the `lazy_body` extension
+    ///
+    /// The body then arrives as one chunk, with its trailers added to the
+    /// extensions. The request's concurrency slots are held until then; a
+    /// stream dropped or closed unread drops the response instead, which
+    /// aborts the transfer and closes its connection.
+    pub fn lazy(response: reqwest::Response, limit: Option<BodyLimit>, permits: Vec<Permit>) -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        let (start, started) = oneshot::channel();
+        let total_bytes = response.content_length();
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let counter = bytes_read.clone();
+        let truncated = Arc::new(AtomicBool::new(false));
+        let truncated_flag = truncated.clone();
+        let extensions: Arc<OnceLock<PyObject>> = Default::default();
+        let trailer_extensions = extensions.clone();
+
+        tokio::spawn(async move {
+            if started.await.is_err() {
+                return; // Dropped or closed unread
+            }
+            let read = read_body_with_trailers(response, limit).await;
+            drop(permits);
+            let bytes = match read {
+                Ok((bytes, trailers, truncated)) => {
+                    truncated_flag.store(truncated, Ordering::Relaxed);
+                    if let (Some(trailers), Some(extensions)) = (&trailers, trailer_extensions.get()) {
+                        Python::with_gil(|py| {
+                            let trailers = json_to_python(py, &trailers_to_json(trailers));
+                            let _ = extensions.as_ref(py).set_item("trailers", trailers);
+                        });
+                    }
+                    bytes
+                }
+                Err(e) => {
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            counter.store(bytes.len() as u64, Ordering::Relaxed);
+            if !bytes.is_empty() {
+                let _ = tx.send(Ok(bytes)).await;
+            }
+        });
+
         Self {
-            receiver: Arc::new(tokio::sync::Mutex::new(rx)),
+            receiver: Arc::new(tokio::sync::Mutex::new(rx.into())),
+            total_bytes,
+            bytes_read,
+            truncated,
+            extensions,
+            start: std::sync::Mutex::new(Some(start)),
         }
     }
-    
+
+    /// Start reading a lazy body, if this is one not yet started
+    fn start_reading(&self) {
+        if let Some(start) = self.start.lock().unwrap().take() {
+            let _ = start.send(());
+        }
+    }
+
+    /// Start reading, and take the channel the chunks arrive on, for
+    /// consumers that read the stream from Rust
+    pub fn chunks(&self) -> Arc<tokio::sync::Mutex<Receiver>> {
+        self.start_reading();
+        self.receiver.clone()
+    }
+
+    /// Report truncation in `extensions`, the response's extensions dict
+    pub fn set_extensions(&self, extensions: PyObject) {
+        let _ = self.extensions.set(extensions);
+    }
+
+    /// Wrap an already-loaded body as a single-chunk stream
+    ///
+    /// The chunk is queued up front and the channel closed behind it, so no
+    /// producer task is spawned and no runtime is needed.
+    pub fn from_bytes(data: Bytes) -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        let len = data.len() as u64;
+        if !data.is_empty() {
+            tx.try_send(Ok(data)).expect("a new channel has room for one chunk");
+        }
+
+        Self {
+            receiver: Arc::new(tokio::sync::Mutex::new(rx.into())),
+            total_bytes: Some(len),
+            bytes_read: Arc::new(AtomicU64::new(len)),
+            truncated: Default::default(),
+            extensions: Default::default(),
+            start: Default::default(),
+        }
+    }
+
+    /// A stream that ends immediately
+    pub fn empty() -> Self {
+        Self::from_bytes(Bytes::new())
+    }
+
+    /// Wrap a static body without copying it
+    pub fn from_static(data: &'static [u8]) -> Self {
+        Self::from_bytes(Bytes::from_static(data))
+    }
+
     /// Create a new ByteStream from a bytes iterator
-    pub fn from_bytes_iter<I>(iter: I) -> Self 
+    pub fn from_bytes_iter<I>(iter: I) -> Self
     where
         I: Iterator<Item = Result<Bytes, TransportError>> + Send + 'static,
     {
         let (tx, rx) = mpsc::channel(32);
-        
+        let bytes_read = Arc::new(AtomicU64::new(0));
+        let counter = bytes_read.clone();
+
         // Spawn a task to forward the iterator to the channel
         tokio::spawn(async move {
             for result in iter {
+                if let Ok(bytes) = &result {
+                    counter.fetch_add(bytes.len() as u64, Ordering::Relaxed);
+                }
                 if tx.send(result).await.is_err() {
                     break; // Receiver dropped
                 }
             }
         });
-        
+
         Self {
-            receiver: Arc::new(tokio::sync::Mutex::new(rx)),
+            receiver: Arc::new(tokio::sync::Mutex::new(rx.into())),
+            total_bytes: None,
+            bytes_read,
+            truncated: Default::default(),
+            extensions: Default::default(),
+            start: Default::default(),
         }
     }
 }
 
+/// Forward `bytes` once the consumer has left room for it in `budget`, in
+/// pieces of at most `max_bytes`, counting them in `counter` as they go
+///
+/// Returns false once the receiver is gone, whether it went while this
+/// waited for room or at a send.
+async fn send_within_budget(
+    tx: &mpsc::Sender<Result<Bytes, TransportError>>,
+    budget: &Semaphore,
+    max_bytes: usize,
+    mut bytes: Bytes,
+    counter: &AtomicU64,
+) -> bool {
+    while !bytes.is_empty() {
+        let piece = bytes.split_to(bytes.len().min(max_bytes));
+        // `max_bytes` fits in a u32, see `check_stream_buffer_bytes`
+        let room = tokio::select! {
+            room = budget.acquire_many(piece.len() as u32) => room,
+            _ = tx.closed() => return false,
+        };
+        // Given back as the consumer takes the piece, see `Receiver::recv`
+        room.expect("the budget is never closed").forget();
+        counter.fetch_add(piece.len() as u64, Ordering::Relaxed);
+        if tx.send(Ok(piece)).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 #[pymethods]
 impl ByteStream {
     /// Get the next chunk of bytes (async) - simplified version
     fn read_chunk<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        self.start_reading();
         let receiver = self.receiver.clone();
-        
+
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            let mut rx = receiver.lock().await;
+            let mut rx = lock(&receiver).await;
             match rx.recv().await {
-                Some(Ok(bytes)) => {
-                    Python::with_gil(|py| {
-                        let py_bytes = PyBytes::new(py, &bytes);
-                        Ok::<PyObject, PyErr>(py_bytes.into())
-                    })
-                }
+                Some(Ok(bytes)) => Python::with_gil(|py| {
+                    let py_bytes = PyBytes::new(py, &bytes);
+                    Ok::<PyObject, PyErr>(py_bytes.into())
+                }),
+                Some(Err(e)) => Err(PyErr::from(e)),
+                None => Python::with_gil(|py| Ok::<PyObject, PyErr>(py.None())),
+            }
+        })
+    }
+
+    /// Async iteration support so httpcore can consume the stream directly
+    fn __aiter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    /// Get the next chunk, raising `StopAsyncIteration` at end of body
+    fn __anext__<'py>(&self, py: Python<'py>) -> PyResult<Option<&'py PyAny>> {
+        self.start_reading();
+        let receiver = self.receiver.clone();
+        let truncated = self.truncated.clone();
+        let extensions = self.extensions.clone();
+
+        let next = pyo3_asyncio::tokio::future_into_py(py, async move {
+            let mut rx = lock(&receiver).await;
+            match rx.recv().await {
+                Some(Ok(bytes)) => Python::with_gil(|py| {
+                    let py_bytes = PyBytes::new(py, &bytes);
+                    Ok::<PyObject, PyErr>(py_bytes.into())
+                }),
                 Some(Err(e)) => Err(PyErr::from(e)),
                 None => {
-                    Python::with_gil(|py| {
-                        Ok::<PyObject, PyErr>(py.None())
-                    })
+                    if truncated.load(Ordering::Relaxed) {
+                        if let Some(extensions) = extensions.get() {
+                            Python::with_gil(|py| extensions.as_ref(py).set_item("truncated", true))?;
+                        }
+                    }
+                    Err(PyStopAsyncIteration::new_err(()))
+                }
+            }
+        })?;
+        Ok(Some(next))
+    }
+
+    /// Bytes of the body not yet received, if `Content-Length` is known
+    ///
+    /// Counts bytes once the producer task has forwarded them, so up to the
+    /// channel's buffered chunks may be counted before Python reads them.
+    fn remaining_hint(&self) -> Option<u64> {
+        let total = self.total_bytes?;
+        Some(total.saturating_sub(self.bytes_read.load(Ordering::Relaxed)))
+    }
+
+    /// The body's total length from `Content-Length`, for progress bars;
+    /// `None` if unknown, as for decompressed bodies
+    #[getter]
+    fn content_length(&self) -> Option<u64> {
+        self.total_bytes
+    }
+
+    /// Stop receiving chunks; the forwarding task exits on its next send,
+    /// and a lazy body not yet read is dropped unread
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        drop(self.start.lock().unwrap().take());
+        let receiver = self.receiver.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            lock(&receiver).await.close();
+            Python::with_gil(|py| Ok(py.None()))
+        })
+    }
+}
+
+/// How many written bytes an `AsyncBodyWriter` buffers ahead of the
+/// connection before `write` waits
+const BODY_WRITER_BUFFER: usize = 64 * 1024;
+
+/// A request body written from Python chunk by chunk while the request is
+/// being sent, for bodies generated as they go (e.g. rows from a database
+/// cursor)
+///
+/// Pass it as the request's content, start the request, then `write` the
+/// chunks and `aclose` the writer to end the body. Writes wait once
+/// `BODY_WRITER_BUFFER` bytes are waiting to be sent, so the body is never
+/// buffered whole.
+#[pyclass]
+pub struct AsyncBodyWriter {
+    writer: Arc<tokio::sync::Mutex<Option<tokio::io::DuplexStream>>>,
+    /// The read half, until a request takes it as its body
+    reader: std::sync::Mutex<Option<tokio::io::DuplexStream>>,
+}
+
+impl AsyncBodyWriter {
+    /// The body the written chunks stream into; a writer is the body of one
+    /// request only
+    pub fn finish(&self) -> PyResult<reqwest::Body> {
+        let reader = self.reader.lock().unwrap().take().ok_or_else(|| {
+            pyo3::exceptions::PyRuntimeError::new_err("AsyncBodyWriter is already the body of a request")
+        })?;
+        Ok(reqwest::Body::wrap_stream(tokio_util::io::ReaderStream::new(reader)))
+    }
+}
+
+#[pymethods]
+impl AsyncBodyWriter {
+    #[new]
+    fn new() -> Self {
+        let (writer, reader) = tokio::io::duplex(BODY_WRITER_BUFFER);
+        Self { writer: Arc::new(tokio::sync::Mutex::new(Some(writer))), reader: std::sync::Mutex::new(Some(reader)) }
+    }
+
+    /// Append `chunk` to the body (async), waiting while the connection
+    /// catches up
+    ///
+    /// Fails with a write error once the request has stopped reading the
+    /// body, say because it failed, and after `aclose`.
+    fn write<'py>(&self, py: Python<'py>, chunk: Vec<u8>) -> PyResult<&'py PyAny> {
+        let writer = self.writer.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let mut writer = writer.lock().await;
+            let Some(stream) = writer.as_mut() else {
+                return Err(TransportError::WriteError("AsyncBodyWriter is closed".to_string()).into());
+            };
+            stream
+                .write_all(&chunk)
+                .await
+                .map_err(|e| TransportError::WriteError(format!("request body no longer being sent: {}", e)))?;
+            Python::with_gil(|py| Ok(py.None()))
+        })
+    }
+
+    /// End the body (async); closing again does nothing
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let writer = self.writer.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            if let Some(mut stream) = writer.lock().await.take() {
+                // The request may already have stopped reading the body
+                let _ = stream.shutdown().await;
+            }
+            Python::with_gil(|py| Ok(py.None()))
+        })
+    }
+}
+
+/// What a `DeferredBody` holds: the unread response, or what reading it gave
+enum Deferred {
+    Unread { response: reqwest::Response, limit: Option<BodyLimit>, permits: Vec<Permit> },
+    Read(Bytes),
+    Failed,
+    Closed,
+}
+
+/// A response body left unread by the `defer_body` extension, for the
+/// caller to read or discard once the headers show whether it is wanted
+///
+/// The request's concurrency slots and its connection are held until the
+/// body is read or closed.
+#[pyclass]
+pub struct DeferredBody {
+    state: Arc<tokio::sync::Mutex<Deferred>>,
+    /// The response's extensions, which get the body's `"trailers"` (and
+    /// `"truncated"`) once it is read
+    extensions: Arc<OnceLock<PyObject>>,
+}
+
+impl DeferredBody {
+    /// Defer reading `response`'s body, applying `limit` once it is read
+    pub fn new(response: reqwest::Response, limit: Option<BodyLimit>, permits: Vec<Permit>) -> Self {
+        Self {
+            state: Arc::new(tokio::sync::Mutex::new(Deferred::Unread { response, limit, permits })),
+            extensions: Default::default(),
+        }
+    }
+
+    /// A deferred body already known to be empty, for responses that have none
+    pub fn empty() -> Self {
+        Self { state: Arc::new(tokio::sync::Mutex::new(Deferred::Read(Bytes::new()))), extensions: Default::default() }
+    }
+
+    /// Report trailers and truncation in `extensions`, the response's
+    /// extensions dict
+    pub fn set_extensions(&self, extensions: PyObject) {
+        let _ = self.extensions.set(extensions);
+    }
+}
+
+#[pymethods]
+impl DeferredBody {
+    /// Read the whole body (async); later calls return the same bytes
+    fn read<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let state = self.state.clone();
+        let extensions = self.extensions.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let mut state = state.lock().await;
+            let bytes = match std::mem::replace(&mut *state, Deferred::Failed) {
+                Deferred::Unread { response, limit, permits } => {
+                    let (bytes, trailers, truncated) = read_body_with_trailers(response, limit).await?;
+                    drop(permits);
+                    if let Some(extensions) = extensions.get() {
+                        Python::with_gil(|py| {
+                            let extensions = extensions.as_ref(py);
+                            if let Some(trailers) = &trailers {
+                                extensions.set_item("trailers", json_to_python(py, &trailers_to_json(trailers)))?;
+                            }
+                            if limit.is_some_and(|limit| limit.truncate) {
+                                extensions.set_item("truncated", truncated)?;
+                            }
+                            Ok::<(), PyErr>(())
+                        })?;
+                    }
+                    bytes
+                }
+                Deferred::Read(bytes) => bytes,
+                Deferred::Failed => {
+                    return Err(pyo3::exceptions::PyRuntimeError::new_err("the deferred body failed to read"));
+                }
+                Deferred::Closed => {
+                    *state = Deferred::Closed;
+                    return Err(pyo3::exceptions::PyRuntimeError::new_err("the deferred body was closed unread"));
                 }
+            };
+            *state = Deferred::Read(bytes.clone());
+            Python::with_gil(|py| Ok::<PyObject, PyErr>(PyBytes::new(py, &bytes).into()))
+        })
+    }
+
+    /// Discard the body unread (async), closing its connection and freeing
+    /// its concurrency slots; a body already read stays readable
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let state = self.state.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let mut state = state.lock().await;
+            if matches!(*state, Deferred::Unread { .. }) {
+                *state = Deferred::Closed;
             }
+            Python::with_gil(|py| Ok(py.None()))
         })
     }
 }
@@ -91,25 +632,9 @@ pub struct SyncByteStream {
 }
 
 impl SyncByteStream {
-    /// Create a new SyncByteStream from a response body
-    pub fn from_response(response: reqwest::blocking::Response) -> Self {
-        let mut bytes_vec = Vec::new();
-        
-        // For blocking responses, we'll just read the full body at once
-        // This is simpler and more appropriate for the sync API
-        match response.bytes() {
-            Ok(bytes) => {
-                bytes_vec.push(Ok(bytes));
-            }
-            Err(e) => {
-                bytes_vec.push(Err(TransportError::from(e)));
-            }
-        }
-        
-        Self {
-            bytes_vec,
-            index: 0,
-        }
+    /// Create a SyncByteStream yielding pre-built chunks (or errors) in order
+    pub fn from_chunks(bytes_vec: Vec<Result<Bytes, TransportError>>) -> Self {
+        Self { bytes_vec, index: 0 }
     }
 }
 
@@ -120,10 +645,10 @@ impl SyncByteStream {
         if self.index >= self.bytes_vec.len() {
             return Ok(py.None());
         }
-        
+
         let result = &self.bytes_vec[self.index];
         self.index += 1;
-        
+
         match result {
             Ok(bytes) => {
                 let py_bytes = PyBytes::new(py, bytes);
@@ -132,25 +657,98 @@ impl SyncByteStream {
             Err(e) => Err(PyErr::from(e.clone())),
         }
     }
+
+    /// Iteration support so httpcore can consume the stream directly
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self, py: Python) -> PyResult<Option<PyObject>> {
+        let chunk = self.read_chunk(py)?;
+        Ok((!chunk.is_none(py)).then_some(chunk))
+    }
+}
+
+/// Read a whole response body, along with the trailers that followed it
+///
+/// `bytes()` drops trailers, so the body is collected frame by frame
+/// instead. Trailers are `None` if the server sent none. With a `limit`,
+/// a body over it fails, or is cut at the limit (and the connection
+/// dropped) if it truncates; the flag returned says whether it was.
+pub async fn read_body_with_trailers(
+    response: reqwest::Response,
+    limit: Option<BodyLimit>,
+) -> Result<(Bytes, Option<HeaderMap>, bool), TransportError> {
+    read_body_within(response, limit, None).await
+}
+
+/// [`read_body_with_trailers`], failing with `ReadTimeout` when the server
+/// sends nothing for `read_timeout`
+///
+/// The timeout applies to each wait for the next part of the body, so a
+/// slow body that keeps arriving is read however long it takes in all.
+pub async fn read_body_within(
+    response: reqwest::Response,
+    limit: Option<BodyLimit>,
+    read_timeout: Option<Duration>,
+) -> Result<(Bytes, Option<HeaderMap>, bool), TransportError> {
+    if limit.is_none() && read_timeout.is_none() {
+        let collected = reqwest::Body::from(response).collect().await.map_err(TransportError::from)?;
+        let trailers = collected.trailers().cloned();
+        return Ok((collected.to_bytes(), trailers, false));
+    }
+    let url = response.url().clone();
+    let mut body = reqwest::Body::from(response);
+    let mut data = BytesMut::new();
+    let mut trailers = None;
+    loop {
+        let frame = match read_timeout {
+            Some(read_timeout) => tokio::time::timeout(read_timeout, body.frame())
+                .await
+                .map_err(|_| TransportError::ReadTimeout(format!("no data from {} for {:?}", url, read_timeout)))?,
+            None => body.frame().await,
+        };
+        let Some(frame) = frame else {
+            break;
+        };
+        match frame.map_err(TransportError::from)?.into_data() {
+            Ok(chunk) => {
+                let (chunk, truncated) = match limit {
+                    Some(limit) => limit.admit(data.len() as u64, chunk)?,
+                    None => (chunk, false),
+                };
+                data.extend_from_slice(&chunk);
+                if truncated {
+                    return Ok((data.freeze(), None, true));
+                }
+            }
+            Err(frame) => trailers = frame.into_trailers().ok(),
+        }
+    }
+    Ok((data.freeze(), trailers, false))
 }
 
 /// Utility functions for handling Python request bodies
 pub fn extract_body_from_python(py_body: &PyAny) -> PyResult<reqwest::Body> {
+    Ok(reqwest::Body::from(extract_body_bytes_from_python(py_body)?))
+}
+
+/// Read a Python request body (bytes, string, or iterator of either) into memory
+pub fn extract_body_bytes_from_python(py_body: &PyAny) -> PyResult<Vec<u8>> {
     if py_body.is_none() {
-        return Ok(reqwest::Body::from(""));
+        return Ok(Vec::new());
     }
-    
+
     // Try to extract as bytes first
     if let Ok(py_bytes) = py_body.downcast::<PyBytes>() {
-        let bytes = py_bytes.as_bytes();
-        return Ok(reqwest::Body::from(bytes.to_vec()));
+        return Ok(py_bytes.as_bytes().to_vec());
     }
-    
+
     // Try to extract as string
     if let Ok(py_str) = py_body.extract::<String>() {
-        return Ok(reqwest::Body::from(py_str));
+        return Ok(py_str.into_bytes());
     }
-    
+
     // Try to extract as iterator
     if let Ok(py_iter) = py_body.iter() {
         let mut body_data = Vec::new();
@@ -161,15 +759,11 @@ pub fn extract_body_from_python(py_body: &PyAny) -> PyResult<reqwest::Body> {
             } else if let Ok(chunk_str) = item.extract::<String>() {
                 body_data.extend_from_slice(chunk_str.as_bytes());
             } else {
-                return Err(pyo3::exceptions::PyTypeError::new_err(
-                    "Body iterator must yield bytes or strings"
-                ));
+                return Err(pyo3::exceptions::PyTypeError::new_err("Body iterator must yield bytes or strings"));
             }
         }
-        return Ok(reqwest::Body::from(body_data));
+        return Ok(body_data);
     }
-    
-    Err(pyo3::exceptions::PyTypeError::new_err(
-        "Body must be bytes, string, or iterator"
-    ))
-} 
\ No newline at end of file
+
+    Err(pyo3::exceptions::PyTypeError::new_err("Body must be bytes, string, or iterator"))
+}
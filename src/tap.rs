@@ -0,0 +1,422 @@
+//! Wiretapped requests, for the `wiretap` extension: the literal bytes a
+//! request and its response were sent as, for debugging protocol-level
+//! mismatches
+//!
+//! reqwest's connections are sealed, so their I/O can't be watched from a
+//! connector layer. A wiretapped request is sent on a connection of its
+//! own instead, opened as `check_origin` opens one (through the
+//! transport's resolver and verified as its requests are, but never
+//! through its proxy), and spoken to over HTTP/1.1 by hyper. Every byte
+//! written to and read from the connection, above TLS, is kept, and the
+//! response body is read whole, so the whole exchange is held in memory
+//! twice over. The middleware doesn't see the request: it isn't retried,
+//! signed, given a token or redirected, and its body isn't decoded.
+//!
+//! Requests sent in TLS 1.3 early data take the same path, since reqwest's
+//! connector never writes any. The connection resumes a session from the
+//! transport's probe cache, and the request goes out with the ClientHello
+//! when the session allows it. tokio-rustls sends it again once the
+//! handshake completes if the server rejects the early data, and a `425 Too
+//! Early` response has it sent once more, on a new connection without
+//! early data.
+
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper_util::rt::TokioIo;
+use reqwest::header::{HeaderValue, ACCEPT, HOST, USER_AGENT};
+use reqwest::{Request, Response, ResponseBuilderExt, StatusCode, Url};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use crate::client::ClientConfig;
+use crate::dns::DnsResolver;
+use crate::errors::TransportError;
+use crate::probe::{connect, host_and_port, resolve, ProbeSessions};
+use crate::wire::{authority, origin_form};
+
+/// The bytes of a wiretapped exchange, carried in the response's
+/// extensions until they are attached to the Python response
+#[derive(Debug, Clone)]
+pub struct WireTap {
+    /// Everything written: the request head and body
+    pub request: Bytes,
+    /// Everything read: the response head and body, framed as they were
+    /// sent (e.g. chunked)
+    pub response: Bytes,
+}
+
+/// Marks a response to a request the server accepted in TLS 1.3 early data
+#[derive(Debug, Clone, Copy)]
+pub struct SentEarly;
+
+/// A connection's I/O, keeping a copy of every byte through it
+struct Tap<S> {
+    inner: S,
+    written: Arc<Mutex<Vec<u8>>>,
+    read: Arc<Mutex<Vec<u8>>>,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Tap<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        let polled = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = polled {
+            this.read.lock().unwrap().extend_from_slice(&buf.filled()[filled..]);
+        }
+        polled
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Tap<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let polled = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = polled {
+            this.written.lock().unwrap().extend_from_slice(&buf[..written]);
+        }
+        polled
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// Send `request` on a wiretapped connection of its own and read its
+/// response whole, within the request's timeout (or the client's)
+///
+/// The response carries the exchange's bytes as a [`WireTap`] in its
+/// extensions, and [`SentEarly`] if `early_data` had the request sent in
+/// TLS 1.3 early data and the server accepted it. Only a GET or HEAD
+/// without a body is sent in early data, since the server may act on it
+/// before the handshake rules out a replay. A streamed request body can't
+/// be sent this way.
+pub async fn send_tapped(
+    config: &ClientConfig,
+    resolver: &DnsResolver,
+    sessions: &ProbeSessions,
+    request: Request,
+    early_data: bool,
+) -> Result<Response, TransportError> {
+    let timeout = request.timeout().copied().unwrap_or(config.timeout);
+    let url = request.url().clone();
+    let early_data = early_data
+        && matches!(*request.method(), http::Method::GET | http::Method::HEAD)
+        && request.body().is_none_or(|body| body.as_bytes().is_some_and(<[u8]>::is_empty));
+    tokio::time::timeout(timeout, exchange(config, resolver, sessions, request, early_data))
+        .await
+        .map_err(|_| TransportError::RequestTimeout(format!("wiretapped request to {} timed out", url)))?
+}
+
+async fn exchange(
+    config: &ClientConfig,
+    resolver: &DnsResolver,
+    sessions: &ProbeSessions,
+    request: Request,
+    early_data: bool,
+) -> Result<Response, TransportError> {
+    let url = request.url().clone();
+    let body = match request.body() {
+        None => Bytes::new(),
+        Some(body) => Bytes::copy_from_slice(body.as_bytes().ok_or_else(|| {
+            TransportError::LocalProtocolError("wiretap needs a buffered request body, not a stream".to_string())
+        })?),
+    };
+    let outgoing = || {
+        let mut outgoing = http::Request::builder()
+            .method(request.method().clone())
+            .uri(origin_form(&url))
+            .body(Full::new(body.clone()))
+            .map_err(|e| TransportError::LocalProtocolError(e.to_string()))?;
+        // What reqwest adds to every request, and hyper's client connections don't
+        let headers = outgoing.headers_mut();
+        *headers = request.headers().clone();
+        if let Ok(host) = HeaderValue::from_str(&authority(&url)) {
+            headers.entry(HOST).or_insert(host);
+        }
+        headers.entry(ACCEPT).or_insert(HeaderValue::from_static("*/*"));
+        if let Ok(user_agent) = HeaderValue::from_str(&config.user_agent) {
+            headers.entry(USER_AGENT).or_insert(user_agent);
+        }
+        Ok::<_, TransportError>(outgoing)
+    };
+
+    let (host, port) = host_and_port(&url)?;
+    let resolved = resolve(resolver, host, port).await?;
+    let (mut response, mut tap, mut sent_early) =
+        exchange_once(config, sessions, &url, host, &resolved, outgoing()?, early_data).await?;
+    // The server wants the request again, once the handshake rules out a
+    // replay (RFC 8470)
+    if sent_early && response.status() == StatusCode::TOO_EARLY {
+        (response, tap, sent_early) =
+            exchange_once(config, sessions, &url, host, &resolved, outgoing()?, false).await?;
+    }
+    let mut response = into_response(url, response, tap);
+    if sent_early {
+        response.extensions_mut().insert(SentEarly);
+    }
+    Ok(response)
+}
+
+/// Send `request` on a new connection to `resolved`, in early data if
+/// `early_data` and the session resumed allows it, returning the response,
+/// the exchange's bytes and whether the server accepted the early data
+async fn exchange_once(
+    config: &ClientConfig,
+    sessions: &ProbeSessions,
+    url: &Url,
+    host: &str,
+    resolved: &[SocketAddr],
+    request: http::Request<Full<Bytes>>,
+    early_data: bool,
+) -> Result<(http::Response<Bytes>, WireTap, bool), TransportError> {
+    let (stream, _) = connect(url, resolved).await?;
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let read = Arc::new(Mutex::new(Vec::new()));
+    let accepted = Arc::new(AtomicBool::new(false));
+    let response = if url.scheme() == "https" {
+        let stream = tls_connect(config, sessions, host, stream, early_data, accepted.clone()).await?;
+        send(Tap { inner: stream, written: written.clone(), read: read.clone() }, request).await?
+    } else {
+        send(Tap { inner: stream, written: written.clone(), read: read.clone() }, request).await?
+    };
+    let tap = WireTap {
+        request: Bytes::from(std::mem::take(&mut *written.lock().unwrap())),
+        response: Bytes::from(std::mem::take(&mut *read.lock().unwrap())),
+    };
+    Ok((response, tap, accepted.load(Ordering::Relaxed)))
+}
+
+/// Send `request` over HTTP/1.1 on `io`, reading the response whole
+async fn send<S>(io: Tap<S>, request: http::Request<Full<Bytes>>) -> Result<http::Response<Bytes>, TransportError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(io))
+        .await
+        .map_err(|e| TransportError::ConnectError(e.to_string()))?;
+    let connection = tokio::spawn(connection);
+    let sent = async {
+        let (parts, body) = sender.send_request(request).await?.into_parts();
+        let body = body.collect().await?.to_bytes();
+        Ok::<_, hyper::Error>(http::Response::from_parts(parts, body))
+    }
+    .await;
+    connection.abort();
+    sent.map_err(|e| {
+        if e.is_parse() {
+            TransportError::RemoteProtocolError(format!("wiretapped response is invalid: {}", e))
+        } else {
+            TransportError::ReadError(format!("wiretapped request failed: {}", e))
+        }
+    })
+}
+
+/// `response` to `url` as a reqwest response, carrying `tap`
+fn into_response(url: Url, response: http::Response<Bytes>, tap: WireTap) -> Response {
+    let (parts, body) = response.into_parts();
+    let mut tapped = http::Response::builder()
+        .url(url)
+        .body(reqwest::Body::from(body))
+        .expect("a response with only a URL is valid");
+    *tapped.status_mut() = parts.status;
+    *tapped.version_mut() = parts.version;
+    *tapped.headers_mut() = parts.headers;
+    tapped.extensions_mut().insert(tap);
+    tapped.into()
+}
+
+/// Start a TLS handshake with `host` on `stream`, offering only HTTP/1.1
+///
+/// With `early_data`, and a resumed session that allows it, the handshake
+/// completes as the request is written, and `accepted` is set once it has
+/// if the server accepted the request as early data.
+#[cfg(feature = "rustls")]
+async fn tls_connect(
+    config: &ClientConfig,
+    sessions: &ProbeSessions,
+    host: &str,
+    stream: TcpStream,
+    early_data: bool,
+    accepted: Arc<AtomicBool>,
+) -> Result<EarlyData, TransportError> {
+    use rustls::pki_types::ServerName;
+
+    if config.tls_backend != crate::client::TlsBackend::Rustls {
+        return Err(TransportError::Other("wiretap only supports https with the rustls backend".to_string()));
+    }
+    let mut tls_config = sessions.tls_config(config, vec![b"http/1.1".to_vec()])?;
+    tls_config.enable_early_data = early_data;
+    let server_name = ServerName::try_from(host.to_string())
+        .map_err(|e| TransportError::InvalidURL(format!("invalid server name {}: {}", host, e)))?;
+    let inner = tokio_rustls::TlsConnector::from(Arc::new(tls_config))
+        .early_data(early_data)
+        .connect(server_name, stream)
+        .await
+        .map_err(|e| TransportError::SSLError(e.to_string()))?;
+    Ok(EarlyData { inner, accepted })
+}
+
+#[cfg(not(feature = "rustls"))]
+async fn tls_connect(
+    _config: &ClientConfig,
+    _sessions: &ProbeSessions,
+    _host: &str,
+    _stream: TcpStream,
+    _early_data: bool,
+    _accepted: Arc<AtomicBool>,
+) -> Result<TcpStream, TransportError> {
+    Err(TransportError::Other("wiretap requires the rustls feature for https".to_string()))
+}
+
+/// A TLS stream, noting whether the server accepted the early data written
+/// to it once a read shows the handshake is complete
+#[cfg(feature = "rustls")]
+struct EarlyData {
+    inner: tokio_rustls::client::TlsStream<TcpStream>,
+    accepted: Arc<AtomicBool>,
+}
+
+#[cfg(feature = "rustls")]
+impl AsyncRead for EarlyData {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let polled = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if polled.is_ready() {
+            let accepted = this.inner.get_ref().1.is_early_data_accepted();
+            this.accepted.store(accepted, Ordering::Relaxed);
+        }
+        polled
+    }
+}
+
+#[cfg(feature = "rustls")]
+impl AsyncWrite for EarlyData {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(test, feature = "rustls"))]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use rustls::pki_types::{CertificateDer, PrivatePkcs8KeyDer};
+    use rustls::server::{ServerConfig, ServerConnection, ServerSessionMemoryCache, StoresServerSessions};
+
+    use super::*;
+
+    /// A config for a server at 127.0.0.1 accepting up to
+    /// `max_early_data_size` bytes of early data, resuming `sessions`
+    fn server_config(sessions: Arc<dyn StoresServerSessions>, max_early_data_size: u32) -> Arc<ServerConfig> {
+        let certified = rcgen::generate_simple_self_signed(vec!["127.0.0.1".to_string()]).unwrap();
+        let key = PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+        let mut config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(vec![CertificateDer::from(certified.cert.der().to_vec())], key.into())
+            .unwrap();
+        config.session_storage = sessions;
+        config.max_early_data_size = max_early_data_size;
+        Arc::new(config)
+    }
+
+    /// Serve one request per connection, answering with the status
+    /// `status` picks for a request that did or didn't arrive in early
+    /// data, and a body saying which
+    fn serve(config: Arc<ServerConfig>, status: fn(bool) -> u16) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("https://{}/", listener.local_addr().unwrap())).unwrap();
+        std::thread::spawn(move || {
+            for socket in listener.incoming() {
+                let mut socket = socket.unwrap();
+                let mut connection = ServerConnection::new(config.clone()).unwrap();
+                connection.complete_io(&mut socket).unwrap();
+                let mut request = Vec::new();
+                if let Some(mut early_data) = connection.early_data() {
+                    early_data.read_to_end(&mut request).unwrap();
+                }
+                let early = !request.is_empty();
+                let mut stream = rustls::Stream::new(&mut connection, &mut socket);
+                while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                    let mut buf = [0; 1024];
+                    let read = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                }
+                let body = if early { "early" } else { "late" };
+                write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status(early),
+                    body.len(),
+                    body
+                )
+                .unwrap();
+                connection.send_close_notify();
+                let _ = connection.complete_io(&mut socket);
+            }
+        });
+        url
+    }
+
+    async fn get(sessions: &ProbeSessions, method: http::Method, url: &Url) -> (u16, bool, Bytes) {
+        let config = ClientConfig { verify: false, early_data: true, ..Default::default() };
+        let resolver = DnsResolver::new(Default::default());
+        let request = Request::new(method, url.clone());
+        let response = send_tapped(&config, &resolver, sessions, request, true).await.unwrap();
+        let sent_early = response.extensions().get::<SentEarly>().is_some();
+        (response.status().as_u16(), sent_early, response.bytes().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn resumed_requests_go_in_early_data() {
+        let url = serve(server_config(ServerSessionMemoryCache::new(16), 16384), |_| 200);
+        let sessions = ProbeSessions::default();
+        assert_eq!(get(&sessions, http::Method::GET, &url).await, (200, false, Bytes::from("late")));
+        assert_eq!(get(&sessions, http::Method::GET, &url).await, (200, true, Bytes::from("early")));
+        assert_eq!(get(&sessions, http::Method::HEAD, &url).await, (200, true, Bytes::new()));
+        // Never a request that isn't safe to replay
+        assert_eq!(get(&sessions, http::Method::POST, &url).await, (200, false, Bytes::from("late")));
+    }
+
+    #[tokio::test]
+    async fn rejected_early_data_is_sent_again() {
+        let sessions = ServerSessionMemoryCache::new(16);
+        let accepting = serve(server_config(sessions.clone(), 16384), |_| 200);
+        let rejecting = serve(server_config(sessions, 0), |_| 200);
+        let client_sessions = ProbeSessions::default();
+        get(&client_sessions, http::Method::GET, &accepting).await;
+        // The session allows early data, but this server turns it down
+        assert_eq!(get(&client_sessions, http::Method::GET, &rejecting).await, (200, false, Bytes::from("late")));
+    }
+
+    #[tokio::test]
+    async fn too_early_responses_are_retried_without_early_data() {
+        let url = serve(server_config(ServerSessionMemoryCache::new(16), 16384), |early| if early { 425 } else { 200 });
+        let sessions = ProbeSessions::default();
+        get(&sessions, http::Method::GET, &url).await;
+        assert_eq!(get(&sessions, http::Method::GET, &url).await, (200, false, Bytes::from("late")));
+    }
+}
@@ -1,248 +1,1629 @@
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
+use bytes::Bytes;
+use futures::StreamExt;
 use pyo3::prelude::*;
-use pyo3::types::PyBytes;
-use reqwest_middleware::ClientWithMiddleware;
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple};
 
-use crate::client::get_client;
+use reqwest_middleware::{ClientWithMiddleware, RequestBuilder};
+use tokio::io::AsyncWriteExt;
+
+use crate::accumulate::ResponseBodyAccumulator;
+use crate::batch::send_batch;
+use crate::cert::{subject_alt_names, CertificateSummary};
+use crate::client::{
+    check_expected_status, check_http_version, count_redirects, create_sync_client, get_pool, note_unfollowed_redirect,
+    unexpected_status, ClientConfig,
+};
+use crate::compress::{compress_body, compress_stream, request_compression, Compression, CompressionStats};
+use crate::config::NO_TIMEOUT;
+use crate::connect::ConnectTimeout;
+use crate::decompress::{accept_compressed, decompress};
+use crate::dns::{DnsResolver, Resolution};
+use crate::error_detail::error_detail;
 use crate::errors::TransportError;
-use crate::streaming::{ByteStream, SyncByteStream, extract_body_from_python};
+use crate::form::{form_body_from_extensions, set_form_headers};
+use crate::handshake::Handshakes;
+#[cfg(feature = "health-server")]
+use crate::health::HealthServer;
+use crate::hedge::send_hedged;
+use crate::hsts::HstsStore;
+use crate::json::{
+    check_json_content_type, from_json_bytes, json_body_from_extensions, set_json_headers, to_json_bytes, JsonOptions,
+};
+use crate::limit::{Permit, Priority, RequestLimits};
+use crate::multipart::MultipartBody;
+use crate::pool::{origin_of, ConnectionPool, PartitionKey};
+use crate::probe::{check_origin, ssl_info, ProbeSessions};
+use crate::retry::{inject_idempotency_key, is_idempotent, record_retries, retry_history_json, ResponseTimeout};
+use crate::sniff::set_sniffed_content_type;
+use crate::streaming::{
+    extract_body_bytes_from_python, extract_body_from_python, read_body_with_trailers, read_body_within,
+    AsyncBodyWriter, ByteStream, DeferredBody, SyncByteStream,
+};
+use crate::tap::{send_tapped, SentEarly, WireTap};
+use crate::upload::{watch_request_writes, EarlyResponse};
 use crate::utils::{
-    extract_method, extract_url, extract_headers, extract_extensions,
-    create_response_object, extract_timeout_from_extensions, is_streaming_requested,
+    create_response_object, extract_client_config, extract_extensions, extract_headers, extract_method, extract_url,
+    get_request_body, json_to_python, merge_headers, redacted_extensions, response_has_body, set_priority_header,
+    should_stream, trailers_to_json, ParsedExtensions,
 };
+use crate::wire::WireRecord;
+
+/// The body from a `json_body` or `form_body` extension, if the request
+/// set one, with `headers` updated to describe it
+fn body_from_extensions(py_extensions: &PyAny, headers: &mut reqwest::header::HeaderMap) -> PyResult<Option<Vec<u8>>> {
+    match (json_body_from_extensions(py_extensions)?, form_body_from_extensions(py_extensions)?) {
+        (Some(_), Some(_)) => Err(pyo3::exceptions::PyValueError::new_err("json_body and form_body can't both be set")),
+        (Some(body), None) => {
+            set_json_headers(headers);
+            Ok(Some(body))
+        }
+        (None, Some(body)) => {
+            set_form_headers(headers);
+            Ok(Some(body))
+        }
+        (None, None) => Ok(None),
+    }
+}
+
+/// Put the parsed `body` in `response.extensions["json"]`, for a request
+/// with the `parse_json` extension
+fn attach_json(py: Python, response: &PyObject, body: &[u8]) -> PyResult<()> {
+    let json = from_json_bytes(py, body)?;
+    response.getattr(py, "extensions")?.as_ref(py).set_item("json", json)
+}
+
+/// The DER certificate the server of `response`'s connection presented, if
+/// it was reached over TLS
+fn peer_certificate(response: &reqwest::Response) -> Option<Vec<u8>> {
+    let info = response.extensions().get::<reqwest::tls::TlsInfo>()?;
+    info.peer_certificate().map(<[u8]>::to_vec)
+}
+
+/// Put a summary of the server's `certificate` in
+/// `response.extensions["peer_certificate"]`, for a request with the
+/// `capture_certificate` extension or transport option
+fn attach_peer_certificate(py: Python, response: &PyObject, certificate: &[u8]) -> PyResult<()> {
+    let summary = PyDict::new(py);
+    let decoded = CertificateSummary::from_der(certificate);
+    summary.set_item("subject", decoded.as_ref().map(|c| &c.subject))?;
+    summary.set_item("issuer", decoded.as_ref().map(|c| &c.issuer))?;
+    summary.set_item("not_before", decoded.as_ref().map(|c| &c.not_before))?;
+    summary.set_item("not_after", decoded.as_ref().map(|c| &c.not_after))?;
+    summary.set_item("subject_alt_names", subject_alt_names(certificate))?;
+    // Hashed with ring, which only rustls builds have
+    #[cfg(feature = "rustls")]
+    summary.set_item(
+        "spki_sha256",
+        crate::cert::subject_public_key_info(certificate).map(|spki| {
+            use base64::Engine;
+            base64::engine::general_purpose::STANDARD.encode(ring::digest::digest(&ring::digest::SHA256, spki))
+        }),
+    )?;
+    summary.set_item("der", PyBytes::new(py, certificate))?;
+    response.getattr(py, "extensions")?.as_ref(py).set_item("peer_certificate", summary)
+}
+
+/// Put the exchange captured in `record` in `response.extensions["wire"]`,
+/// for a request with the `capture_wire` extension
+fn attach_wire(py: Python, response: &PyObject, record: &WireRecord) -> PyResult<()> {
+    let wire = PyDict::new(py);
+    wire.set_item("http_version", format!("{:?}", record.version))?;
+    wire.set_item("request_head", PyBytes::new(py, &record.request_head))?;
+    wire.set_item("response_head", PyBytes::new(py, &record.response_head))?;
+    if record.body_bytes > 0 {
+        wire.set_item("request_body", PyBytes::new(py, &record.request_body))?;
+        wire.set_item("response_body", PyBytes::new(py, &record.response_body()))?;
+    }
+    response.getattr(py, "extensions")?.as_ref(py).set_item("wire", wire)
+}
+
+/// Put the bytes a wiretapped request and its response were sent as in
+/// `response.extensions["wire_request"]` and `["wire_response"]`
+fn attach_wiretap(py: Python, response: &PyObject, tap: &WireTap) -> PyResult<()> {
+    let extensions = response.getattr(py, "extensions")?;
+    extensions.as_ref(py).set_item("wire_request", PyBytes::new(py, &tap.request))?;
+    extensions.as_ref(py).set_item("wire_response", PyBytes::new(py, &tap.response))
+}
+
+/// Give `err`, raised for a request that was retried, the attempts made as
+/// its `retry_history` attribute
+fn with_retry_history(err: PyErr, retry_history: Option<serde_json::Value>) -> PyErr {
+    if let Some(retry_history) = retry_history {
+        Python::with_gil(|py| {
+            // Exceptions without a `__dict__` just go without it
+            let _ = err.value(py).setattr("retry_history", json_to_python(py, &retry_history));
+        });
+    }
+    err
+}
+
+/// Compress a multipart form as it streams, setting the `Content-Type`
+/// (with its boundary) that `.multipart()` would have set
+fn compress_form(
+    compression: Compression,
+    form: reqwest::multipart::Form,
+    headers: &mut reqwest::header::HeaderMap,
+) -> PyResult<(reqwest::Body, CompressionStats)> {
+    let content_type = format!("multipart/form-data; boundary={}", form.boundary());
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        content_type
+            .try_into()
+            .map_err(|e: http::header::InvalidHeaderValue| TransportError::LocalProtocolError(e.to_string()))?,
+    );
+    Ok(compress_stream(compression, form.into_stream(), headers))
+}
+
+/// The root URL of `url`'s origin, used to warm the pool after a preflight
+fn origin_root(url: &reqwest::Url) -> reqwest::Url {
+    let mut root = url.clone();
+    root.set_path("/");
+    root.set_query(None);
+    root.set_fragment(None);
+    root
+}
+
+/// `warm_dns`'s result: each host mapped to its resolution dict
+fn resolutions_to_dict(py: Python, hosts: &[String], resolutions: &[Resolution]) -> PyResult<PyObject> {
+    let result = PyDict::new(py);
+    for (host, resolution) in hosts.iter().zip(resolutions) {
+        result.set_item(host, resolution.to_dict(py)?)?;
+    }
+    Ok(result.into())
+}
+
+/// Request components extracted from Python while holding the GIL
+struct AsyncRequest {
+    method: reqwest::Method,
+    url: reqwest::Url,
+    headers: reqwest::header::HeaderMap,
+    body: reqwest::Body,
+    /// Assembled later, off the GIL
+    multipart: Option<MultipartBody>,
+    extensions: ParsedExtensions,
+    /// Also applied later, off the GIL
+    compression: Option<Compression>,
+    compress_min_size: u64,
+}
+
+impl AsyncRequest {
+    fn extract(pool: &ConnectionPool, request: &PyAny) -> PyResult<Self> {
+        Self::from_parts(
+            pool,
+            request.getattr("method")?,
+            request.getattr("url")?,
+            request.getattr("headers")?,
+            request.getattr("extensions")?,
+            get_request_body(request),
+        )
+    }
+
+    /// Extract a request from its components, as `extract` finds them on a
+    /// request object
+    fn from_parts(
+        pool: &ConnectionPool,
+        method: &PyAny,
+        url: &PyAny,
+        headers: &PyAny,
+        py_extensions: &PyAny,
+        py_body: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let method = extract_method(method)?;
+        let mut url = extract_url(url)?;
+        if let Some(hsts) = pool.hsts() {
+            hsts.enforce(&mut url)?;
+        }
+        let mut headers = merge_headers(&pool.config().default_headers, &extract_headers(headers)?);
+        let extensions = extract_extensions(py_extensions)?;
+        set_priority_header(extensions.priority, &mut headers);
+        if let Some(header) = &pool.config().idempotency_key {
+            inject_idempotency_key(header, &method, &mut headers);
+        }
+        let extension_body = body_from_extensions(py_extensions, &mut headers)?;
+        let compression = request_compression(pool.config().compress_request, extensions.compress_request, &headers);
+        let compress_min_size = pool.config().compress_request_min_size;
+
+        // A `json_body` or `form_body` extension replaces the request's content
+        let py_body = py_body.filter(|_| extension_body.is_none());
+        let multipart = py_body.and_then(|b| b.extract::<MultipartBody>().ok());
+        let body = match (extension_body, py_body) {
+            (Some(extension_body), _) => reqwest::Body::from(extension_body),
+            (None, Some(py_body)) if multipart.is_none() => match py_body.extract::<PyRef<AsyncBodyWriter>>() {
+                // Sent chunked, as Python writes it
+                Ok(writer) => writer.finish()?,
+                Err(_) => extract_body_from_python(py_body)?,
+            },
+            _ => reqwest::Body::from(""),
+        };
+        // Streamed bodies aren't read ahead to sniff them
+        if let Some(bytes) = body.as_bytes().filter(|_| multipart.is_none() && extensions.sniff_content_type) {
+            set_sniffed_content_type(&mut headers, bytes);
+        }
+
+        Ok(Self { method, url, headers, body, multipart, extensions, compression, compress_min_size })
+    }
+
+    /// The pool partition for this request, honouring its `follow_redirects`
+    /// and `http_version` extensions
+    fn partition(&self, pool: &ConnectionPool) -> PartitionKey {
+        let follow_redirects = self.extensions.follow_redirects.unwrap_or(pool.config().follow_redirects);
+        pool.partition_key(&self.url, follow_redirects, self.extensions.http_version)
+    }
+
+    /// Build the request on `client`, opening multipart file parts and
+    /// compressing the body if `compress_request` applies
+    async fn into_builder(self, client: &ClientWithMiddleware) -> PyResult<(RequestBuilder, Option<CompressionStats>)> {
+        let mut headers = self.headers;
+        let mut stats = None;
+        let req_builder = client.request(self.method, self.url);
+
+        // Multipart sets its own Content-Type (with boundary) and Content-Length;
+        // file parts stream from disk, so the retry middleware sends it only once.
+        // A compressed form streams through the encoder and is sent chunked
+        let mut req_builder = match (self.multipart, self.compression) {
+            (Some(multipart), None) => req_builder.headers(headers).multipart(multipart.to_form().await?),
+            (Some(multipart), Some(compression)) => {
+                let (body, compressed) = compress_form(compression, multipart.to_form().await?, &mut headers)?;
+                stats = Some(compressed);
+                req_builder.headers(headers).body(body)
+            }
+            (None, Some(compression))
+                if self.body.as_bytes().is_some_and(|body| body.len() as u64 >= self.compress_min_size) =>
+            {
+                let body = self.body.as_bytes().unwrap_or_default();
+                let (body, compressed) = compress_body(compression, body, &mut headers)
+                    .map_err(|e| TransportError::LocalProtocolError(format!("request compression failed: {}", e)))?;
+                stats = Some(compressed);
+                req_builder.headers(headers).body(body)
+            }
+            (None, _) => req_builder.headers(headers).body(self.body),
+        };
+
+        // A `timeout` extension replaces the transport's timeout, even when
+        // it sets no overall limit
+        if let Some(timeouts) = self.extensions.timeouts {
+            req_builder = req_builder.timeout(timeouts.total.unwrap_or(NO_TIMEOUT));
+        }
+        Ok((req_builder, stats))
+    }
+
+    /// Send the request on `pool`, first waiting for its concurrency slots
+    /// (for no longer than the `pool` timeout)
+    ///
+    /// Returns the response with its body unread, the extensions to return
+    /// with it, and the slots, which the caller holds until it is done with
+    /// the body.
+    async fn send(
+        self,
+        pool: &ConnectionPool,
+    ) -> PyResult<(reqwest::Response, HashMap<String, serde_json::Value>, Vec<Permit>)> {
+        let partition = self.partition(pool);
+        let method = self.method.clone();
+        let host = self.url.host_str().map(str::to_owned);
+        let client = pool.client(&partition);
+        let mut parsed = self.extensions.clone();
+        let mut extensions = std::mem::take(&mut parsed.values);
+        let hedge_after = parsed.hedge_after.filter(|_| is_idempotent(&self.method));
+        // Sent on a connection of its own, like a wiretapped request
+        let early_data = parsed.early_data
+            && pool.config().early_data
+            && self.url.scheme() == "https"
+            && matches!(self.method, reqwest::Method::GET | reqwest::Method::HEAD);
+        tracing::debug!(
+            method = %self.method,
+            url = %self.url,
+            extensions = %redacted_extensions(&extensions, &pool.config().redact_extensions),
+            "sending request"
+        );
+
+        let (req_builder, compression) = self.into_builder(&client).await?;
+        let timeouts = parsed.timeouts.unwrap_or_default();
+        let (req_builder, watchdog) = match timeouts.write {
+            Some(write_timeout) => watch_request_writes(req_builder, write_timeout)?,
+            None => (req_builder, None),
+        };
+        let req_builder = match parsed.capture_wire {
+            Some(capture) => req_builder.with_extension(capture),
+            None => req_builder,
+        };
+        // Each attempt may take this long to connect, escalating across retries
+        let req_builder = match timeouts.connect {
+            Some(base) => {
+                req_builder.with_extension(ConnectTimeout { base, deadline: timeouts.total.unwrap_or(NO_TIMEOUT) })
+            }
+            None => req_builder,
+        };
+        let req_builder = match timeouts.read {
+            Some(read) => req_builder.with_extension(ResponseTimeout(read)),
+            None => req_builder,
+        };
+        let urgency = parsed.priority.map_or(Priority::DEFAULT_URGENCY, |priority| priority.urgency);
+        let acquire = pool.acquire(host.as_deref(), urgency);
+        let permits = match timeouts.pool {
+            Some(pool_timeout) => tokio::time::timeout(pool_timeout, acquire)
+                .await
+                .map_err(|_| TransportError::PoolTimeout(format!("no request slot free within {:?}", pool_timeout)))?,
+            None => acquire.await,
+        };
+
+        let ((response, redirects), attempts) = if parsed.wiretap || early_data {
+            // On a connection of its own, past the middleware (see `tap.rs`)
+            let request = req_builder.build().map_err(TransportError::from)?;
+            let mut response =
+                send_tapped(pool.config(), pool.resolver(), pool.probe_sessions(), request, early_data).await?;
+            if !parsed.wiretap {
+                response.extensions_mut().remove::<WireTap>();
+            }
+            ((Ok(response), 0), Vec::new())
+        } else {
+            // Execute the request, failing it if writing a streamed body stalls
+            let send = record_retries(count_redirects(async {
+                match hedge_after {
+                    Some(hedge_after) => {
+                        let (result, winner) = send_hedged(req_builder, hedge_after).await;
+                        extensions.insert("hedge_winner".to_string(), serde_json::Value::from(winner.as_str()));
+                        result
+                    }
+                    None => req_builder.send().await,
+                }
+            }));
+            match watchdog {
+                Some(watchdog) => tokio::select! {
+                    sent = send => sent,
+                    stalled = watchdog.stalled() => return Err(stalled.into()),
+                },
+                None => send.await,
+            }
+        };
+        let retry_history = retry_history_json(&attempts);
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => return Err(with_retry_history(TransportError::from(e).into(), retry_history)),
+        };
+        extensions.insert("redirect_count".to_string(), serde_json::Value::from(redirects));
+        note_unfollowed_redirect(&method, partition.follow_redirects, &response);
+        if let Some(retry_history) = retry_history {
+            extensions.insert("retry_history".to_string(), retry_history);
+        }
+        if response.extensions().get::<EarlyResponse>().is_some() {
+            extensions.insert("early_response".to_string(), serde_json::Value::Bool(true));
+        }
+        if parsed.early_data {
+            let sent_early = response.extensions().get::<SentEarly>().is_some();
+            extensions.insert("timings".to_string(), serde_json::json!({ "early_data": sent_early }));
+        }
+        let content_length_present = response.headers().contains_key(reqwest::header::CONTENT_LENGTH);
+        extensions.insert("content_length_present".to_string(), serde_json::Value::Bool(content_length_present));
+        let detail = error_detail(response.status(), response.headers()).filter(|_| pool.config().parse_error_headers);
+        if let Some(detail) = detail {
+            extensions.insert("error_detail".to_string(), detail);
+        }
+        pool.tracker().record(&partition, &response);
+        let capture_certificate = parsed.capture_certificate.unwrap_or(pool.config().capture_certificate);
+        if let Some((handshake, first)) = pool.handshakes().of(&response) {
+            if first {
+                extensions.insert("tls_handshake".to_string(), handshake.to_json());
+            }
+            if capture_certificate {
+                extensions.insert("cipher_suite".to_string(), serde_json::Value::from(handshake.cipher_suite));
+            }
+        }
+        if let Some(hsts) = pool.hsts() {
+            hsts.record(response.url(), response.headers());
+        }
+        check_http_version(pool.config().require_http_version, response.version())?;
+        let response = check_expected_status(parsed.expected_status.as_deref(), response).await?;
+        if let Some(compression) = compression {
+            extensions.insert("request_compression".to_string(), compression.to_json());
+        }
+        Ok((response, extensions, permits))
+    }
+}
 
 /// Async transport for httpx using Rust reqwest + tower
 #[pyclass]
 pub struct AsyncTransport {
-    client: Arc<ClientWithMiddleware>,
+    pool: Arc<ConnectionPool>,
+    /// Whether `pool` may be shared with other transports, and so outlives
+    /// `aclose`
+    shared: bool,
+    #[cfg(feature = "health-server")]
+    health_server: Arc<std::sync::Mutex<Option<HealthServer>>>,
 }
 
 #[pymethods]
 impl AsyncTransport {
+    /// Create a transport; keyword arguments configure its pool
+    ///
+    /// Each transport owns its pool, closed by `aclose`. With `shared=True`,
+    /// transports created with the same configuration (including none)
+    /// share one pool instead, which lives as long as any of them.
     #[new]
-    fn new() -> Self {
+    #[pyo3(signature = (*, shared=false, **kwargs))]
+    fn new(shared: bool, kwargs: Option<&PyDict>) -> PyResult<Self> {
         // Initialize tracing on first use
         crate::client::init_tracing();
-        
-        Self {
-            client: get_client(),
-        }
+
+        let config = extract_client_config(kwargs)?;
+        let pool = if shared { get_pool(config) } else { Arc::new(ConnectionPool::new(config)) };
+
+        Ok(Self {
+            pool,
+            shared,
+            #[cfg(feature = "health-server")]
+            health_server: Default::default(),
+        })
     }
-    
+
     /// Handle an async HTTP request
-    fn handle_async_request<'py>(
+    ///
+    /// The returned awaitable wraps a tokio future. If the awaiting task is
+    /// cancelled (e.g. by `asyncio.timeout` or `asyncio.wait_for`), the
+    /// tokio future is dropped: the in-flight request is aborted, its
+    /// connection is released, and `CancelledError` propagates to the caller
+    /// unchanged rather than being converted into a transport error.
+    ///
+    /// With a `hedge_after` extension (seconds) on an idempotent request, a
+    /// duplicate is sent if no response has arrived by then. The first
+    /// response wins, the other attempt is aborted, and
+    /// `extensions["hedge_winner"]` is `"primary"` or `"hedge"`.
+    ///
+    /// With an `expected_status` extension (a list of status codes), any
+    /// other status raises `UnexpectedStatusError` with the status and the
+    /// start of the body as its `status` and `body` attributes.
+    ///
+    /// With an `auth` option, the request carries a bearer token from
+    /// `get_token`, cached until close to expiry. A response with a
+    /// `refresh_on` status (401 by default) refreshes the token once and
+    /// resends a replayable request; concurrent requests share one refresh.
+    ///
+    /// With `max_concurrent_requests`, the request first waits for a slot,
+    /// taken in `pool_fairness` order and held until the response body has
+    /// been read (or, for a streamed response, until it is returned). With
+    /// `max_concurrent_per_host` it waits for one of its host's slots, the
+    /// same way, before that. Waiting longer than the `pool` timeout raises
+    /// `TimeoutError`.
+    ///
+    /// The request may also be passed by keyword, `request=`, as httpcore
+    /// sometimes does.
+    #[pyo3(signature = (request))]
+    fn handle_async_request<'py>(&self, py: Python<'py>, request: &PyAny) -> PyResult<&'py PyAny> {
+        // Extract request components while holding GIL
+        let request = AsyncRequest::extract(&self.pool, request)?;
+        self.respond(py, request)
+    }
+
+    /// Send a request built from its parts, without an `httpcore.Request`
+    ///
+    /// `content` is bytes, a string, an iterable of either or a
+    /// `MultipartBody`; `headers` a dict or a list of pairs. The request is
+    /// handled exactly as `handle_async_request` handles one, extensions
+    /// included, and resolves to the same kind of response.
+    #[pyo3(signature = (method, url, content=None, headers=None, extensions=None))]
+    fn request<'py>(
+        &self,
+        py: Python<'py>,
+        method: &PyAny,
+        url: &PyAny,
+        content: Option<&PyAny>,
+        headers: Option<&PyAny>,
+        extensions: Option<&PyAny>,
+    ) -> PyResult<&'py PyAny> {
+        let none = py.None().into_ref(py);
+        let request = AsyncRequest::from_parts(
+            &self.pool,
+            method,
+            url,
+            headers.unwrap_or(none),
+            extensions.unwrap_or(none),
+            content,
+        )?;
+        self.respond(py, request)
+    }
+
+    /// Send a request built from its parts, as `request` does, and resolve
+    /// to its JSON body parsed into Python objects
+    ///
+    /// Raises `RemoteProtocolError` unless the response's `Content-Type` is
+    /// `application/json` (or a `+json` type), so an HTML error page never
+    /// reaches the parser, and `DecodingError` if the body isn't valid JSON.
+    /// A compressed body is decoded first.
+    #[pyo3(signature = (method, url, content=None, headers=None, extensions=None))]
+    fn fetch_json<'py>(
+        &self,
+        py: Python<'py>,
+        method: &PyAny,
+        url: &PyAny,
+        content: Option<&PyAny>,
+        headers: Option<&PyAny>,
+        extensions: Option<&PyAny>,
+    ) -> PyResult<&'py PyAny> {
+        let none = py.None().into_ref(py);
+        let request = AsyncRequest::from_parts(
+            &self.pool,
+            method,
+            url,
+            headers.unwrap_or(none),
+            extensions.unwrap_or(none),
+            content,
+        )?;
+        let pool = self.pool.clone();
+        let body_limit = self.pool.config().body_limit();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let (response, _, _permits) = request.send(&pool).await?;
+            check_json_content_type(response.headers())?;
+            if let Some(limit) = body_limit {
+                limit.check_content_length(response.content_length())?;
+            }
+            let (body, _, _) = read_body_with_trailers(response, body_limit).await?;
+            Python::with_gil(|py| from_json_bytes(py, &body))
+        })
+    }
+
+    /// Send a request and write its response body to `path`, resolving to
+    /// the number of bytes written
+    ///
+    /// The body is copied from the connection to the file on the tokio
+    /// runtime, never entering Python. `progress`, if given, is called with
+    /// `(bytes_written, total)` after each chunk, where `total` is the
+    /// `Content-Length` or `None`; an exception it raises aborts the
+    /// download. The file is created once the response headers have
+    /// arrived, and receives the body whatever the status.
+    #[pyo3(signature = (request, path, progress=None))]
+    fn stream_to_file<'py>(
         &self,
         py: Python<'py>,
         request: &PyAny,
+        path: PathBuf,
+        progress: Option<PyObject>,
     ) -> PyResult<&'py PyAny> {
-        let client = self.client.clone();
-        
-        // Extract request components while holding GIL
-        let method = extract_method(request.getattr("method")?)?;
-        let url = extract_url(request.getattr("url")?)?;
-        let headers = extract_headers(request.getattr("headers")?)?;
-        let extensions = extract_extensions(request.getattr("extensions")?)?;
-        
-        // Extract body
-        let body = if let Ok(py_body) = request.getattr("content") {
-            extract_body_from_python(py_body)?
-        } else {
-            reqwest::Body::from("")
-        };
-        
-        // Check configuration from extensions
-        let timeout = extract_timeout_from_extensions(&extensions);
-        let streaming = is_streaming_requested(&extensions);
-        
+        let request = AsyncRequest::extract(&self.pool, request)?;
+        let pool = self.pool.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let (mut response, _, _permits) = request.send(&pool).await?;
+            let total = response.content_length();
+            let mut file = tokio::io::BufWriter::with_capacity(64 * 1024, tokio::fs::File::create(&path).await?);
+            let mut written = 0;
+            while let Some(chunk) = response.chunk().await.map_err(TransportError::from)? {
+                file.write_all(&chunk).await?;
+                written += chunk.len() as u64;
+                if let Some(progress) = &progress {
+                    Python::with_gil(|py| progress.call1(py, (written, total)))?;
+                }
+            }
+            file.flush().await?;
+            Ok(written)
+        })
+    }
+
+    /// Read a streamed response body whole (async), resolving to
+    /// `(body, checksum)`
+    ///
+    /// Bundles what callers otherwise do chunk by chunk in Python: fails
+    /// with `RemoteProtocolError` once the body grows past `max_bytes`,
+    /// hashes it with `hash` (`"sha256"`, `"sha384"`, `"sha512"` or
+    /// `"crc32"`) into a hex checksum, or `None` without one, and, with
+    /// `decompress`, gunzips a body served as a gzip file rather than with
+    /// a `Content-Encoding` the transport decodes itself. See
+    /// `ResponseBodyAccumulator`.
+    #[pyo3(signature = (stream, max_bytes, hash=None, decompress=false))]
+    fn accumulate<'py>(
+        &self,
+        py: Python<'py>,
+        stream: Py<ByteStream>,
+        max_bytes: usize,
+        hash: Option<String>,
+        decompress: bool,
+    ) -> PyResult<&'py PyAny> {
+        ResponseBodyAccumulator::new(max_bytes, hash, decompress)?.accumulate(py, stream)
+    }
+
+    /// Serialize `obj` to JSON bytes the way the `json_body` extension does
+    ///
+    /// `big_ints` (`"error"` or `"string"`) handles integers outside 64
+    /// bits, `nan` (`"error"` or `"null"`) handles non-finite floats, and
+    /// `default` is called with objects JSON has no type for.
+    #[staticmethod]
+    #[pyo3(signature = (obj, big_ints="error", nan="error", default=None))]
+    fn json_dumps<'py>(
+        py: Python<'py>,
+        obj: &PyAny,
+        big_ints: &str,
+        nan: &str,
+        default: Option<PyObject>,
+    ) -> PyResult<&'py PyBytes> {
+        let body = to_json_bytes(obj, &JsonOptions::new(big_ints, nan, default)?)?;
+        Ok(PyBytes::new(py, &body))
+    }
+
+    /// Parse JSON `data` into Python objects the way the `parse_json`
+    /// extension does, raising `DecodingError` if it isn't valid JSON
+    #[staticmethod]
+    fn json_loads(py: Python, data: &[u8]) -> PyResult<PyObject> {
+        from_json_bytes(py, data)
+    }
+
+    /// Send a list of requests concurrently, returning buffered responses in order
+    ///
+    /// Same-origin requests wait for the first one's connection and are
+    /// then multiplexed onto it over HTTP/2. Each response's
+    /// `extensions["shared_connection"]` tells whether another response in
+    /// the batch arrived on the same connection. Any failure fails the batch,
+    /// including a status outside a request's `expected_status`.
+    fn send_batch<'py>(&self, py: Python<'py>, requests: Vec<&PyAny>) -> PyResult<&'py PyAny> {
+        let requests = requests
+            .into_iter()
+            .map(|request| AsyncRequest::extract(&self.pool, request))
+            .collect::<PyResult<Vec<_>>>()?;
+        let pool = self.pool.clone();
+        let required_version = self.pool.config().require_http_version;
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let mut builders = Vec::with_capacity(requests.len());
+            let mut extensions = Vec::with_capacity(requests.len());
+            for request in requests {
+                let partition = request.partition(&pool);
+                let client = pool.client(&partition);
+                let mut request_extensions = request.extensions.clone();
+                let (req_builder, compression) = request.into_builder(&client).await?;
+                if let Some(compression) = compression {
+                    request_extensions.values.insert("request_compression".to_string(), compression.to_json());
+                }
+                extensions.push(request_extensions);
+                builders.push((partition, req_builder));
+            }
+
+            let responses = send_batch(&pool, builders).await?;
+            for (response, extensions) in responses.iter().zip(&extensions) {
+                check_http_version(required_version, response.version)?;
+                if extensions.expected_status.as_ref().is_some_and(|expected| !expected.contains(&response.status)) {
+                    return Err(unexpected_status(response.status, &response.body).into());
+                }
+            }
+
+            Python::with_gil(|py| {
+                let py_responses = PyList::empty(py);
+                for (response, ParsedExtensions { values: mut extensions, parse_json, .. }) in
+                    responses.into_iter().zip(extensions)
+                {
+                    extensions
+                        .insert("shared_connection".to_string(), serde_json::Value::Bool(response.shared_connection));
+                    extensions.insert("redirect_count".to_string(), serde_json::Value::from(response.redirects));
+                    if pool.config().body_limit().is_some_and(|limit| limit.truncate) {
+                        extensions.insert("truncated".to_string(), serde_json::Value::Bool(response.truncated));
+                    }
+                    let py_response = create_response_object(
+                        py,
+                        response.status,
+                        response.version,
+                        response.headers,
+                        Some(PyBytes::new(py, &response.body).into()),
+                        None,
+                        Some(extensions),
+                    )?;
+                    if parse_json {
+                        attach_json(py, &py_response, &response.body)?;
+                    }
+                    py_responses.append(py_response)?;
+                }
+                Ok::<PyObject, PyErr>(py_responses.into())
+            })
+        })
+    }
+
+    /// Snapshot of the connections this transport has observed
+    ///
+    /// Returns one dict per connection with its origin, protocol,
+    /// local/remote addresses, age and idle time in seconds, the number of
+    /// requests served, and the partition it is pooled under: its origin,
+    /// whether it follows redirects, the HTTP version it speaks, its proxy
+    /// and whether it verifies certificates.
+    fn pool(&self, py: Python) -> PyResult<PyObject> {
+        let connections = PyList::empty(py);
+        let now = std::time::Instant::now();
+        for record in self.pool.tracker().snapshot() {
+            let partition = PyDict::new(py);
+            partition.set_item("origin", &record.partition.origin)?;
+            partition.set_item("follow_redirects", record.partition.follow_redirects)?;
+            let http_version = record.partition.http_version.map(|version| match version {
+                http::Version::HTTP_11 => "HTTP/1.1",
+                _ => "HTTP/2",
+            });
+            partition.set_item("http_version", http_version)?;
+            partition.set_item("proxy", &record.partition.proxy)?;
+            partition.set_item("verify", record.partition.verify)?;
+            let connection = PyDict::new(py);
+            connection.set_item("origin", &record.partition.origin)?;
+            connection.set_item("partition", partition)?;
+            connection.set_item("protocol", format!("{:?}", record.protocol))?;
+            connection.set_item("local_addr", record.local_addr.to_string())?;
+            connection.set_item("remote_addr", record.remote_addr.to_string())?;
+            connection.set_item("age", now.duration_since(record.established).as_secs_f64())?;
+            connection.set_item("idle", now.duration_since(record.last_used).as_secs_f64())?;
+            connection.set_item("requests", record.requests)?;
+            connections.append(connection)?;
+        }
+        Ok(connections.into())
+    }
+
+    /// Drop all pooled connections to `origin` (e.g. `"https://api.example.com"`)
+    ///
+    /// Returns the number of tracked connections that were dropped.
+    fn evict(&self, origin: &str) -> PyResult<usize> {
+        let url = reqwest::Url::parse(origin)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid URL: {}", e)))?;
+        Ok(self.pool.evict(&origin_of(&url)))
+    }
+
+    /// Preflight `url`'s origin: DNS, TCP, TLS and ALPN, without a request
+    ///
+    /// Returns a dict with the resolved addresses, the address connected
+    /// to, the TLS version, cipher suite and ALPN protocol (None for plain
+    /// HTTP), and the elapsed time in seconds. Failures raise the same
+    /// errors as requests. With `keep=True`, a `HEAD` request to the origin
+    /// root then leaves a warm connection in the pool.
+    #[pyo3(signature = (url, keep=false))]
+    fn check_origin<'py>(&self, py: Python<'py>, url: &PyAny, keep: bool) -> PyResult<&'py PyAny> {
+        let url = extract_url(url)?;
+        let pool = self.pool.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let check = check_origin(pool.config(), pool.resolver(), pool.probe_sessions(), &url).await?;
+            if keep {
+                let partition = pool.partition_key(&url, pool.config().follow_redirects, None);
+                let response =
+                    pool.client(&partition).head(origin_root(&url)).send().await.map_err(TransportError::from)?;
+                pool.tracker().record(&partition, &response);
+            }
+            Python::with_gil(|py| check.to_dict(py))
+        })
+    }
+
+    /// Inspect the TLS certificate chain of `url`'s origin (async) without
+    /// sending a request
+    ///
+    /// Completes a handshake as `check_origin` does, verified as the
+    /// transport's requests are, and returns a dict with the negotiated
+    /// `tls_version`, `cipher_suite` and `alpn` protocol and the
+    /// `certificates` of the chain, leaf first, each with its `subject`,
+    /// `issuer`, `not_before` and `not_after` (RFC 3339), `subject_alt_names`
+    /// and `sha256_fingerprint`. `url` must be an `https` URL.
+    fn get_ssl_info<'py>(&self, py: Python<'py>, url: &PyAny) -> PyResult<&'py PyAny> {
+        let url = extract_url(url)?;
+        let pool = self.pool.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let check = ssl_info(pool.config(), pool.resolver(), pool.probe_sessions(), &url).await?;
+            Python::with_gil(|py| check.ssl_info_dict(py))
+        })
+    }
+
+    /// Resolve `host` through the transport's resolver
+    ///
+    /// Uses the same path as connections: `dns_overrides`, then the TTL
+    /// cache, then the configured `nameservers` (or the system resolver).
+    /// Returns a dict with the addresses (each with its TTL in seconds and
+    /// record type), the record types in the answer, and its source.
+    fn resolve<'py>(&self, py: Python<'py>, host: String) -> PyResult<&'py PyAny> {
+        let resolver = self.pool.resolver().clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let resolution =
+                resolver.resolve(&host).await.map_err(|e| TransportError::ConnectError(format!("dns error: {}", e)))?;
+            Python::with_gil(|py| resolution.to_dict(py))
+        })
+    }
+
+    /// Resolve `hosts` concurrently, so first requests skip the lookup
+    ///
+    /// Answers from `nameservers` stay cached for their TTL (and, with
+    /// `dns_serve_stale`, are kept as a fallback after it). Returns a dict
+    /// of host to its `resolve` result; fails if any host can't be resolved.
+    fn warm_dns<'py>(&self, py: Python<'py>, hosts: Vec<String>) -> PyResult<&'py PyAny> {
+        let resolver = self.pool.resolver().clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            let resolutions =
+                resolver.warm(&hosts).await.map_err(|e| TransportError::ConnectError(format!("dns error: {}", e)))?;
+            Python::with_gil(|py| resolutions_to_dict(py, &hosts, &resolutions))
+        })
+    }
+
+    /// Resolver cache counters: hits, misses, overrides, stale answers,
+    /// live entries and hit rate
+    fn resolve_stats(&self, py: Python) -> PyResult<PyObject> {
+        self.pool.resolver().stats().to_dict(py)
+    }
+
+    /// TLS handshake counters: handshakes, full, resumed and resumption rate
+    ///
+    /// Counts the handshakes of every connection the pool made with the
+    /// rustls backend, including those made after `evict`.
+    fn tls_stats(&self, py: Python) -> PyResult<PyObject> {
+        self.pool.handshakes().stats().to_dict(py)
+    }
+
+    /// Forget every cached DNS answer, including cached NXDOMAINs, so the
+    /// next lookup of each host queries the nameservers again
+    ///
+    /// Returns how many hosts were cached. The counters in `resolve_stats`
+    /// are kept.
+    fn clear_dns_cache(&self) -> usize {
+        self.pool.resolver().clear()
+    }
+
+    /// Hosts known to require HTTPS, with the `hsts` option
+    ///
+    /// Returns a dict of host to its policy: `include_subdomains`, and
+    /// `expires_in`, the seconds until it lapses. Empty without `hsts`.
+    fn hsts_hosts(&self, py: Python) -> PyResult<PyObject> {
+        match self.pool.hsts() {
+            Some(hsts) => hsts.to_dict(py),
+            None => Ok(PyDict::new(py).into()),
+        }
+    }
+
+    /// The settings the transport is using, defaults included, as a dict
+    /// keyed by option name
+    ///
+    /// For checking that options were applied as intended. Durations are in
+    /// seconds; proxy credentials and credential headers are redacted, and
+    /// `auth` and `sigv4` credentials are left out.
+    fn config(&self, py: Python) -> PyObject {
+        json_to_python(py, &self.pool.config().to_json())
+    }
+
+    /// Start a liveness probe server on `127.0.0.1:{port}`
+    ///
+    /// `GET /health` returns `{"status": "ok", "version": ...}` with pool
+    /// stats. Resolves to the bound port, so `port=0` picks a free one.
+    #[cfg(feature = "health-server")]
+    fn start_health_server<'py>(&self, py: Python<'py>, port: u16) -> PyResult<&'py PyAny> {
+        let pool = self.pool.clone();
+        let slot = self.health_server.clone();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            if slot.lock().unwrap().is_some() {
+                return Err(pyo3::exceptions::PyRuntimeError::new_err("Health server is already running"));
+            }
+            let server = HealthServer::start(port, pool).await?;
+            let port = server.addr().port();
+
+            // A concurrent start may have finished first; keep the first server
+            let rejected = {
+                let mut running = slot.lock().unwrap();
+                match *running {
+                    Some(_) => Some(server),
+                    None => {
+                        *running = Some(server);
+                        None
+                    }
+                }
+            };
+            if let Some(server) = rejected {
+                server.stop().await;
+                return Err(pyo3::exceptions::PyRuntimeError::new_err("Health server is already running"));
+            }
+            Ok(port)
+        })
+    }
+
+    /// Stop the health server, if one is running
+    #[cfg(feature = "health-server")]
+    fn stop_health_server<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let server = self.health_server.lock().unwrap().take();
+
+        pyo3_asyncio::tokio::future_into_py(py, async move {
+            if let Some(server) = server {
+                server.stop().await;
+            }
+            Ok(())
+        })
+    }
+
+    fn __aenter__<'py>(slf: PyRef<'py, Self>, py: Python<'py>) -> PyResult<&'py PyAny> {
+        let slf: PyObject = slf.into_py(py);
+        pyo3_asyncio::tokio::future_into_py(py, async move { Ok(slf) })
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __aexit__<'py>(&self, py: Python<'py>, _args: &PyTuple) -> PyResult<&'py PyAny> {
+        self.aclose(py)
+    }
+
+    /// Close the transport, dropping its pool's connections
+    ///
+    /// A shared pool is left alone: it may serve other transports and is
+    /// dropped with the last of them. In-flight requests complete.
+    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
+        if !self.shared {
+            self.pool.close();
+        }
+        pyo3_asyncio::tokio::future_into_py(py, async move { Python::with_gil(|py| Ok(py.None())) })
+    }
+}
+
+impl AsyncTransport {
+    /// Send `request` and build the Python response, buffered or streamed
+    fn respond<'py>(&self, py: Python<'py>, request: AsyncRequest) -> PyResult<&'py PyAny> {
+        let pool = self.pool.clone();
+        let auto_stream_threshold = self.pool.config().auto_stream_threshold;
+        let stream_yield_interval = self.pool.config().stream_yield_interval;
+        let stream_buffer_bytes = self.pool.config().stream_buffer_bytes;
+        let body_limit = self.pool.config().body_limit();
+        let parse_json = request.extensions.parse_json;
+        // A body to parse is read whole
+        let stream_requested = request.extensions.stream.filter(|_| !parse_json);
+        let auto_stream_threshold = auto_stream_threshold.filter(|_| !parse_json);
+        let capture_certificate =
+            request.extensions.capture_certificate.unwrap_or(self.pool.config().capture_certificate);
+        let defer_body = request.extensions.defer_body;
+        let read_timeout = request.extensions.timeouts.and_then(|timeouts| timeouts.read);
+        let lazy_body = request.extensions.lazy_body && !parse_json;
+        let method = request.method.clone();
+
         // Release GIL and perform the request
         pyo3_asyncio::tokio::future_into_py(py, async move {
-            let mut req_builder = client.request(method, url)
-                .headers(headers)
-                .body(body);
-            
-            // Apply timeout if specified
-            if let Some(timeout_duration) = timeout {
-                req_builder = req_builder.timeout(timeout_duration);
-            }
-            
-            // Execute the request
-            let response = req_builder.send().await
-                .map_err(TransportError::from)?;
-            
+            let (response, mut extensions, permits) = request.send(&pool).await?;
+
             // Extract response components
+            let certificate = peer_certificate(&response).filter(|_| capture_certificate);
+            let wire = response.extensions().get::<WireRecord>().cloned();
+            let tap = response.extensions().get::<WireTap>().cloned();
             let status = response.status().as_u16();
+            let http_version = response.version();
             let response_headers = response.headers().clone();
-            let response_extensions = Some(extensions.clone());
-            
-            if streaming {
-                // Create streaming response
-                let stream = ByteStream::from_response(response);
+            let has_body = response_has_body(&method, status);
+            let streamed = should_stream(stream_requested, auto_stream_threshold, response.content_length());
+            if let Some(limit) = body_limit.filter(|_| has_body) {
+                limit.check_content_length(response.content_length())?;
+                if limit.truncate {
+                    extensions.insert("truncated".to_string(), serde_json::Value::Bool(false));
+                }
+            }
+
+            if defer_body {
+                // Leave the body for `extensions["deferred_body"]` to read,
+                // holding the request's slots until then
+                let deferred =
+                    if !has_body { DeferredBody::empty() } else { DeferredBody::new(response, body_limit, permits) };
+                Python::with_gil(|py| {
+                    let py_response = create_response_object(
+                        py,
+                        status,
+                        http_version,
+                        response_headers,
+                        None,
+                        Some(ByteStream::empty().into_py(py)),
+                        Some(extensions),
+                    )?;
+                    let py_extensions = py_response.getattr(py, "extensions")?;
+                    deferred.set_extensions(py_extensions.clone_ref(py));
+                    py_extensions.as_ref(py).set_item("deferred_body", Py::new(py, deferred)?)?;
+                    if let Some(certificate) = &certificate {
+                        attach_peer_certificate(py, &py_response, certificate)?;
+                    }
+                    if let Some(wire) = &wire {
+                        attach_wire(py, &py_response, wire)?;
+                    }
+                    if let Some(tap) = &tap {
+                        attach_wiretap(py, &py_response, tap)?;
+                    }
+                    Ok(py_response)
+                })
+            } else if streamed || lazy_body {
+                // Create streaming response, or one whose body is read
+                // whole once the stream is first read from
+                let stream = if !has_body {
+                    ByteStream::empty()
+                } else if streamed {
+                    ByteStream::from_response(response, body_limit, stream_yield_interval, stream_buffer_bytes)
+                } else {
+                    ByteStream::lazy(response, body_limit, permits)
+                };
+                let response_extensions = Some(extensions);
                 Python::with_gil(|py| {
                     let py_stream = Py::new(py, stream)?;
-                    create_response_object(
+                    let py_response = create_response_object(
                         py,
                         status,
+                        http_version,
                         response_headers,
-                        None,  // No content for streaming
+                        None, // No content for streaming
                         Some(py_stream.to_object(py)),
                         response_extensions,
-                    )
+                    )?;
+                    // The stream flags truncation once it gets there
+                    py_stream.borrow(py).set_extensions(py_response.getattr(py, "extensions")?);
+                    if let Some(certificate) = &certificate {
+                        attach_peer_certificate(py, &py_response, certificate)?;
+                    }
+                    if let Some(wire) = &wire {
+                        attach_wire(py, &py_response, wire)?;
+                    }
+                    if let Some(tap) = &tap {
+                        attach_wiretap(py, &py_response, tap)?;
+                    }
+                    Ok(py_response)
                 })
             } else {
-                // Read full response body
-                let bytes = response.bytes().await
-                    .map_err(TransportError::from)?;
-                
+                // Read full response body and any trailers, unless the
+                // response has none whatever its Content-Length says
+                let (bytes, trailers, truncated) = if !has_body {
+                    (Bytes::new(), None, false)
+                } else {
+                    read_body_within(response, body_limit, read_timeout).await?
+                };
+                if let Some(trailers) = &trailers {
+                    extensions.insert("trailers".to_string(), trailers_to_json(trailers));
+                }
+                if truncated {
+                    extensions.insert("truncated".to_string(), serde_json::Value::Bool(true));
+                }
+                let response_extensions = Some(extensions);
+
                 Python::with_gil(|py| {
                     let py_content = PyBytes::new(py, &bytes);
-                    create_response_object(
+                    let py_response = create_response_object(
                         py,
                         status,
+                        http_version,
                         response_headers,
                         Some(py_content.into()),
-                        None,  // No stream for non-streaming
+                        None, // No stream for non-streaming
                         response_extensions,
-                    )
+                    )?;
+                    if parse_json && has_body {
+                        attach_json(py, &py_response, &bytes)?;
+                    }
+                    if let Some(certificate) = &certificate {
+                        attach_peer_certificate(py, &py_response, certificate)?;
+                    }
+                    if let Some(wire) = &wire {
+                        attach_wire(py, &py_response, wire)?;
+                    }
+                    if let Some(tap) = &tap {
+                        attach_wiretap(py, &py_response, tap)?;
+                    }
+                    Ok(py_response)
                 })
             }
         })
     }
-    
-    /// Close the transport (cleanup)
-    fn aclose<'py>(&self, py: Python<'py>) -> PyResult<&'py PyAny> {
-        // For now, we don't need to do anything as the client is shared
-        // and managed by the singleton pattern
-        pyo3_asyncio::tokio::future_into_py(py, async move {
-            Python::with_gil(|py| {
-                Ok(py.None())
-            })
-        })
-    }
 }
 
-/// Sync transport for httpx using Rust reqwest (blocking)
+/// Sync transport for httpx using Rust reqwest, blocking on the shared runtime
 #[pyclass]
 pub struct SyncTransport {
-    client: reqwest::blocking::Client,
+    /// Built on first use, and again after `close` drops it
+    client: Mutex<Option<ClientWithMiddleware>>,
+    config: ClientConfig,
+    resolver: DnsResolver,
+    hsts: Option<HstsStore>,
+    probe_sessions: ProbeSessions,
+    handshakes: Handshakes,
+    limits: RequestLimits,
 }
 
 #[pymethods]
 impl SyncTransport {
     #[new]
-    fn new() -> PyResult<Self> {
+    #[pyo3(signature = (**kwargs))]
+    fn new(kwargs: Option<&PyDict>) -> PyResult<Self> {
         // Initialize tracing on first use
         crate::client::init_tracing();
-        
-        // Create a blocking client
-        let client = reqwest::blocking::Client::builder()
-            .pool_max_idle_per_host(64)
-            .user_agent(format!("rust-httpx-transport/{}", env!("CARGO_PKG_VERSION")))
-            .timeout(std::time::Duration::from_secs(30))
-            .build()
+
+        // Create the client
+        let config = extract_client_config(kwargs)?;
+        if config.auth.is_some() {
+            return Err(pyo3::exceptions::PyValueError::new_err("auth is only supported by AsyncTransport"));
+        }
+        let resolver = DnsResolver::new(config.dns.clone());
+        let handshakes = Handshakes::new(config.pool_idle_timeout);
+        let client = create_sync_client(&config, resolver.clone(), handshakes.clone())
             .map_err(|e| pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create client: {}", e)))?;
-        
-        Ok(Self { client })
+        let hsts = config.hsts.as_ref().map(|hsts| HstsStore::new(hsts, config.verify));
+        let limits =
+            RequestLimits::new(config.max_concurrent_requests, config.max_concurrent_per_host, config.pool_fairness);
+
+        Ok(Self {
+            client: Mutex::new(Some(client)),
+            config,
+            resolver,
+            hsts,
+            probe_sessions: ProbeSessions::default(),
+            handshakes,
+            limits,
+        })
     }
-    
-    /// Handle a sync HTTP request
-    fn handle_request(
-        &self,
-        py: Python,
-        request: &PyAny,
-    ) -> PyResult<PyObject> {
-        // Extract request components
+
+    /// Handle a sync HTTP request, passed positionally or as `request=`
+    #[pyo3(signature = (request))]
+    fn handle_request(&self, py: Python, request: &PyAny) -> PyResult<PyObject> {
         let method = extract_method(request.getattr("method")?)?;
-        let url = extract_url(request.getattr("url")?)?;
-        let headers = extract_headers(request.getattr("headers")?)?;
-        let extensions = extract_extensions(request.getattr("extensions")?)?;
-        
-        // Extract body - convert to bytes for sync client
-        let body_bytes: Vec<u8> = if let Ok(py_body) = request.getattr("content") {
-            // For sync transport, we need to extract the body as bytes
-            if py_body.is_none() {
-                Vec::new()
-            } else if let Ok(py_bytes) = py_body.downcast::<pyo3::types::PyBytes>() {
-                py_bytes.as_bytes().to_vec()
-            } else if let Ok(py_str) = py_body.extract::<String>() {
-                py_str.into_bytes()
-            } else {
-                return Err(pyo3::exceptions::PyTypeError::new_err(
-                    "Sync transport only supports bytes or string bodies"
-                ));
-            }
-        } else {
-            Vec::new()
-        };
-        
-        // Check configuration from extensions
-        let timeout = extract_timeout_from_extensions(&extensions);
-        let streaming = is_streaming_requested(&extensions);
-        
-        // Build request
-        let mut req_builder = self.client.request(method, url)
-            .headers(headers)
-            .body(body_bytes);
-        
-        // Apply timeout if specified
-        if let Some(timeout_duration) = timeout {
-            req_builder = req_builder.timeout(timeout_duration);
-        }
-        
-        // Execute the request (this will block)
-        let response = req_builder.send()
-            .map_err(TransportError::from)?;
-        
+        // Held until the body has been read, or the stream returned
+        let (response, parsed, _permits) = self.send(py, request)?;
+        let parse_json = parsed.parse_json;
+        // A body to parse is read whole
+        let stream_requested = parsed.stream.filter(|_| !parse_json);
+        let auto_stream_threshold = self.config.auto_stream_threshold.filter(|_| !parse_json);
+        let runtime = pyo3_asyncio::tokio::get_runtime();
+
         // Extract response components
+        let capture_certificate = parsed.capture_certificate.unwrap_or(self.config.capture_certificate);
+        let certificate = peer_certificate(&response).filter(|_| capture_certificate);
+        let mut extensions = parsed.values;
+        // Never sent in early data (see `tap.rs`)
+        if parsed.early_data {
+            extensions.insert("timings".to_string(), serde_json::json!({ "early_data": false }));
+        }
+        if let Some((handshake, first)) = self.handshakes.of(&response) {
+            if first {
+                extensions.insert("tls_handshake".to_string(), handshake.to_json());
+            }
+            if capture_certificate {
+                extensions.insert("cipher_suite".to_string(), serde_json::Value::from(handshake.cipher_suite));
+            }
+        }
+        let content_length_present = response.headers().contains_key(reqwest::header::CONTENT_LENGTH);
+        extensions.insert("content_length_present".to_string(), serde_json::Value::Bool(content_length_present));
+        let detail = error_detail(response.status(), response.headers()).filter(|_| self.config.parse_error_headers);
+        if let Some(detail) = detail {
+            extensions.insert("error_detail".to_string(), detail);
+        }
+        let wire = response.extensions().get::<WireRecord>().cloned();
         let status = response.status().as_u16();
+        let http_version = response.version();
         let response_headers = response.headers().clone();
-        let response_extensions = Some(extensions.clone());
-        
-        if streaming {
-            // Create streaming response
-            let stream = SyncByteStream::from_response(response);
-            let py_stream = Py::new(py, stream)?;
-            
-            create_response_object(
+        let streamed = should_stream(stream_requested, auto_stream_threshold, response.content_length());
+        let has_body = response_has_body(&method, status);
+
+        // Read full response body and any trailers, even when streaming,
+        // since the sync stream is buffered; 204, 304 and HEAD responses
+        // have no body, even when they carry a Content-Length
+        let body_limit = self.config.body_limit().filter(|_| has_body);
+        if let Some(limit) = body_limit {
+            limit.check_content_length(response.content_length())?;
+        }
+        let body = if !has_body {
+            Ok((Bytes::new(), None, false))
+        } else {
+            // A server that stops sending fails the read rather than
+            // blocking the thread until the overall timeout
+            let read_timeout = parsed.timeouts.and_then(|timeouts| timeouts.read);
+            py.allow_threads(|| runtime.block_on(read_body_within(response, body_limit, read_timeout)))
+        };
+        if body_limit.is_some_and(|limit| limit.truncate) {
+            let truncated = body.as_ref().is_ok_and(|(_, _, truncated)| *truncated);
+            extensions.insert("truncated".to_string(), serde_json::Value::Bool(truncated));
+        }
+
+        if streamed {
+            // Create streaming response; a failed read surfaces when the
+            // stream is consumed
+            let chunks = match body {
+                Ok((bytes, trailers, _)) => {
+                    if let Some(trailers) = &trailers {
+                        extensions.insert("trailers".to_string(), trailers_to_json(trailers));
+                    }
+                    vec![Ok(bytes)]
+                }
+                Err(e) => vec![Err(e)],
+            };
+            let response_extensions = Some(extensions);
+            let py_stream = Py::new(py, SyncByteStream::from_chunks(chunks))?;
+
+            let py_response = create_response_object(
                 py,
                 status,
+                http_version,
                 response_headers,
-                None,  // No content for streaming
+                None, // No content for streaming
                 Some(py_stream.to_object(py)),
                 response_extensions,
-            )
+            )?;
+            if let Some(certificate) = &certificate {
+                attach_peer_certificate(py, &py_response, certificate)?;
+            }
+            if let Some(wire) = &wire {
+                attach_wire(py, &py_response, wire)?;
+            }
+            Ok(py_response)
         } else {
-            // Read full response body
-            let bytes = response.bytes()
-                .map_err(TransportError::from)?;
-            
+            let (bytes, trailers, _) = body?;
+            if let Some(trailers) = &trailers {
+                extensions.insert("trailers".to_string(), trailers_to_json(trailers));
+            }
+            let response_extensions = Some(extensions);
+
             let py_content = PyBytes::new(py, &bytes);
-            create_response_object(
+            let py_response = create_response_object(
                 py,
                 status,
+                http_version,
                 response_headers,
                 Some(py_content.into()),
-                None,  // No stream for non-streaming
+                None, // No stream for non-streaming
                 response_extensions,
-            )
+            )?;
+            if parse_json && has_body {
+                attach_json(py, &py_response, &bytes)?;
+            }
+            if let Some(certificate) = &certificate {
+                attach_peer_certificate(py, &py_response, certificate)?;
+            }
+            if let Some(wire) = &wire {
+                attach_wire(py, &py_response, wire)?;
+            }
+            Ok(py_response)
+        }
+    }
+
+    /// Send a request and write its response body to `path`, returning the
+    /// number of bytes written
+    ///
+    /// The body goes from the connection through a 64 KiB buffered writer
+    /// without passing through Python, with the GIL released throughout.
+    /// The file is created (or truncated) once the response headers have
+    /// arrived, and receives the body whatever the status.
+    fn stream_to_file(&self, py: Python, request: &PyAny, path: PathBuf) -> PyResult<u64> {
+        let (mut response, _, _permits) = self.send(py, request)?;
+
+        py.allow_threads(|| {
+            let mut writer = BufWriter::with_capacity(64 * 1024, File::create(&path)?);
+            pyo3_asyncio::tokio::get_runtime().block_on(async {
+                let mut written = 0;
+                while let Some(chunk) = response.chunk().await.map_err(TransportError::from)? {
+                    writer.write_all(&chunk)?;
+                    written += chunk.len() as u64;
+                }
+                writer.flush()?;
+                Ok(written)
+            })
+        })
+    }
+
+    /// Serialize `obj` to JSON bytes; see `AsyncTransport.json_dumps`
+    #[staticmethod]
+    #[pyo3(signature = (obj, big_ints="error", nan="error", default=None))]
+    fn json_dumps<'py>(
+        py: Python<'py>,
+        obj: &PyAny,
+        big_ints: &str,
+        nan: &str,
+        default: Option<PyObject>,
+    ) -> PyResult<&'py PyBytes> {
+        AsyncTransport::json_dumps(py, obj, big_ints, nan, default)
+    }
+
+    /// Parse JSON `data` into Python objects; see `AsyncTransport.json_loads`
+    #[staticmethod]
+    fn json_loads(py: Python, data: &[u8]) -> PyResult<PyObject> {
+        AsyncTransport::json_loads(py, data)
+    }
+
+    /// Preflight `url`'s origin; see `AsyncTransport.check_origin`
+    #[pyo3(signature = (url, keep=false))]
+    fn check_origin(&self, py: Python, url: &PyAny, keep: bool) -> PyResult<PyObject> {
+        let url = extract_url(url)?;
+
+        let check = py.allow_threads(|| {
+            let check = pyo3_asyncio::tokio::get_runtime().block_on(check_origin(
+                &self.config,
+                &self.resolver,
+                &self.probe_sessions,
+                &url,
+            ))?;
+            if keep {
+                let client = self.client()?;
+                pyo3_asyncio::tokio::get_runtime()
+                    .block_on(async { client.head(origin_root(&url)).send().await })
+                    .map_err(TransportError::from)?;
+            }
+            Ok::<_, PyErr>(check)
+        })?;
+        check.to_dict(py)
+    }
+
+    /// Inspect the certificate chain of `url`'s origin; see
+    /// `AsyncTransport.get_ssl_info`
+    fn get_ssl_info(&self, py: Python, url: &PyAny) -> PyResult<PyObject> {
+        let url = extract_url(url)?;
+
+        let check = py.allow_threads(|| {
+            pyo3_asyncio::tokio::get_runtime().block_on(ssl_info(
+                &self.config,
+                &self.resolver,
+                &self.probe_sessions,
+                &url,
+            ))
+        })?;
+        check.ssl_info_dict(py)
+    }
+
+    /// Resolve `host`; see `AsyncTransport.resolve`
+    fn resolve(&self, py: Python, host: &str) -> PyResult<PyObject> {
+        let resolution = py.allow_threads(|| {
+            pyo3_asyncio::tokio::get_runtime()
+                .block_on(self.resolver.resolve(host))
+                .map_err(|e| TransportError::ConnectError(format!("dns error: {}", e)))
+        })?;
+        resolution.to_dict(py)
+    }
+
+    /// Pre-resolve `hosts`; see `AsyncTransport.warm_dns`
+    fn warm_dns(&self, py: Python, hosts: Vec<String>) -> PyResult<PyObject> {
+        let resolutions = py.allow_threads(|| {
+            pyo3_asyncio::tokio::get_runtime()
+                .block_on(self.resolver.warm(&hosts))
+                .map_err(|e| TransportError::ConnectError(format!("dns error: {}", e)))
+        })?;
+        resolutions_to_dict(py, &hosts, &resolutions)
+    }
+
+    /// Warm the connection pool with a `HEAD` request to each of `urls`,
+    /// sent concurrently, at most `concurrency` at a time (all at once by
+    /// default)
+    ///
+    /// Returns a `(url, success, latency_ms)` tuple per URL, in order. Any
+    /// response counts as a success, whatever its status, since it leaves a
+    /// connection in the pool; failures are reported rather than raised.
+    #[pyo3(signature = (urls, concurrency=None))]
+    fn prefetch_connections(&self, py: Python, urls: Vec<String>, concurrency: Option<usize>) -> PyResult<PyObject> {
+        if concurrency == Some(0) {
+            return Err(pyo3::exceptions::PyValueError::new_err("Invalid concurrency: must be at least 1"));
+        }
+        let mut parsed = Vec::with_capacity(urls.len());
+        for url in &urls {
+            let mut url = reqwest::Url::parse(url)
+                .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid URL: {}", e)))?;
+            if let Some(hsts) = &self.hsts {
+                hsts.enforce(&mut url)?;
+            }
+            parsed.push(url);
+        }
+
+        let client = &self.client()?;
+        let concurrency = concurrency.unwrap_or(parsed.len()).max(1);
+        let prefetches = parsed.into_iter().map(|url| async move {
+            let started = std::time::Instant::now();
+            let sent = client.head(url.clone()).send().await;
+            if let Err(e) = &sent {
+                tracing::debug!(url = %url, error = %e, "connection prefetch failed");
+            }
+            (sent.is_ok(), started.elapsed().as_secs_f64() * 1000.0)
+        });
+        let attempts = py.allow_threads(|| {
+            // Buffered, so the results keep the order of `urls`
+            let prefetches = futures::stream::iter(prefetches).buffered(concurrency).collect::<Vec<_>>();
+            pyo3_asyncio::tokio::get_runtime().block_on(prefetches)
+        });
+        let results = PyList::empty(py);
+        for (url, (success, latency_ms)) in urls.into_iter().zip(attempts) {
+            results.append((url, success, latency_ms))?;
+        }
+        Ok(results.into())
+    }
+
+    /// Resolver cache counters; see `AsyncTransport.resolve_stats`
+    fn resolve_stats(&self, py: Python) -> PyResult<PyObject> {
+        self.resolver.stats().to_dict(py)
+    }
+
+    /// TLS handshake counters; see `AsyncTransport.tls_stats`
+    fn tls_stats(&self, py: Python) -> PyResult<PyObject> {
+        self.handshakes.stats().to_dict(py)
+    }
+
+    /// Forget every cached DNS answer; see `AsyncTransport.clear_dns_cache`
+    fn clear_dns_cache(&self) -> usize {
+        self.resolver.clear()
+    }
+
+    /// Known HSTS hosts; see `AsyncTransport.hsts_hosts`
+    fn hsts_hosts(&self, py: Python) -> PyResult<PyObject> {
+        match &self.hsts {
+            Some(hsts) => hsts.to_dict(py),
+            None => Ok(PyDict::new(py).into()),
         }
     }
-    
-    /// Close the transport (cleanup)
+
+    /// The settings the transport is using; see `AsyncTransport.config`
+    fn config(&self, py: Python) -> PyObject {
+        json_to_python(py, &self.config.to_json())
+    }
+
+    /// Close the transport, dropping its idle connections
+    ///
+    /// In-flight requests complete. The next request builds a new client
+    /// and opens fresh connections.
     fn close(&self) -> PyResult<()> {
-        // For now, we don't need to do anything
+        self.client.lock().unwrap().take();
         Ok(())
     }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    #[pyo3(signature = (*_args))]
+    fn __exit__(&self, _args: &PyTuple) -> PyResult<()> {
+        self.close()
+    }
+}
+
+impl SyncTransport {
+    /// The client requests are sent with, built again if `close` dropped it
+    fn client(&self) -> PyResult<ClientWithMiddleware> {
+        let mut client = self.client.lock().unwrap();
+        match &*client {
+            Some(client) => Ok(client.clone()),
+            None => {
+                let built =
+                    create_sync_client(&self.config, self.resolver.clone(), self.handshakes.clone()).map_err(|e| {
+                        pyo3::exceptions::PyRuntimeError::new_err(format!("Failed to create client: {}", e))
+                    })?;
+                Ok(client.insert(built).clone())
+            }
+        }
+    }
+
+    /// Build, sign and send `request`, first waiting for its concurrency
+    /// slots (for no longer than the `pool` timeout)
+    ///
+    /// Returns the response with its body unread, the request's extensions,
+    /// and the slots, which the caller holds until it is done with the body.
+    fn send(&self, py: Python, request: &PyAny) -> PyResult<(reqwest::Response, ParsedExtensions, Vec<Permit>)> {
+        // Extract request components
+        let method = extract_method(request.getattr("method")?)?;
+        let mut url = extract_url(request.getattr("url")?)?;
+        if let Some(hsts) = &self.hsts {
+            hsts.enforce(&mut url)?;
+        }
+        let mut headers = merge_headers(&self.config.default_headers, &extract_headers(request.getattr("headers")?)?);
+        let mut extensions = extract_extensions(request.getattr("extensions")?)?;
+        set_priority_header(extensions.priority, &mut headers);
+        if let Some(header) = &self.config.idempotency_key {
+            inject_idempotency_key(header, &method, &mut headers);
+        }
+        tracing::debug!(
+            method = %method,
+            url = %url,
+            extensions = %redacted_extensions(&extensions.values, &self.config.redact_extensions),
+            "sending request"
+        );
+
+        // Extract body - convert to bytes for sync client; a `json_body` or
+        // `form_body` extension replaces the request's content
+        let extension_body = body_from_extensions(request.getattr("extensions")?, &mut headers)?;
+        let py_body = get_request_body(request).filter(|_| extension_body.is_none());
+        let multipart = py_body.and_then(|body| body.extract::<MultipartBody>().ok());
+        let mut body_bytes: Vec<u8> = match (extension_body, py_body) {
+            (Some(body), _) => body,
+            (None, Some(py_body)) if multipart.is_none() => extract_body_bytes_from_python(py_body)?,
+            _ => Vec::new(),
+        };
+        if extensions.sniff_content_type && multipart.is_none() {
+            set_sniffed_content_type(&mut headers, &body_bytes);
+        }
+        let compression = request_compression(self.config.compress_request, extensions.compress_request, &headers);
+        let runtime = pyo3_asyncio::tokio::get_runtime();
+
+        // Compress without the GIL, so other Python threads keep running
+        if let Some(compression) = compression
+            .filter(|_| multipart.is_none() && body_bytes.len() as u64 >= self.config.compress_request_min_size)
+        {
+            let (compressed, stats) = py
+                .allow_threads(|| compress_body(compression, &body_bytes, &mut headers))
+                .map_err(|e| TransportError::LocalProtocolError(format!("request compression failed: {}", e)))?;
+            body_bytes = compressed;
+            extensions.values.insert("request_compression".to_string(), stats.to_json());
+        }
+
+        // Build request, asking for a compressed response as the async
+        // client's middleware does
+        accept_compressed(&mut headers);
+        let host = url.host_str().map(str::to_owned);
+        let req_builder = self.client()?.request(method.clone(), url);
+        let mut form_compression = None;
+        let mut req_builder = match multipart {
+            // File parts are opened on the runtime and streamed from disk,
+            // as the async transport sends them
+            Some(multipart) => {
+                let form = py.allow_threads(|| runtime.block_on(multipart.to_form()))?;
+                match compression {
+                    Some(compression) => {
+                        let (body, stats) = compress_form(compression, form, &mut headers)?;
+                        form_compression = Some(stats);
+                        req_builder.headers(headers).body(body)
+                    }
+                    None => req_builder.headers(headers).multipart(form),
+                }
+            }
+            None => req_builder.headers(headers).body(body_bytes),
+        };
+
+        // A `timeout` extension replaces the transport's timeout, even when
+        // it sets no overall limit
+        if let Some(timeouts) = extensions.timeouts {
+            req_builder = req_builder.timeout(timeouts.total.unwrap_or(NO_TIMEOUT));
+        }
+
+        // Captured by the client's innermost middleware, as signed
+        if let Some(capture) = extensions.capture_wire {
+            req_builder = req_builder.with_extension(capture);
+        }
+        // Each attempt may take this long to connect, escalating across
+        // retries, then the read timeout for its response
+        let timeouts = extensions.timeouts.unwrap_or_default();
+        if let Some(base) = timeouts.connect {
+            req_builder =
+                req_builder.with_extension(ConnectTimeout { base, deadline: timeouts.total.unwrap_or(NO_TIMEOUT) });
+        }
+        if let Some(read) = timeouts.read {
+            req_builder = req_builder.with_extension(ResponseTimeout(read));
+        }
+
+        // Execute the request with the GIL released, so Python threads
+        // (including an in-process server) keep running while it blocks
+        // (`send()` starts the timeout timer, so it is called on the runtime)
+        let expected_status = extensions.expected_status.clone();
+        let urgency = extensions.priority.map_or(Priority::DEFAULT_URGENCY, |priority| priority.urgency);
+        let (response, redirects, retry_history, permits) = py.allow_threads(|| {
+            runtime.block_on(async {
+                let acquire = self.limits.acquire(host.as_deref(), urgency);
+                let permits = match timeouts.pool {
+                    Some(pool_timeout) => tokio::time::timeout(pool_timeout, acquire).await.map_err(|_| {
+                        TransportError::PoolTimeout(format!("no request slot free within {:?}", pool_timeout))
+                    })?,
+                    None => acquire.await,
+                };
+                let ((response, redirects), attempts) = record_retries(count_redirects(req_builder.send())).await;
+                let retry_history = retry_history_json(&attempts);
+                let response =
+                    response.map_err(|e| with_retry_history(TransportError::from(e).into(), retry_history.clone()))?;
+                if let Some(hsts) = &self.hsts {
+                    hsts.record(response.url(), response.headers());
+                }
+                let response = decompress(response, self.config.max_decompressed_size);
+                check_http_version(self.config.require_http_version, response.version())?;
+                let response = check_expected_status(expected_status.as_deref(), response).await?;
+                Ok::<_, PyErr>((response, redirects, retry_history, permits))
+            })
+        })?;
+        extensions.values.insert("redirect_count".to_string(), serde_json::Value::from(redirects));
+        if let Some(retry_history) = retry_history {
+            extensions.values.insert("retry_history".to_string(), retry_history);
+        }
+        note_unfollowed_redirect(&method, self.config.follow_redirects, &response);
+        if response.extensions().get::<EarlyResponse>().is_some() {
+            extensions.values.insert("early_response".to_string(), serde_json::Value::Bool(true));
+        }
+        // A streamed form's sizes are known once it has been sent
+        if let Some(compression) = form_compression {
+            extensions.values.insert("request_compression".to_string(), compression.to_json());
+        }
+
+        Ok((response, extensions, permits))
+    }
 }
 
 impl Default for AsyncTransport {
     fn default() -> Self {
-        Self::new()
+        Self::new(false, None).expect("Failed to create AsyncTransport")
     }
 }
 
 impl Default for SyncTransport {
     fn default() -> Self {
-        Self::new().expect("Failed to create SyncTransport")
+        Self::new(None).expect("Failed to create SyncTransport")
     }
-} 
\ No newline at end of file
+}
@@ -0,0 +1,220 @@
+//! The `write` timeout for streamed request bodies, such as multipart
+//! uploads of files
+//!
+//! reqwest has no write timeout of its own. hyper pulls the next chunk of a
+//! streamed body once it has room to write it, so a server that stops
+//! reading shows up as a gap between pulls; a gap longer than the timeout
+//! fails the request with a `WriteError`.
+//!
+//! The same pulls tell when a server responds before it has the whole
+//! body, as one rejecting an upload with a `413` does: hyper stops writing
+//! the body, returns the response, and closes the connection rather than
+//! reuse it. With the `detect_early_responses` option, such a response is
+//! flagged with `extensions["early_response"]`. A tracked body can't be
+//! replayed, so reqwest returns a 307 or 308 redirect to it as the response
+//! rather than follow it.
+
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use http::Extensions;
+use http_body_util::BodyDataStream;
+use hyper::body::{Body, Frame, SizeHint};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next, RequestBuilder};
+use tokio::sync::Notify;
+
+use crate::errors::TransportError;
+
+/// Watches a body wrapped by [`watch_writes`] for a stalled write
+#[derive(Debug)]
+pub struct WriteWatchdog {
+    timeout: Duration,
+    progress: Arc<Notify>,
+    finished: Arc<AtomicBool>,
+}
+
+impl WriteWatchdog {
+    /// Resolve once the body has gone `timeout` without a chunk being
+    /// pulled, and never if it is sent in full
+    ///
+    /// The clock starts with the first chunk, so connecting (and waiting
+    /// for a concurrency slot) doesn't count against it.
+    pub async fn stalled(self) -> TransportError {
+        self.progress.notified().await;
+        loop {
+            if self.finished.load(Ordering::Acquire) {
+                return std::future::pending().await;
+            }
+            if tokio::time::timeout(self.timeout, self.progress.notified()).await.is_err() {
+                return TransportError::WriteError(format!(
+                    "request body write stalled for more than {:?}",
+                    self.timeout
+                ));
+            }
+        }
+    }
+}
+
+/// Wrap a streamed `body` so a [`WriteWatchdog`] can tell when writing it
+/// stalls for longer than `timeout`
+pub fn watch_writes(body: reqwest::Body, timeout: Duration) -> (reqwest::Body, WriteWatchdog) {
+    let progress = Arc::new(Notify::new());
+    let finished = Arc::new(AtomicBool::new(false));
+    let pulled = progress.clone();
+    let (ended, end_progress) = (finished.clone(), progress.clone());
+    let chunks =
+        BodyDataStream::new(body).inspect(move |_| pulled.notify_one()).chain(futures::stream::poll_fn(move |_| {
+            ended.store(true, Ordering::Release);
+            end_progress.notify_one();
+            Poll::Ready(None)
+        }));
+    (reqwest::Body::wrap_stream(chunks), WriteWatchdog { timeout, progress, finished })
+}
+
+/// Watch the body of the request `builder` builds, if it is streamed;
+/// buffered bodies are left alone, so they can still be retried
+pub fn watch_request_writes(
+    builder: RequestBuilder,
+    timeout: Duration,
+) -> Result<(RequestBuilder, Option<WriteWatchdog>), TransportError> {
+    let (client, request) = builder.build_split();
+    let mut request = request.map_err(TransportError::from)?;
+    let watchdog = match request.body_mut().take() {
+        Some(body) if body.as_bytes().is_none() => {
+            let (body, watchdog) = watch_writes(body, timeout);
+            *request.body_mut() = Some(body);
+            Some(watchdog)
+        }
+        body => {
+            *request.body_mut() = body;
+            None
+        }
+    };
+    Ok((RequestBuilder::from_parts(client, request), watchdog))
+}
+
+/// How much of a buffered body is handed to hyper at a time, so an early
+/// response can be told apart from one to a body sent in full
+const UPLOAD_CHUNK: usize = 64 * 1024;
+
+/// Marks a response that arrived before its request's body was sent in full
+#[derive(Debug, Clone, Copy)]
+pub struct EarlyResponse;
+
+/// Whether the body wrapped by [`track_upload`] was sent in full
+#[derive(Debug, Clone)]
+pub struct UploadProgress {
+    finished: Arc<AtomicBool>,
+}
+
+impl UploadProgress {
+    /// Mark `response` as an [`EarlyResponse`] if the body wasn't sent in
+    /// full by the time it arrived
+    ///
+    /// The last chunk counts as sent once hyper pulls it, so a response to
+    /// all but its final 64 KiB isn't told apart.
+    pub fn mark(&self, response: &mut Response) {
+        if !self.finished.load(Ordering::Acquire) {
+            response.extensions_mut().insert(EarlyResponse);
+        }
+    }
+}
+
+/// A request body that notes when hyper has pulled the last of it, handing
+/// its data over in chunks of at most [`UPLOAD_CHUNK`]
+struct TrackedBody {
+    inner: reqwest::Body,
+    /// The rest of the last data frame pulled from `inner`
+    pending: Bytes,
+    finished: Arc<AtomicBool>,
+}
+
+impl Body for TrackedBody {
+    type Data = Bytes;
+    type Error = reqwest::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, reqwest::Error>>> {
+        let this = &mut *self;
+        if this.pending.is_empty() {
+            match futures::ready!(Pin::new(&mut this.inner).poll_frame(cx)) {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => this.pending = data,
+                    Err(trailers) => return Poll::Ready(Some(Ok(trailers))),
+                },
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.finished.store(true, Ordering::Release);
+                    return Poll::Ready(None);
+                }
+            }
+        }
+        let chunk = this.pending.split_to(this.pending.len().min(UPLOAD_CHUNK));
+        if this.is_end_stream() {
+            this.finished.store(true, Ordering::Release);
+        }
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.pending.is_empty() && self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        let inner = self.inner.size_hint();
+        let pending = self.pending.len() as u64;
+        let mut hint = SizeHint::new();
+        hint.set_lower(inner.lower() + pending);
+        if let Some(upper) = inner.upper() {
+            hint.set_upper(upper + pending);
+        }
+        hint
+    }
+}
+
+/// Wrap the body of `request` so the returned [`UploadProgress`] can tell
+/// whether it was sent in full; there is nothing to track without a body
+pub fn track_upload(request: &mut Request) -> Option<UploadProgress> {
+    let inner = request.body_mut().take()?;
+    if inner.as_bytes() == Some(&[]) {
+        *request.body_mut() = Some(inner);
+        return None;
+    }
+    let finished = Arc::new(AtomicBool::new(false));
+    let tracked = TrackedBody { inner, pending: Bytes::new(), finished: finished.clone() };
+    *request.body_mut() = Some(reqwest::Body::wrap(tracked));
+    Some(UploadProgress { finished })
+}
+
+/// Flags responses that arrive before their request's body was sent in
+/// full as [`EarlyResponse`]s
+///
+/// Innermost in the stack, so each attempt's body is tracked as it is
+/// sent, and retries still get a buffered body to clone.
+#[derive(Debug, Clone, Copy)]
+pub struct EarlyResponseMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware for EarlyResponseMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let progress = track_upload(&mut req);
+        let mut response = next.run(req, extensions).await?;
+        if let Some(progress) = progress {
+            progress.mark(&mut response);
+        }
+        Ok(response)
+    }
+}
@@ -1,45 +1,122 @@
 use std::collections::HashMap;
 use std::str::FromStr;
+use std::time::Duration;
 
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyTuple};
+use pyo3::types::{PyBool, PyBytes, PyDict, PyList, PyTuple};
 use reqwest::{Method, Url};
 
+use crate::auth::AuthConfig;
+use crate::client::ClientConfig;
+use crate::compress::Compression;
+use crate::config::{
+    check_connect_timeout_escalation, check_dns_ttl_bounds, check_dscp, check_max_concurrent_per_host,
+    check_max_concurrent_requests, check_pool_max_idle_per_host, check_stream_buffer_bytes,
+    check_stream_yield_interval, check_user_agent, parse_client_timeout, parse_compress_request, parse_dns_override,
+    parse_expected_status, parse_http_version_hint, parse_idempotency_key_header, parse_local_address,
+    parse_nameserver, parse_pin, parse_pool_fairness, parse_priority, parse_proxy, parse_required_http_version,
+    parse_staple_host, parse_timeout_extension, read_ca_file, Timeouts, DEFAULT_IDEMPOTENCY_KEY_HEADER,
+};
+use crate::dns::DEFAULT_MAX_STALE;
+use crate::hsts::HstsConfig;
+use crate::limit::Priority;
+use crate::pin::PinConfig;
+use crate::revocation::RevocationConfig;
+use crate::socket::{SocketOptions, SUPPORTED_OPTIONS};
+use crate::wire::WireCapture;
 
+/// Extract a string from either a Python `str` or `bytes` object
+///
+/// httpcore represents methods, header names and header values as bytes,
+/// while hand-built requests usually pass plain strings.
+fn extract_str_or_bytes(value: &PyAny) -> PyResult<String> {
+    if let Ok(py_bytes) = value.downcast::<PyBytes>() {
+        return String::from_utf8(py_bytes.as_bytes().to_vec())
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid UTF-8: {}", e)));
+    }
+    value.extract()
+}
 
 /// Extract HTTP method from Python request
 pub fn extract_method(py_method: &PyAny) -> PyResult<Method> {
-    let method_str: String = py_method.extract()?;
+    let method_str = extract_str_or_bytes(py_method)?;
     Method::from_str(&method_str)
         .map_err(|_| pyo3::exceptions::PyValueError::new_err(format!("Invalid HTTP method: {}", method_str)))
 }
 
 /// Extract URL from Python request
+///
+/// Accepts `str`, `bytes`, `httpcore.URL` (via `bytes(url)`) and
+/// `httpx.URL` (via `str(url)`).
 pub fn extract_url(py_url: &PyAny) -> PyResult<Url> {
-    let url_str: String = py_url.extract()?;
-    Url::parse(&url_str)
-        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid URL: {}", e)))
+    let url_str = if py_url.hasattr("__bytes__")? {
+        extract_str_or_bytes(py_url.call_method0("__bytes__")?)?
+    } else if let Ok(url_str) = extract_str_or_bytes(py_url) {
+        url_str
+    } else {
+        py_url.str()?.extract()?
+    };
+    Url::parse(&url_str).map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid URL: {}", e)))
+}
+
+/// Encode `params`, a dict or a list of `(key, value)` pairs of `str`, as a
+/// URL query string, the way HTML forms encode their fields
+///
+/// Pairs keep their order, so a list can repeat keys; spaces become `+`.
+pub fn build_query_string(params: &PyAny) -> PyResult<String> {
+    let invalid = || {
+        pyo3::exceptions::PyTypeError::new_err(format!(
+            "Invalid query params: expected a dict or a list of (key, value) pairs of str, got {}",
+            params.get_type().name().unwrap_or("?")
+        ))
+    };
+    let pairs: Vec<(&str, &str)> = match params.downcast::<PyDict>() {
+        Ok(dict) => dict
+            .iter()
+            .map(|(key, value)| Ok((key.extract()?, value.extract()?)))
+            .collect::<PyResult<_>>()
+            .map_err(|_| invalid())?,
+        Err(_) if params.is_instance_of::<PyList>() || params.is_instance_of::<PyTuple>() => {
+            params.extract().map_err(|_| invalid())?
+        }
+        Err(_) => return Err(invalid()),
+    };
+    Ok(url::form_urlencoded::Serializer::new(String::new()).extend_pairs(pairs).finish())
+}
+
+/// Encode `params` as a URL query string; see `build_query_string`
+#[pyfunction]
+pub fn encode_query_params(params: &PyAny) -> PyResult<String> {
+    build_query_string(params)
+}
+
+/// Get the body object of a Python request
+///
+/// Hand-built requests expose `content`, while httpcore requests carry the
+/// body as an iterable `stream`.
+pub fn get_request_body(request: &PyAny) -> Option<&PyAny> {
+    request.getattr("content").or_else(|_| request.getattr("stream")).ok()
 }
 
 /// Extract headers from Python request
 pub fn extract_headers(py_headers: &PyAny) -> PyResult<reqwest::header::HeaderMap> {
     let mut headers = reqwest::header::HeaderMap::new();
-    
+
     if py_headers.is_none() {
         return Ok(headers);
     }
-    
+
     // Handle different header formats
     if let Ok(py_dict) = py_headers.downcast::<PyDict>() {
         for (key, value) in py_dict {
-            let key_str: String = key.extract()?;
-            let value_str: String = value.extract()?;
-            
+            let key_str = extract_str_or_bytes(key)?;
+            let value_str = extract_str_or_bytes(value)?;
+
             let header_name = reqwest::header::HeaderName::from_str(&key_str)
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid header name: {}", e)))?;
             let header_value = reqwest::header::HeaderValue::from_str(&value_str)
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid header value: {}", e)))?;
-            
+
             headers.insert(header_name, header_value);
         }
     } else if let Ok(py_list) = py_headers.downcast::<PyList>() {
@@ -49,82 +126,675 @@ pub fn extract_headers(py_headers: &PyAny) -> PyResult<reqwest::header::HeaderMa
             if tuple.len() != 2 {
                 return Err(pyo3::exceptions::PyValueError::new_err("Header tuples must have exactly 2 elements"));
             }
-            
-            let key_str: String = tuple.get_item(0)?.extract()?;
-            let value_str: String = tuple.get_item(1)?.extract()?;
-            
+
+            let key_str = extract_str_or_bytes(tuple.get_item(0)?)?;
+            let value_str = extract_str_or_bytes(tuple.get_item(1)?)?;
+
             let header_name = reqwest::header::HeaderName::from_str(&key_str)
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid header name: {}", e)))?;
             let header_value = reqwest::header::HeaderValue::from_str(&value_str)
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid header value: {}", e)))?;
-            
+
             headers.insert(header_name, header_value);
         }
     }
-    
+
     Ok(headers)
 }
 
+/// Merge two header maps, with `overrides` taking precedence
+///
+/// Every value of a header present in `overrides` replaces all of that
+/// header's values in `base`; headers only in `base` are kept as-is.
+pub fn merge_headers(
+    base: &reqwest::header::HeaderMap,
+    overrides: &reqwest::header::HeaderMap,
+) -> reqwest::header::HeaderMap {
+    let mut merged = base.clone();
+    for name in overrides.keys() {
+        merged.remove(name);
+        for value in overrides.get_all(name) {
+            merged.append(name.clone(), value.clone());
+        }
+    }
+    merged
+}
+
 /// Convert Rust response headers to Python format
 pub fn convert_headers_to_python(headers: &reqwest::header::HeaderMap, py: Python) -> PyResult<PyObject> {
     let py_list = PyList::empty(py);
-    
+
     for (name, value) in headers {
         let name_str = name.as_str();
-        let value_str = value.to_str()
+        let value_str = value
+            .to_str()
             .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid header value: {}", e)))?;
-        
-        let tuple = PyTuple::new(py, &[name_str, value_str]);
+
+        let tuple = PyTuple::new(py, [name_str, value_str]);
         py_list.append(tuple)?;
     }
-    
+
     Ok(py_list.into())
 }
 
+/// Convert response trailers to the `extensions["trailers"]` value, a list
+/// of `[name, value]` pairs in the order they were received
+pub fn trailers_to_json(trailers: &reqwest::header::HeaderMap) -> serde_json::Value {
+    trailers
+        .iter()
+        .map(|(name, value)| serde_json::json!([name.as_str(), String::from_utf8_lossy(value.as_bytes())]))
+        .collect()
+}
+
+/// A request's extensions, with the ones the transports act on parsed once
+/// by `extract_extensions`
+#[derive(Debug, Clone, Default)]
+pub struct ParsedExtensions {
+    /// Every extension as JSON, returned with the response
+    pub values: HashMap<String, serde_json::Value>,
+    /// The `timeout` extension; without one, the transport's `timeout`
+    /// bounds the whole request
+    pub timeouts: Option<Timeouts>,
+    pub stream: Option<bool>,
+    pub follow_redirects: Option<bool>,
+    /// The `http_version` extension: HTTP/1.1 or HTTP/2
+    pub http_version: Option<http::Version>,
+    /// `hedge_after`, if positive
+    pub hedge_after: Option<Duration>,
+    pub expected_status: Option<Vec<u16>>,
+    pub priority: Option<Priority>,
+    /// `compress_request`, if the request set it; `None` opts out of the
+    /// transport's `compress_request`
+    pub compress_request: Option<Option<Compression>>,
+    /// Parse the body into `extensions["json"]`
+    pub parse_json: bool,
+    /// Guess a missing `Content-Type` from the body
+    pub sniff_content_type: bool,
+    /// Leave the body for `extensions["deferred_body"]` to read
+    pub defer_body: bool,
+    /// Read the body once the response's stream is first read from
+    pub lazy_body: bool,
+    /// `capture_certificate`, overriding the transport's option
+    pub capture_certificate: Option<bool>,
+    /// What `capture_wire` and `unsafe_capture` ask to capture
+    pub capture_wire: Option<WireCapture>,
+    /// Send the request on a connection of its own and keep its bytes
+    pub wiretap: bool,
+    /// Send the request in TLS 1.3 early data, if the transport allows it
+    pub early_data: bool,
+}
+
 /// Extract extensions from Python request
-pub fn extract_extensions(py_extensions: &PyAny) -> PyResult<HashMap<String, serde_json::Value>> {
-    let mut extensions = HashMap::new();
-    
+///
+/// Values are converted recursively: dicts become JSON objects (with keys
+/// converted by `str`), lists and tuples become arrays, and values with no
+/// JSON counterpart fall back to their `str`.
+///
+/// `timeout` is the exception: it must be what httpx sends, either seconds
+/// or a `{"connect": f, "read": f, "write": f, "pool": f}` dict whose values
+/// may be `None` or missing. `None`, `0` and a missing key mean no timeout,
+/// not the transport's, and other values (such as negative seconds) raise
+/// `ValueError`. So does an `expected_status` (or
+/// its alias `expect_status`) that isn't a list of status codes, a `compress_request` that isn't a
+/// supported algorithm (with a valid level) or `None`, a `priority`
+/// outside RFC 9218's urgencies, an `http_version` other than
+/// `"HTTP/1.1"` and `"HTTP/2"`, a `capture_wire` that is neither a bool
+/// nor a number of body bytes, or a flag such as `parse_json` or
+/// `early_data` that isn't a bool.
+pub fn extract_extensions(py_extensions: &PyAny) -> PyResult<ParsedExtensions> {
+    let mut extensions = ParsedExtensions::default();
+
     if py_extensions.is_none() {
         return Ok(extensions);
     }
-    
+
+    let mut capture_wire = None;
+    let mut unsafe_capture = false;
     if let Ok(py_dict) = py_extensions.downcast::<PyDict>() {
         for (key, value) in py_dict {
             let key_str: String = key.extract()?;
-            
-            // Convert Python value to JSON value for processing
-            let json_value = if value.is_none() {
-                serde_json::Value::Null
-            } else if let Ok(b) = value.extract::<bool>() {
-                serde_json::Value::Bool(b)
-            } else if let Ok(i) = value.extract::<i64>() {
-                serde_json::Value::Number(serde_json::Number::from(i))
-            } else if let Ok(f) = value.extract::<f64>() {
-                if let Some(num) = serde_json::Number::from_f64(f) {
-                    serde_json::Value::Number(num)
-                } else {
-                    serde_json::Value::Null
-                }
-            } else if let Ok(s) = value.extract::<String>() {
-                serde_json::Value::String(s)
-            } else {
-                // Try to convert to string as fallback
-                let s: String = value.str()?.extract()?;
-                serde_json::Value::String(s)
+            // Serialized into the request body instead (see `json.rs` and `form.rs`)
+            if matches!(key_str.as_str(), "json_body" | "json_options" | "form_body") {
+                continue;
+            }
+            let value = python_to_json(value)?;
+            let flag = || {
+                value.as_bool().ok_or_else(|| {
+                    pyo3::exceptions::PyValueError::new_err(format!(
+                        "Invalid {}: {} (expected True or False)",
+                        key_str, value
+                    ))
+                })
             };
-            
-            extensions.insert(key_str, json_value);
+            match key_str.as_str() {
+                "timeout" => extensions.timeouts = Some(parse_timeout_extension(&value)?),
+                "stream" => extensions.stream = value.as_bool(),
+                "follow_redirects" => extensions.follow_redirects = value.as_bool(),
+                "http_version" => extensions.http_version = Some(parse_http_version_hint(&value)?),
+                "hedge_after" => extensions.hedge_after = positive_seconds(&value),
+                "expected_status" | "expect_status" => {
+                    extensions.expected_status = Some(parse_expected_status(&value)?)
+                }
+                "priority" => extensions.priority = Some(parse_priority(&value)?),
+                "compress_request" => extensions.compress_request = Some(parse_compress_request(&value)?),
+                "parse_json" => extensions.parse_json = flag()?,
+                "sniff_content_type" => extensions.sniff_content_type = flag()?,
+                "defer_body" => extensions.defer_body = flag()?,
+                "lazy_body" => extensions.lazy_body = flag()?,
+                "capture_certificate" => extensions.capture_certificate = Some(flag()?),
+                "unsafe_capture" => unsafe_capture = flag()?,
+                "wiretap" => extensions.wiretap = flag()?,
+                "early_data" => extensions.early_data = flag()?,
+                "capture_wire" => {
+                    capture_wire = match &value {
+                        serde_json::Value::Bool(capture) => capture.then_some(0),
+                        _ => Some(value.as_u64().and_then(|bytes| usize::try_from(bytes).ok()).ok_or_else(|| {
+                            pyo3::exceptions::PyValueError::new_err(format!(
+                                "Invalid capture_wire: {} (expected True, False or a number of body bytes)",
+                                value
+                            ))
+                        })?),
+                    };
+                }
+                _ => {}
+            }
+            extensions.values.insert(key_str, value);
         }
     }
-    
+    extensions.capture_wire = capture_wire.map(|body_bytes| WireCapture { body_bytes, unsafe_capture });
+
     Ok(extensions)
 }
 
+/// A number of seconds as a duration, if positive
+fn positive_seconds(value: &serde_json::Value) -> Option<Duration> {
+    value.as_f64().filter(|seconds| *seconds > 0.0).map(Duration::from_secs_f64)
+}
+
+/// Extension keys whose values request logs leave out unless the
+/// `redact_extensions` option says otherwise
+pub const DEFAULT_REDACTED_EXTENSIONS: &[&str] = &["auth", "proxy"];
+
+/// `extensions` as the request log shows them, with the value of every key
+/// in `redact` (compared case-insensitively, at any depth) replaced by
+/// `"[redacted]"`; the request itself still gets the full values
+pub fn redacted_extensions(extensions: &HashMap<String, serde_json::Value>, redact: &[String]) -> serde_json::Value {
+    fn redact_value(value: &serde_json::Value, redact: &[String]) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(object) => serde_json::Value::Object(
+                object.iter().map(|(key, value)| (key.clone(), redact_entry(key, value, redact))).collect(),
+            ),
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.iter().map(|item| redact_value(item, redact)).collect())
+            }
+            value => value.clone(),
+        }
+    }
+    fn redact_entry(key: &str, value: &serde_json::Value, redact: &[String]) -> serde_json::Value {
+        if redact.iter().any(|redacted| redacted.eq_ignore_ascii_case(key)) {
+            serde_json::Value::from("[redacted]")
+        } else {
+            redact_value(value, redact)
+        }
+    }
+
+    serde_json::Value::Object(
+        extensions.iter().map(|(key, value)| (key.clone(), redact_entry(key, value, redact))).collect(),
+    )
+}
+
+/// Convert one extension value to JSON
+fn python_to_json(value: &PyAny) -> PyResult<serde_json::Value> {
+    let json_value = if value.is_none() {
+        serde_json::Value::Null
+    } else if let Ok(b) = value.extract::<bool>() {
+        serde_json::Value::Bool(b)
+    } else if let Ok(i) = value.extract::<i64>() {
+        serde_json::Value::Number(serde_json::Number::from(i))
+    } else if let Ok(f) = value.extract::<f64>() {
+        serde_json::Number::from_f64(f).map(serde_json::Value::Number).unwrap_or(serde_json::Value::Null)
+    } else if let Ok(s) = value.extract::<String>() {
+        serde_json::Value::String(s)
+    } else if let Ok(dict) = value.downcast::<PyDict>() {
+        let mut object = serde_json::Map::with_capacity(dict.len());
+        for (key, item) in dict {
+            let key = match key.extract::<String>() {
+                Ok(key) => key,
+                Err(_) => key.str()?.extract()?,
+            };
+            object.insert(key, python_to_json(item)?);
+        }
+        serde_json::Value::Object(object)
+    } else if let Ok(list) = value.downcast::<PyList>() {
+        serde_json::Value::Array(list.iter().map(python_to_json).collect::<PyResult<_>>()?)
+    } else if let Ok(tuple) = value.downcast::<PyTuple>() {
+        serde_json::Value::Array(tuple.iter().map(python_to_json).collect::<PyResult<_>>()?)
+    } else {
+        // Try to convert to string as fallback
+        let s: String = value.str()?.extract()?;
+        serde_json::Value::String(s)
+    };
+    Ok(json_value)
+}
+
+/// Build a `ClientConfig` from transport constructor keyword arguments
+///
+/// Omitted keywords keep their defaults; unknown keywords raise `TypeError`
+/// so a typo doesn't silently fall back to the default behaviour.
+pub fn extract_client_config(kwargs: Option<&PyDict>) -> PyResult<ClientConfig> {
+    let mut config = ClientConfig::default();
+    let Some(kwargs) = kwargs else {
+        return Ok(config);
+    };
+    let mut pins_report_only = false;
+    let mut inject_idempotency_key = false;
+    let mut idempotency_key_header = None;
+    let mut crl_files: Vec<std::path::PathBuf> = Vec::new();
+    let mut staple_hosts = Vec::new();
+    let mut default_accept_language = None;
+
+    for (key, value) in kwargs {
+        let key: &str = key.extract()?;
+        match key {
+            "pool_name" => {
+                config.pool_name = value.extract()?;
+            }
+            "timeout" => {
+                config.timeout = parse_client_timeout("timeout", value.extract()?)?;
+            }
+            "pool_max_idle_per_host" => {
+                config.pool_max_idle_per_host = check_pool_max_idle_per_host(value.extract()?)?;
+            }
+            "pool_idle_timeout" => {
+                config.pool_idle_timeout = parse_client_timeout("pool_idle_timeout", value.extract()?)?;
+            }
+            "user_agent" => {
+                config.user_agent = check_user_agent(value.extract()?)?.to_string();
+            }
+            "require_http_version" => {
+                config.require_http_version = if value.is_none() {
+                    None
+                } else {
+                    let version: String = value.extract()?;
+                    Some(parse_required_http_version(&version)?)
+                };
+            }
+            "http2_prior_knowledge" => {
+                config.http2_prior_knowledge = value.extract()?;
+            }
+            "connect_timeout_escalation" => {
+                config.connect_timeout_escalation =
+                    if value.is_none() { None } else { Some(check_connect_timeout_escalation(value.extract()?)?) };
+            }
+            "follow_redirects" => {
+                config.follow_redirects = value.extract()?;
+            }
+            "max_redirects" => {
+                config.max_redirects = value.extract()?;
+            }
+            "auto_stream_threshold" => {
+                config.auto_stream_threshold = value.extract()?;
+            }
+            "stream_yield_interval" => {
+                config.stream_yield_interval = check_stream_yield_interval(value.extract()?)?;
+            }
+            "stream_buffer_bytes" => {
+                config.stream_buffer_bytes =
+                    if value.is_none() { None } else { Some(check_stream_buffer_bytes(value.extract()?)?) };
+            }
+            "default_headers" => {
+                config.default_headers = extract_headers(value)?;
+            }
+            "default_accept_language" => {
+                default_accept_language = if value.is_none() {
+                    None
+                } else {
+                    let language: String = value.extract()?;
+                    Some(reqwest::header::HeaderValue::from_str(&language).map_err(|e| {
+                        pyo3::exceptions::PyValueError::new_err(format!("Invalid default_accept_language: {}", e))
+                    })?)
+                };
+            }
+            "socket_options" => {
+                config.socket_options = extract_socket_options(value.downcast()?)?;
+            }
+            "dns_overrides" => {
+                let overrides: HashMap<String, Vec<String>> = value.extract()?;
+                config.dns.overrides = overrides
+                    .iter()
+                    .map(|(host, addresses)| parse_dns_override(host, addresses))
+                    .collect::<Result<_, _>>()?;
+            }
+            "nameservers" => {
+                let nameservers: Vec<String> = value.extract()?;
+                config.dns.nameservers =
+                    nameservers.iter().map(|nameserver| parse_nameserver(nameserver)).collect::<Result<_, _>>()?;
+            }
+            "max_concurrent_requests" => {
+                config.max_concurrent_requests =
+                    if value.is_none() { None } else { Some(check_max_concurrent_requests(value.extract()?)?) };
+            }
+            "max_concurrent_per_host" => {
+                config.max_concurrent_per_host =
+                    if value.is_none() { None } else { Some(check_max_concurrent_per_host(value.extract()?)?) };
+            }
+            "pool_fairness" => {
+                config.pool_fairness = parse_pool_fairness(value.extract()?)?;
+            }
+            "hsts" => {
+                config.hsts = if value.is_none() {
+                    None
+                } else if let Ok(enabled) = value.downcast::<pyo3::types::PyBool>() {
+                    enabled.is_true().then(HstsConfig::default)
+                } else {
+                    Some(HstsConfig { path: Some(value.extract()?) })
+                };
+            }
+            "pins" => {
+                config.pins = if value.is_none() { None } else { Some(extract_pins(value.downcast()?)?) };
+            }
+            "detect_early_responses" => {
+                config.detect_early_responses = value.extract()?;
+            }
+            "parse_error_headers" => {
+                config.parse_error_headers = value.extract()?;
+            }
+            "capture_certificate" => {
+                config.capture_certificate = value.extract()?;
+            }
+            "early_data" => {
+                config.early_data = value.extract()?;
+            }
+            "redact_extensions" => {
+                config.redact_extensions = value.extract()?;
+            }
+            "pins_report_only" => {
+                pins_report_only = value.extract()?;
+            }
+            "crls" => {
+                crl_files = if value.is_none() { Vec::new() } else { value.extract()? };
+            }
+            "require_ocsp_staple" => {
+                staple_hosts = match value.downcast::<PyBool>() {
+                    Ok(required) => {
+                        if required.is_true() {
+                            vec!["*".to_string()]
+                        } else {
+                            Vec::new()
+                        }
+                    }
+                    Err(_) if value.is_none() => Vec::new(),
+                    Err(_) => {
+                        value.extract::<Vec<&str>>()?.into_iter().map(parse_staple_host).collect::<Result<_, _>>()?
+                    }
+                };
+            }
+            "inject_idempotency_key" => {
+                inject_idempotency_key = value.extract()?;
+            }
+            "idempotency_key_header" => {
+                idempotency_key_header = Some(parse_idempotency_key_header(value.extract()?)?);
+            }
+            "disable_keepalive" => {
+                config.disable_keepalive = value.extract()?;
+            }
+            "compress_request" => {
+                config.compress_request = parse_compress_request(&python_to_json(value)?)?;
+            }
+            "compress_request_min_size" => {
+                config.compress_request_min_size = value.extract()?;
+            }
+            "max_response_body_size" => {
+                config.max_response_body_size = value.extract()?;
+            }
+            "truncate_oversized" => {
+                config.truncate_oversized = value.extract()?;
+            }
+            "max_decompressed_size" => {
+                config.max_decompressed_size = value.extract()?;
+            }
+            "auth" => {
+                config.auth = AuthConfig::from_py(value)?;
+            }
+            #[cfg(feature = "sigv4")]
+            "sigv4" => {
+                config.sigv4 = crate::sigv4::SigV4Config::from_py(value)?;
+            }
+            "dns_serve_stale" => {
+                config.dns.serve_stale = extract_serve_stale(value)?;
+            }
+            "dns_cache_size" => {
+                config.dns.cache_size = value.extract()?;
+            }
+            "dns_min_ttl" => {
+                config.dns.min_ttl = extract_dns_ttl("dns_min_ttl", value)?;
+            }
+            "dns_max_ttl" => {
+                config.dns.max_ttl = extract_dns_ttl("dns_max_ttl", value)?;
+            }
+            "dns_negative_ttl" => {
+                config.dns.negative_ttl = extract_dns_ttl("dns_negative_ttl", value)?;
+            }
+            "ca_file" => {
+                config.ca_bundle = if value.is_none() {
+                    None
+                } else {
+                    let path: std::path::PathBuf = value.extract()?;
+                    Some(read_ca_file(&path)?)
+                };
+            }
+            "proxy" => {
+                config.proxy = if value.is_none() { None } else { Some(parse_proxy(value.extract()?)?) };
+            }
+            "verify" => {
+                config.verify = value.extract()?;
+            }
+            _ => {
+                return Err(pyo3::exceptions::PyTypeError::new_err(format!("Unexpected keyword argument: {}", key)));
+            }
+        }
+    }
+
+    // Whatever order the keywords came in, the dedicated option wins over a
+    // default_headers entry
+    check_dns_ttl_bounds(config.dns.min_ttl, config.dns.max_ttl)?;
+    if let Some(language) = default_accept_language {
+        config.default_headers.insert(reqwest::header::ACCEPT_LANGUAGE, language);
+    }
+    if inject_idempotency_key {
+        config.idempotency_key = Some(
+            idempotency_key_header.unwrap_or_else(|| http::HeaderName::from_static(DEFAULT_IDEMPOTENCY_KEY_HEADER)),
+        );
+    }
+    if let Some(pins) = &mut config.pins {
+        pins.report_only = pins_report_only;
+        if !config.verify {
+            return Err(pyo3::exceptions::PyValueError::new_err("pins can't be used with verify=False"));
+        }
+        #[cfg(feature = "rustls")]
+        let rustls = config.tls_backend == crate::client::TlsBackend::Rustls;
+        #[cfg(not(feature = "rustls"))]
+        let rustls = false;
+        if !rustls {
+            return Err(pyo3::exceptions::PyValueError::new_err("pins require the rustls TLS backend"));
+        }
+    }
+    if !crl_files.is_empty() || !staple_hosts.is_empty() {
+        if !config.verify {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "crls and require_ocsp_staple can't be used with verify=False",
+            ));
+        }
+        config.revocation = Some(revocation_config(&config, &crl_files, staple_hosts)?);
+    }
+    Ok(config)
+}
+
+/// Build `crls` (read from `crl_files`) and `require_ocsp_staple` into a
+/// `RevocationConfig` for `config`, checking the CRLs parse
+#[cfg(feature = "rustls")]
+fn revocation_config(
+    config: &ClientConfig,
+    crl_files: &[std::path::PathBuf],
+    staple_hosts: Vec<String>,
+) -> PyResult<RevocationConfig> {
+    if config.tls_backend != crate::client::TlsBackend::Rustls {
+        return Err(pyo3::exceptions::PyValueError::new_err(
+            "crls and require_ocsp_staple require the rustls TLS backend",
+        ));
+    }
+    let mut crls = Vec::new();
+    for path in crl_files {
+        crls.extend(crate::config::read_crl_file(path)?);
+    }
+    let revocation = RevocationConfig { crls, staple_hosts };
+    // Surface unparseable CRLs here rather than when the client is built
+    let checked = ClientConfig { revocation: Some(revocation.clone()), ..config.clone() };
+    let provider = std::sync::Arc::new(rustls::crypto::ring::default_provider());
+    crate::client::custom_verifier(&checked, provider)
+        .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid crls: {}", e)))?;
+    Ok(revocation)
+}
+
+/// [`revocation_config`], for builds without rustls, which can't check
+/// revocation
+#[cfg(not(feature = "rustls"))]
+fn revocation_config(
+    _config: &ClientConfig,
+    _crl_files: &[std::path::PathBuf],
+    _staple_hosts: Vec<String>,
+) -> PyResult<RevocationConfig> {
+    Err(pyo3::exceptions::PyValueError::new_err("crls and require_ocsp_staple require the rustls TLS backend"))
+}
+
+/// Parse `pins`: a dict mapping each pinned host to the pins it may
+/// present, such as `{"example.com": ["sha256/..."]}`
+fn extract_pins(pins: &PyDict) -> PyResult<PinConfig> {
+    let mut config = PinConfig::default();
+    for (host, host_pins) in pins {
+        let host: &str = host.extract()?;
+        let host_pins = host_pins.extract::<Vec<&str>>()?.into_iter().map(parse_pin).collect::<Result<Vec<_>, _>>()?;
+        if host_pins.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!("Invalid pins: no pins for {}", host)));
+        }
+        config.hosts.insert(host.trim_end_matches('.').to_ascii_lowercase(), host_pins);
+    }
+    Ok(config)
+}
+
+/// Parse `dns_serve_stale`: `True` for the default staleness limit, or a
+/// limit in seconds; `False`/`None` disable it
+fn extract_serve_stale(value: &PyAny) -> PyResult<Option<Duration>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    if let Ok(enabled) = value.downcast::<PyBool>() {
+        return Ok(enabled.is_true().then_some(DEFAULT_MAX_STALE));
+    }
+    let seconds: f64 = value.extract()?;
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(max_stale) if !max_stale.is_zero() => Ok(Some(max_stale)),
+        _ => Err(pyo3::exceptions::PyValueError::new_err(format!(
+            "Invalid dns_serve_stale: {} (expected True or a positive number of seconds)",
+            seconds
+        ))),
+    }
+}
+
+/// Parse a DNS cache TTL bound in seconds; `None` leaves it unset
+fn extract_dns_ttl(option: &str, value: &PyAny) -> PyResult<Option<Duration>> {
+    if value.is_none() {
+        return Ok(None);
+    }
+    Ok(Some(parse_client_timeout(option, value.extract()?)?))
+}
+
+/// Build `SocketOptions` from a dict such as `{"nodelay": True, "dscp": 46}`
+///
+/// Durations are in seconds. Options unavailable on this platform raise
+/// `ValueError` rather than being silently ignored.
+pub fn extract_socket_options(options: &PyDict) -> PyResult<SocketOptions> {
+    let mut socket_options = SocketOptions::default();
+    let seconds = |value: &PyAny| -> PyResult<Duration> {
+        Duration::try_from_secs_f64(value.extract()?)
+            .map_err(|e| pyo3::exceptions::PyValueError::new_err(format!("Invalid duration: {}", e)))
+    };
+
+    for (key, value) in options {
+        let key: &str = key.extract()?;
+        if !SUPPORTED_OPTIONS.contains(&key) {
+            return Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Unsupported socket option on this platform: {} (supported: {})",
+                key,
+                SUPPORTED_OPTIONS.join(", ")
+            )));
+        }
+        match key {
+            "nodelay" => socket_options.nodelay = Some(value.extract()?),
+            "keepalive" => socket_options.keepalive = Some(seconds(value)?),
+            "keepalive_interval" => socket_options.keepalive_interval = Some(seconds(value)?),
+            "keepalive_retries" => socket_options.keepalive_retries = Some(value.extract()?),
+            "user_timeout" => socket_options.user_timeout = Some(seconds(value)?),
+            "interface" => socket_options.interface = Some(value.extract()?),
+            "local_address" => {
+                socket_options.local_address = Some(parse_local_address(value.extract()?)?);
+            }
+            "dscp" => socket_options.dscp = Some(check_dscp(value.extract()?)?),
+            _ => unreachable!("{} is listed in SUPPORTED_OPTIONS", key),
+        }
+    }
+
+    Ok(socket_options)
+}
+
+/// httpcore's byte-string form of an HTTP version (e.g. `b"HTTP/2"`)
+pub fn http_version_bytes(version: http::Version) -> &'static [u8] {
+    match version {
+        http::Version::HTTP_09 => b"HTTP/0.9",
+        http::Version::HTTP_10 => b"HTTP/1.0",
+        http::Version::HTTP_2 => b"HTTP/2",
+        http::Version::HTTP_3 => b"HTTP/3",
+        _ => b"HTTP/1.1",
+    }
+}
+
+/// Convert an extension value back to Python
+///
+/// Objects and arrays become dicts and lists, converted recursively, so
+/// nested extensions round-trip (tuples come back as lists).
+pub fn json_to_python(py: Python, value: &serde_json::Value) -> PyObject {
+    match value {
+        serde_json::Value::Null => py.None(),
+        serde_json::Value::Bool(b) => b.into_py(py),
+        serde_json::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                i.into_py(py)
+            } else if let Some(f) = n.as_f64() {
+                f.into_py(py)
+            } else {
+                py.None()
+            }
+        }
+        serde_json::Value::String(s) => s.into_py(py),
+        serde_json::Value::Array(items) => {
+            PyList::new(py, items.iter().map(|item| json_to_python(py, item))).into_py(py)
+        }
+        serde_json::Value::Object(object) => {
+            let dict = PyDict::new(py);
+            for (key, item) in object {
+                dict.set_item(key, json_to_python(py, item)).expect("setting a str key on a new dict cannot fail");
+            }
+            dict.into_py(py)
+        }
+    }
+}
+
 /// Create Python response object from Rust response
+#[allow(clippy::too_many_arguments)]
 pub fn create_response_object(
     py: Python,
     status: u16,
+    http_version: http::Version,
     headers: reqwest::header::HeaderMap,
     content: Option<PyObject>,
     stream: Option<PyObject>,
@@ -133,71 +803,114 @@ pub fn create_response_object(
     // Import httpcore Response class
     let httpcore = py.import("httpcore")?;
     let response_class = httpcore.getattr("Response")?;
-    
+
     // Convert headers
     let py_headers = convert_headers_to_python(&headers, py)?;
-    
+
     // Create response kwargs
     let kwargs = PyDict::new(py);
     kwargs.set_item("status", status)?;
     kwargs.set_item("headers", py_headers)?;
-    
+
     if let Some(content) = content {
         kwargs.set_item("content", content)?;
     }
-    
+
+    // httpcore accepts both buffered bytes and (async) iterables via `content`
     if let Some(stream) = stream {
-        kwargs.set_item("stream", stream)?;
+        kwargs.set_item("content", stream)?;
     }
-    
+
+    let py_extensions = PyDict::new(py);
     if let Some(ext) = extensions {
-        let py_extensions = PyDict::new(py);
         for (key, value) in ext {
-            let py_value = match value {
-                serde_json::Value::Null => py.None(),
-                serde_json::Value::Bool(b) => b.into_py(py),
-                serde_json::Value::Number(n) => {
-                    if let Some(i) = n.as_i64() {
-                        i.into_py(py)
-                    } else if let Some(f) = n.as_f64() {
-                        f.into_py(py)
-                    } else {
-                        py.None()
-                    }
-                }
-                serde_json::Value::String(s) => s.into_py(py),
-                _ => py.None(),
-            };
-            py_extensions.set_item(key, py_value)?;
+            py_extensions.set_item(key, json_to_python(py, &value))?;
         }
-        kwargs.set_item("extensions", py_extensions)?;
     }
-    
+    py_extensions.set_item("http_version", PyBytes::new(py, http_version_bytes(http_version)))?;
+    kwargs.set_item("extensions", py_extensions)?;
+
     // Create and return response object
     let response = response_class.call((), Some(kwargs))?;
     Ok(response.to_object(py))
 }
 
-/// Extract timeout configuration from extensions
-pub fn extract_timeout_from_extensions(extensions: &HashMap<String, serde_json::Value>) -> Option<std::time::Duration> {
-    if let Some(timeout_value) = extensions.get("timeout") {
-        match timeout_value {
-            serde_json::Value::Number(n) => {
-                if let Some(seconds) = n.as_f64() {
-                    if seconds > 0.0 {
-                        return Some(std::time::Duration::from_secs_f64(seconds));
-                    }
-                }
+/// Send `priority` as the RFC 9218 `Priority` header, unless the request
+/// already carries one
+pub fn set_priority_header(priority: Option<Priority>, headers: &mut reqwest::header::HeaderMap) {
+    let Some(priority) = priority else {
+        return;
+    };
+    if let Ok(value) = reqwest::header::HeaderValue::from_str(&priority.header_value()) {
+        headers.entry("priority").or_insert(value);
+    }
+}
+
+/// Decide whether to stream a response body
+///
+/// An explicit `stream` extension always wins. Otherwise, with an
+/// `auto_stream_threshold` configured, bodies larger than the threshold or
+/// of unknown length (no `Content-Length`, or decompressed) are streamed
+/// and the rest buffered. Without a threshold, bodies are buffered.
+pub fn should_stream(requested: Option<bool>, auto_stream_threshold: Option<u64>, content_length: Option<u64>) -> bool {
+    match (requested, auto_stream_threshold) {
+        (Some(requested), _) => requested,
+        (None, Some(threshold)) => content_length.is_none_or(|length| length > threshold),
+        (None, None) => false,
+    }
+}
+
+/// Whether a response with `status` to a `method` request carries a body
+///
+/// Responses to HEAD requests and 1xx, 204 and 304 responses never do
+/// (RFC 9110 §6.4.1), whatever their `Content-Length` says, so their body
+/// isn't read.
+pub fn response_has_body(method: &Method, status: u16) -> bool {
+    *method != Method::HEAD && !matches!(status, 100..=199 | 204 | 304)
+}
+
+/// Whether `status` is a redirect that is followed: 301, 302, 303, 307 or
+/// 308 (300 offers a choice, and 304 and 305 aren't redirects to follow)
+pub fn is_redirect_status(status: u16) -> bool {
+    matches!(status, 301 | 302 | 303 | 307 | 308)
+}
+
+/// Whether following a `status` redirect of a `method` request changes it
+/// to a `GET`, dropping its body
+///
+/// As reqwest follows them, a 301, 302 or 303 turns a `POST` (or any
+/// method but `GET` and `HEAD`) into a bodiless `GET`, and a 307 or 308
+/// keeps the method and body.
+pub fn requires_body_after_redirect(method: &Method, status: u16) -> bool {
+    matches!(status, 301..=303) && !matches!(*method, Method::GET | Method::HEAD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redirect_statuses() {
+        for status in [301, 302, 303, 307, 308] {
+            assert!(is_redirect_status(status), "{}", status);
+        }
+        for status in [200, 300, 304, 305, 306, 309, 399] {
+            assert!(!is_redirect_status(status), "{}", status);
+        }
+    }
+
+    #[test]
+    fn redirects_changing_post_to_get() {
+        for status in [301, 302, 303] {
+            assert!(requires_body_after_redirect(&Method::POST, status), "{}", status);
+            assert!(!requires_body_after_redirect(&Method::GET, status), "{}", status);
+            assert!(!requires_body_after_redirect(&Method::HEAD, status), "{}", status);
+        }
+        // 307 and 308 keep the method and body
+        for status in [307, 308] {
+            for method in [Method::GET, Method::POST, Method::HEAD] {
+                assert!(!requires_body_after_redirect(&method, status), "{} {}", method, status);
             }
-            _ => {}
         }
     }
-    None
 }
-
-/// Check if streaming is requested in extensions
-pub fn is_streaming_requested(extensions: &HashMap<String, serde_json::Value>) -> bool {
-    extensions.get("stream")
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false)
-} 
\ No newline at end of file
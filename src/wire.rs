@@ -0,0 +1,277 @@
+//! Capture of a single exchange for the `capture_wire` extension, for
+//! debugging what a request looked like on the wire
+//!
+//! reqwest doesn't expose its connections, so the heads aren't tapped from
+//! the socket. The request head is rebuilt from the request as the client
+//! sends it (signed, with the middleware's headers) plus what reqwest and
+//! hyper add to it: the default `Accept` and `User-Agent`, `Host`, and the
+//! body's `Content-Length` or chunked `Transfer-Encoding`. The response
+//! head is rebuilt from the status and headers hyper parsed, which keeps
+//! neither the case of header names nor the reason phrase. Over HTTP/2 the
+//! heads are the logical header blocks, pseudo-headers first; the frames
+//! carrying them aren't visible.
+
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use http::Extensions;
+use http_body_util::BodyExt;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_LENGTH, COOKIE, HOST, PROXY_AUTHORIZATION,
+    SET_COOKIE, TRANSFER_ENCODING, USER_AGENT,
+};
+use reqwest::{Method, Request, Response, ResponseBuilderExt, Url, Version};
+use reqwest_middleware::{Middleware, Next, Result};
+
+/// Headers whose values are redacted unless the request sets `unsafe_capture`
+pub const SECRET_HEADERS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+/// Headers HTTP/2 has no place for, which hyper drops
+const CONNECTION_HEADERS: [&str; 5] = ["connection", "keep-alive", "proxy-connection", "transfer-encoding", "upgrade"];
+
+/// What a request's `capture_wire` and `unsafe_capture` extensions ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireCapture {
+    /// How many leading bytes of each body to keep
+    pub body_bytes: usize,
+    /// Keep credential header values rather than redacting them
+    pub unsafe_capture: bool,
+}
+
+/// How hyper frames a request body
+#[derive(Debug, Clone, Copy)]
+enum BodyLength {
+    Empty,
+    Known(u64),
+    Streamed,
+}
+
+/// A request captured on its way out, completed by its response
+#[derive(Debug)]
+pub struct PendingWire {
+    capture: WireCapture,
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+    body_length: BodyLength,
+    body: Bytes,
+}
+
+/// A captured exchange, carried in the response's extensions until it is
+/// attached to the Python response
+#[derive(Debug, Clone)]
+pub struct WireRecord {
+    pub version: Version,
+    pub request_head: Bytes,
+    pub response_head: Bytes,
+    /// The leading bytes of each body, if any were asked for
+    pub body_bytes: usize,
+    pub request_body: Bytes,
+    response_body: Arc<Mutex<Vec<u8>>>,
+}
+
+impl WireRecord {
+    /// The leading bytes of the response body read so far
+    pub fn response_body(&self) -> Bytes {
+        Bytes::copy_from_slice(&self.response_body.lock().unwrap())
+    }
+}
+
+impl WireCapture {
+    /// Capture `request` as the client sends it, with `user_agent` being
+    /// the client's default
+    ///
+    /// A streamed body isn't read ahead, so none of it is kept.
+    pub fn record(self, request: &Request, user_agent: &str) -> PendingWire {
+        let mut headers = request.headers().clone();
+        // reqwest's default headers, in the order it adds them
+        headers.entry(ACCEPT).or_insert(HeaderValue::from_static("*/*"));
+        if let Ok(user_agent) = HeaderValue::from_str(user_agent) {
+            headers.entry(USER_AGENT).or_insert(user_agent);
+        }
+        let (body_length, body) = match request.body().map(|body| body.as_bytes()) {
+            None | Some(Some([])) => (BodyLength::Empty, Bytes::new()),
+            Some(Some(bytes)) => (
+                BodyLength::Known(bytes.len() as u64),
+                Bytes::copy_from_slice(&bytes[..bytes.len().min(self.body_bytes)]),
+            ),
+            Some(None) => (BodyLength::Streamed, Bytes::new()),
+        };
+        PendingWire {
+            capture: self,
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers,
+            body_length,
+            body,
+        }
+    }
+}
+
+impl PendingWire {
+    /// Capture `response`'s head and keep it, with the request's, in its
+    /// extensions as a [`WireRecord`]; its body is kept as it is read
+    pub fn finish(self, response: Response) -> Response {
+        let version = response.version();
+        let http2 = version >= Version::HTTP_2;
+        let request_head = if http2 { self.request_block() } else { self.request_head() };
+        let status = response.status();
+        let mut response_head = if http2 {
+            format!(":status: {}\r\n", status.as_u16())
+        } else {
+            format!("{:?} {} {}\r\n", version, status.as_u16(), status.canonical_reason().unwrap_or(""))
+        }
+        .into_bytes();
+        self.write_headers(response.headers(), &mut response_head);
+        let record = WireRecord {
+            version,
+            request_head: request_head.into(),
+            response_head: response_head.into(),
+            body_bytes: self.capture.body_bytes,
+            request_body: self.body,
+            response_body: Arc::new(Mutex::new(Vec::new())),
+        };
+        let mut response = match record.body_bytes {
+            0 => response,
+            limit => keep_body(response, record.response_body.clone(), limit),
+        };
+        response.extensions_mut().insert(record);
+        response
+    }
+
+    /// The HTTP/1.1 head hyper writes: the request line in origin form,
+    /// then the headers in order, with `Host` and the body's framing added
+    /// last as hyper adds them
+    fn request_head(&self) -> Vec<u8> {
+        let mut headers = self.headers.clone();
+        if let Ok(host) = HeaderValue::from_str(&authority(&self.url)) {
+            headers.entry(HOST).or_insert(host);
+        }
+        let framed = headers.contains_key(CONTENT_LENGTH) || headers.contains_key(TRANSFER_ENCODING);
+        match self.body_length {
+            BodyLength::Empty => {
+                headers.remove(TRANSFER_ENCODING);
+            }
+            BodyLength::Known(length) if !framed => {
+                headers.insert(CONTENT_LENGTH, HeaderValue::from(length));
+            }
+            // hyper assumes these methods have no body rather than send an
+            // empty chunked one
+            BodyLength::Streamed if !framed && !matches!(self.method, Method::GET | Method::HEAD | Method::CONNECT) => {
+                headers.insert(TRANSFER_ENCODING, HeaderValue::from_static("chunked"));
+            }
+            _ => {}
+        }
+
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, origin_form(&self.url)).into_bytes();
+        self.write_headers(&headers, &mut head);
+        head
+    }
+
+    /// The HTTP/2 header block hyper sends: the pseudo-headers, then the
+    /// headers without the connection-specific ones
+    fn request_block(&self) -> Vec<u8> {
+        let mut headers = self.headers.clone();
+        for name in CONNECTION_HEADERS {
+            headers.remove(name);
+        }
+        if let BodyLength::Known(length) = self.body_length {
+            headers.entry(CONTENT_LENGTH).or_insert(HeaderValue::from(length));
+        }
+
+        let mut block = format!(
+            ":method: {}\r\n:scheme: {}\r\n:authority: {}\r\n:path: {}\r\n",
+            self.method,
+            self.url.scheme(),
+            authority(&self.url),
+            origin_form(&self.url)
+        )
+        .into_bytes();
+        self.write_headers(&headers, &mut block);
+        block
+    }
+
+    /// Append `headers` as `name: value` lines and the blank line ending
+    /// the head, redacting credentials unless `unsafe_capture` is set
+    fn write_headers(&self, headers: &HeaderMap, head: &mut Vec<u8>) {
+        for (name, value) in headers {
+            head.extend_from_slice(name.as_str().as_bytes());
+            head.extend_from_slice(b": ");
+            if !self.capture.unsafe_capture && SECRET_HEADERS.contains(name) {
+                head.extend_from_slice(b"[redacted]");
+            } else {
+                head.extend_from_slice(value.as_bytes());
+            }
+            head.extend_from_slice(b"\r\n");
+        }
+        head.extend_from_slice(b"\r\n");
+    }
+}
+
+/// `url`'s host, with its port unless it is the scheme's default
+pub fn authority(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+/// The path and query of `url`, as a request line names them
+pub fn origin_form(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// Keep the first `limit` bytes of `response`'s body in `kept` as it is
+/// read, leaving its length and trailers as they were
+fn keep_body(response: Response, kept: Arc<Mutex<Vec<u8>>>, limit: usize) -> Response {
+    let url = response.url().clone();
+    let (parts, body) = http::Response::<reqwest::Body>::from(response).into_parts();
+    let body = body.map_frame(move |frame| {
+        if let Some(chunk) = frame.data_ref() {
+            let mut kept = kept.lock().unwrap();
+            let room = limit.saturating_sub(kept.len()).min(chunk.len());
+            kept.extend_from_slice(&chunk[..room]);
+        }
+        frame
+    });
+    let mut kept_response = http::Response::builder()
+        .url(url)
+        .body(reqwest::Body::wrap(body))
+        .expect("a response with only a URL is valid");
+    *kept_response.status_mut() = parts.status;
+    *kept_response.version_mut() = parts.version;
+    *kept_response.headers_mut() = parts.headers;
+    kept_response.extensions_mut().extend(parts.extensions);
+    kept_response.into()
+}
+
+/// Captures requests that carry a [`WireCapture`] in their extensions
+///
+/// Innermost in the stack, so it sees each attempt as signed and sent, and
+/// each response before it is decoded.
+#[derive(Debug, Clone)]
+pub struct WireCaptureMiddleware {
+    user_agent: String,
+}
+
+impl WireCaptureMiddleware {
+    pub fn new(user_agent: String) -> Self {
+        Self { user_agent }
+    }
+}
+
+#[async_trait::async_trait]
+impl Middleware for WireCaptureMiddleware {
+    async fn handle(&self, req: Request, extensions: &mut Extensions, next: Next<'_>) -> Result<Response> {
+        let Some(capture) = extensions.get::<WireCapture>().copied() else {
+            return next.run(req, extensions).await;
+        };
+        let pending = capture.record(&req, &self.user_agent);
+        let response = next.run(req, extensions).await?;
+        Ok(pending.finish(response))
+    }
+}